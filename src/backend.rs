@@ -0,0 +1,11 @@
+//! Chain data from the block explorer.
+//!
+//! The async functions share one rate-limited client; the `_request` functions block on them.
+
+pub use crate::blockchain_info::{ChainBackend, TestnetBackend, testnet_status_request, testnet_address_request, testnet_transaction_request,
+    testnet_utxo_request, testnet_broadcast_transaction, testnet_address_history, parse_satoshis};
+pub use crate::blockchain_info::{fetch, block_on, testnet_status, testnet_address, testnet_transaction, testnet_utxos, testnet_history, testnet_history_page, testnet_wallet_history_page, testnet_full_history, testnet_send, HISTORY_PAGE_SIZE};
+pub use crate::blockchain_status::BlockchainStatus;
+pub use crate::blockchain_address::BlockchainAddress;
+pub use crate::blockchain_transaction::BlockchainTransaction;
+pub use crate::blockchain_utxo::UTXO;
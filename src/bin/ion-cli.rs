@@ -0,0 +1,745 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use secp256k1::rand::rngs::OsRng;
+use secp256k1::{Secp256k1, SecretKey, PublicKey};
+
+use ion_wallet::backend::*;
+use ion_wallet::storage::*;
+use ion_wallet::traits::*;
+use ion_wallet::transaction::*;
+use ion_wallet::wallet::*;
+use ion_wallet::will::*;
+use ion_wallet::WalletError;
+
+//command line interface to the same wallet files the GUI uses
+#[derive(Parser)]
+#[command(name = "ion-cli", version, about = "ION Wallet")]
+struct Cli{
+    /// Password of the encrypted wallet files
+    #[arg(long, env = "ION_PASSWORD", global = true, hide_env_values = true)]
+    password: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command{
+    /// Create a new wallet key and wallet file
+    Init,
+    /// Restore a wallet from its hex secret key
+    Restore{
+        secret_key: String,
+    },
+    /// Create, import and export watch-only wallets, which can see balances, history and wills but never sign
+    Watch{
+        #[command(subcommand)]
+        command: WatchCommand,
+    },
+    /// Refresh and show the wallet balance
+    Balance,
+    /// Show the wallet's transactions, newest first
+    History{
+        /// one of a watch-only wallet's addresses, instead of all of them
+        #[arg(long)]
+        address: Option<String>,
+        /// transactions per page
+        #[arg(long, default_value_t = HISTORY_PAGE_SIZE)]
+        count: u64,
+        #[arg(long, default_value_t = 1)]
+        page: u64,
+    },
+    /// Build and sign a transaction, predicting the will parts for the wallet's change
+    Send{
+        /// recipient address; repeat for a batch, each with its own --amount
+        #[arg(long = "to", required = true)]
+        to: Vec<String>,
+        /// satoshis, or max for everything left after the other recipients and the fee
+        #[arg(long = "amount", required = true)]
+        amount: Vec<SendAmount>,
+        #[arg(long)]
+        fee: u64,
+        /// take the fee out of this recipient's amount, counting from 1
+        #[arg(long)]
+        subtract_fee_from: Option<usize>,
+        /// hex data to put on chain in an OP_RETURN output, up to 80 bytes
+        #[arg(long)]
+        data: Option<String>,
+        #[arg(long)]
+        broadcast: bool,
+        /// txid:vout of a coin to spend; when given, exactly these coins are spent
+        #[arg(long = "coin")]
+        coins: Vec<String>,
+    },
+    /// List, freeze and unfreeze the wallet's coins
+    Coins{
+        #[command(subcommand)]
+        command: CoinCommand,
+    },
+    /// Create, inspect and act on wills
+    Will{
+        #[command(subcommand)]
+        command: WillCommand,
+    },
+    /// Write the whole transaction history for accounting, as JSON when the file ends in .json and CSV otherwise
+    Export{
+        file: String,
+        /// CSV of date,price lines for fiat columns
+        #[arg(long)]
+        prices: Option<String>,
+        /// currency of the price file, when its header does not name it
+        #[arg(long)]
+        currency: Option<String>,
+    },
+    /// Label transactions, addresses and coins, and import or export BIP329 label files
+    Label{
+        #[command(subcommand)]
+        command: LabelCommand,
+    },
+    /// Decode a serialized transaction
+    Decode{
+        transaction_hex: String,
+    },
+    /// Show the output descriptors of the wallet and its will vaults, or check a descriptor and list its addresses
+    Descriptor{
+        /// a descriptor to check, with or without its checksum
+        descriptor: Option<String>,
+        /// addresses to list of a descriptor ending in a wildcard
+        #[arg(long, default_value_t = 5)]
+        count: u32,
+    },
+    /// Compile a spending policy, like or(pk(A),and(older(52560),pk(B))), to miniscript and show its script and address
+    Policy{
+        policy: String,
+    },
+    /// Show a script, like a vault's witness script, as opcodes
+    Disassemble{
+        script_hex: String,
+    },
+    /// Sign a message, or a file like a will package, with the wallet key
+    SignMessage{
+        message: Option<String>,
+        /// sign the contents of this file instead of a message
+        #[arg(long)]
+        file: Option<String>,
+        /// one of the key's addresses, the wallet's address by default; a P2WPKH address gives a BIP322 signature
+        #[arg(long)]
+        address: Option<String>,
+    },
+    /// Check a BIP137 or BIP322 signature of a message or file by an address, or by a guardian's public key
+    VerifyMessage{
+        signer: String,
+        signature: String,
+        message: Option<String>,
+        /// check the signature of this file instead of a message
+        #[arg(long)]
+        file: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum WillCommand{
+    /// Create a will, replacing the current one
+    Create{
+        /// name:address:percent[:locktime_blocks]
+        #[arg(long = "inheritor", required = true)]
+        inheritors: Vec<String>,
+        /// name:pubkey, or just name for a guardian without a key
+        #[arg(long = "guardian")]
+        guardians: Vec<String>,
+        /// guardian signatures required to revoke
+        #[arg(long, default_value_t = 0)]
+        threshold: u8,
+        /// default redemption period in blocks, at most 65535
+        #[arg(long, default_value_t = DEFAULT_LOCKTIME_BLOCKS, value_parser = clap::value_parser!(u32).range(1..=MAX_LOCKTIME_BLOCKS as i64))]
+        locktime: u32,
+        /// hex data for an OP_RETURN output of the will initiation, up to 80 bytes
+        #[arg(long, conflicts_with = "commit_file")]
+        data: Option<String>,
+        /// put the sha256 of this file, like the will document, in an OP_RETURN output of the will initiation
+        #[arg(long)]
+        commit_file: Option<String>,
+        /// the policy of the vaults, using parent, guardians, single_use and delay, like or(parent,and(delay,single_use));
+        /// the standard vault when left out
+        #[arg(long)]
+        policy: Option<String>,
+    },
+    /// Show the will and its state on chain
+    Show,
+    /// Copy the will parts files into a directory
+    Export{
+        dir: String,
+    },
+    /// Mark the latest will parts as given to every inheritor and guardian
+    Distributed,
+    /// Broadcast the will initiation from an inheritor's will parts file
+    Initiate{
+        #[arg(long, default_value = "will_parts(Child).json")]
+        package: String,
+    },
+    /// Broadcast the will redemptions from an inheritor's will parts file once they are valid
+    Redeem{
+        #[arg(long, default_value = "will_parts(Child).json")]
+        package: String,
+        /// keep checking every minute until every redemption is done
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Broadcast the will revocation from a guardian's will parts file, or the wallet's own when no file is given
+    Revoke{
+        #[arg(long)]
+        package: Option<String>,
+    },
+}
+
+fn main(){
+    if let Err(e) = run(){
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+//runs the command given on the command line
+fn run() -> Result<(), WalletError>{
+    let cli = Cli::parse();
+    let password = || cli.password.clone().ok_or(WalletError::Policy(String::from("A password is required: use --password or ION_PASSWORD")));
+
+    match cli.command{
+        Command::Init => {
+            let (secret_key, _) = Secp256k1::new().generate_keypair(&mut OsRng);
+            let wallet = create_wallet(secret_key, &password()?)?;
+            println!("Address: {}", wallet.address);
+            Ok(())
+        }
+        Command::Restore{secret_key} => {
+            let secret_key = SecretKey::from_str(secret_key.trim()).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")))?;
+            let wallet = create_wallet(secret_key, &password()?)?;
+            println!("Address: {}", wallet.address);
+            Ok(())
+        }
+        Command::Watch{command} => watch(command, &password()?),
+        Command::Balance => {
+            let password = password()?;
+            let mut info = read_wallet(&password)?;
+            let cache = cache(&password);
+            info.value = 0;
+            for address in info.addresses().iter(){
+                info.value += match &cache{
+                    Some(cache) => block_on(cache.sync(address))?.balance,
+                    None => parse_satoshis(&testnet_address_request(address)?.balance)?
+                };
+            }
+            write_wallet(info.clone(), &password)?;
+            println!("{} sats ({} BTC)", info.value, info.value as f64 / 100000000.0);
+            Ok(())
+        }
+        Command::History{address, count, page} => {
+            let password = password()?;
+            let info = read_wallet(&password)?;
+            let addresses = match address{
+                Some(address) if !info.addresses().contains(&address) => return Err(WalletError::Policy(format!("The wallet does not watch {}", address))),
+                Some(address) => vec![address],
+                None => info.addresses()
+            };
+            let mut history = match cache(&password){
+                Some(cache) => {
+                    for address in addresses.iter(){
+                        block_on(cache.sync(address))?;
+                    }
+                    cache.history_page(&addresses, page, count)?
+                }
+                None => block_on(testnet_wallet_history_page(&addresses, page, count))?
+            };
+            mark_will_transactions(&mut history.transactions, &known_wills(&info));
+            for transaction in history.transactions.iter(){
+                let direction = if transaction.is_sent() { "Sent to" } else { "Received from" };
+                let height = match transaction.block_height{
+                    Some(height) => format!("block {}, {} confirmations", height, transaction.confirmations),
+                    None => String::from("pending")
+                };
+                let counterparties: Vec<String> = transaction.counterparties.iter().map(|address| match info.labels.address(address){
+                    Some(label) => format!("{} ({})", label, address),
+                    None => address.clone()
+                }).collect();
+                println!("{}  {:+} sats (fee {})  {} {}  {}", transaction.date(), transaction.net, transaction.fee, direction, counterparties.join(", "), height);
+                println!("    {}{}{}", transaction.txid,
+                    transaction.will.map(|will| format!("  [{}]", will.description())).unwrap_or_default(),
+                    info.labels.transaction(&transaction.txid).map(|label| format!("  {}", label)).unwrap_or_default());
+            }
+            println!("Page {} of {}", history.page, history.total_pages);
+            Ok(())
+        }
+        Command::Send{to, amount, fee, subtract_fee_from, data, broadcast, coins} => {
+            let password = password()?;
+            let mut info = read_wallet(&password)?;
+            let cache = cache(&password);
+            let mut selected: Vec<Outpoint> = Vec::new();
+            for coin in coins.iter(){
+                selected.push(parse_outpoint(coin)?);
+            }
+            let coins = CoinControl::new(info.labels.frozen(), selected);
+            if to.len() != amount.len(){
+                return Err(WalletError::Policy(format!("{} recipients but {} amounts", to.len(), amount.len())))
+            }
+            let mut payment = Payment::new(to.iter().zip(amount).map(|(address, amount)| Recipient::new(address, amount)).collect(), fee);
+            if let Some(recipient) = subtract_fee_from{
+                payment = payment.with_fee_from(recipient.checked_sub(1).ok_or(WalletError::Policy(String::from("Recipients are counted from 1")))?);
+            }
+            if let Some(data) = data{
+                payment = payment.with_data(data.to_bytes().map_err(|_| WalletError::Parse(format!("OP_RETURN data is not hex: {}", data)))?);
+            }
+            if payment.is_max(){
+                eprintln!("Sending the maximum: {} sats", max_sendable(&payment, &info.addresses(), &coins)?);
+            }
+            if info.is_watch_only(){
+                if broadcast{
+                    return Err(WalletError::Policy(String::from("A watch-only wallet cannot sign; sign the PSBT with the wallet key, then broadcast it")))
+                }
+                println!("{}", create_payment_psbt(&payment, &info.addresses(), &coins)?.to_base64());
+                return Ok(())
+            }
+            let secret_key = signing_key(&info, &password)?;
+            let transaction = create_transaction(&payment, &info.address, &info.pubkey, secret_key, &coins)?;
+            if info.will.is_some(){
+                predict_will(&mut info, transaction.clone(), secret_key)?;
+                write_wallet(info.clone(), &password)?;
+                record_will(cache.as_deref(), &info)?;
+                println!("Will parts updated for the change of this transaction");
+            }
+            let transaction_hex = transaction.concat().to_string();
+            if broadcast{
+                println!("{}", TestnetBackend.broadcast(&transaction_hex)?);
+            }else{
+                println!("{}", transaction_hex);
+            }
+            Ok(())
+        }
+        Command::Coins{command} => coins(command, &password()?),
+        Command::Will{command} => will(command, password),
+        Command::Export{file, prices, currency} => {
+            let password = password()?;
+            let info = read_wallet(&password)?;
+            //the history syncs through the cache when it can be opened
+            let cache = cache(&password);
+            let mut history = block_on(wallet_full_history(cache.as_deref(), &info.addresses()))?;
+            mark_will_transactions(&mut history, &known_wills(&info));
+            let prices = match prices{
+                Some(path) => {
+                    let prices = CsvPriceFile::open(&path)?;
+                    Some(match currency{
+                        Some(currency) => prices.with_currency(&currency),
+                        None => prices
+                    })
+                }
+                None => None
+            };
+            let rows = export_rows(&history, &info.labels, prices.as_ref().map(|prices| prices as &dyn PriceSource))?;
+            write_history_export(&file, &rows)?;
+            println!("{} transactions written to {}", rows.len(), file);
+            Ok(())
+        }
+        Command::Label{command} => label(command, &password()?),
+        Command::Decode{transaction_hex} => {
+            let transaction = decode_transaction(&transaction_hex)?;
+            println!("txid: {}", transaction.txid);
+            println!("version: {}  locktime: {}  size: {}  vsize: {}", transaction.version, transaction.locktime, transaction.size, transaction.vsize);
+            for (i, input) in transaction.inputs.iter().enumerate(){
+                println!("input {}: {}:{} sequence {:08x}", i, input.outpoint.txid, input.outpoint.vout, input.sequence);
+                for item in input.witness.iter(){
+                    println!("    witness: {}", item.to_string());
+                }
+            }
+            for (i, output) in transaction.outputs.iter().enumerate(){
+                match output.op_return_data(){
+                    Some(data) => println!("output {}: OP_RETURN {}", i, data.to_string()),
+                    None => {
+                        println!("output {}: {} sats to {}", i, output.value, output.locking_script.to_string());
+                        println!("    asm: {}", Script::from_bytes(output.locking_script.clone()).asm());
+                    }
+                }
+            }
+            Ok(())
+        }
+        Command::Descriptor{descriptor: Some(descriptor), count} => {
+            let descriptor = Descriptor::from_str(&descriptor)?;
+            println!("{}", descriptor);
+            for (i, address) in descriptor.addresses(count)?.iter().enumerate(){
+                println!("{}: {}", i, address);
+            }
+            Ok(())
+        }
+        Command::Policy{policy} => {
+            let descriptor = Policy::from_str(&policy)?.descriptor()?;
+            println!("Descriptor: {}", descriptor);
+            if let Some(witness_script) = descriptor.witness_script(0)?{
+                println!("Witness script: {}", witness_script.to_string().to_lowercase());
+                println!("Witness script asm: {}", Script::from_bytes(witness_script).asm());
+            }
+            println!("Address: {}", descriptor.address(0)?);
+            Ok(())
+        }
+        Command::Disassemble{script_hex} => {
+            let script = Script::from_bytes(script_hex.trim().to_bytes().map_err(|e| WalletError::Parse(format!("Invalid script hex [Error: {}]", e)))?);
+            script.instructions()?;
+            println!("{}", script.asm());
+            Ok(())
+        }
+        Command::SignMessage{message, file, address} => {
+            let password = password()?;
+            let info = read_wallet(&password)?;
+            let secret_key = signing_key(&info, &password)?;
+            let address = address.unwrap_or(info.address.clone());
+            println!("Address: {}", address);
+            println!("Signature: {}", sign_message(&secret_key, &address, &message_bytes(message, file)?)?);
+            Ok(())
+        }
+        Command::VerifyMessage{signer, signature, message, file} => {
+            let message = message_bytes(message, file)?;
+            let valid = match PublicKey::from_str(signer.trim()){
+                Ok(pubkey) => verify_message_key(&pubkey, &message, &signature)?,
+                Err(_) => verify_message(&signer, &message, &signature)?
+            };
+            match valid{
+                true => {
+                    println!("The signature is valid");
+                    Ok(())
+                }
+                false => Err(WalletError::Crypto(format!("The signature was not made by {}", signer)))
+            }
+        }
+        Command::Descriptor{descriptor: None, ..} => {
+            let info = read_wallet(&password()?)?;
+            for descriptor in info.descriptors()?{
+                println!("Wallet: {}", descriptor);
+            }
+            if let Some(will) = &info.will{
+                for (redemption, descriptor) in will.redemptions.iter().zip(will.vault_descriptors.iter()){
+                    println!("Vault ({} blocks): {}", redemption.locktime_blocks, descriptor);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn will(command: WillCommand, password: impl Fn() -> Result<String, WalletError>) -> Result<(), WalletError>{
+    match command{
+        WillCommand::Create{inheritors, guardians, threshold, locktime, data, commit_file, policy} => {
+            let password = password()?;
+            let mut info = read_wallet(&password)?;
+            let mut parsed: Vec<Inheritor> = Vec::new();
+            for inheritor in inheritors.iter(){
+                parsed.push(parse_inheritor(inheritor)?);
+            }
+            parsed.validate_shares()?;
+            info.inheritors = parsed;
+            info.guardians = guardians.iter().map(|guardian| match guardian.split_once(':'){
+                Some((name, pubkey)) => Guardian::new(name.to_string(), String::new(), pubkey.to_string()),
+                None => Guardian::new(guardian.to_string(), String::new(), String::new()),
+            }).collect();
+            info.guardian_threshold = threshold;
+            info.locktime = locktime;
+            info.initiation_data = match (data, commit_file){
+                (Some(data), _) => data,
+                (None, Some(path)) => file_commitment(&path)?,
+                (None, None) => String::new()
+            };
+            info.vault_policy = policy.unwrap_or_default();
+            let secret_key = signing_key(&info, &password)?;
+            let cache = cache(&password);
+            create_will(&mut info, secret_key)?;
+            write_wallet(info.clone(), &password)?;
+            record_will(cache.as_deref(), &info)?;
+            println!("Will generation {} written to will_parts(Child).json and will_parts(Guardian).json", info.will_generation());
+            Ok(())
+        }
+        WillCommand::Show => {
+            let info = read_wallet(&password()?)?;
+            for inheritor in info.inheritors.iter(){
+                println!("Inheritor {}: {} gets {} after {} blocks", inheritor.name, inheritor.address, inheritor.share_percent(), inheritor.locktime_blocks(info.locktime));
+            }
+            for guardian in info.guardians.iter(){
+                println!("Guardian {}: {}", guardian.name, if guardian.pubkey.is_empty() { "no key" } else { &guardian.pubkey });
+            }
+            if !info.guardians.pubkeys().is_empty(){
+                println!("Revocation needs {} of {} guardian signatures", info.guardian_threshold, info.guardians.pubkeys().len());
+            }
+            if !info.vault_policy.is_empty(){
+                println!("Vault policy: {}", info.vault_policy);
+            }
+            println!("Generation: {}", info.will_generation());
+            let outdated = info.outdated_holders();
+            if !outdated.is_empty(){
+                println!("Not yet given the latest will parts: {}", outdated.join(", "));
+            }
+            println!("State: {}", will_state(&info, &TestnetBackend)?.description());
+            Ok(())
+        }
+        WillCommand::Export{dir} => {
+            fs::create_dir_all(&dir).map_err(|e| WalletError::Storage(format!("cannot create {} [Error: {}]", dir, e)))?;
+            for file in ["will_parts(Child).json", "will_parts(Guardian).json"]{
+                fs::copy(file, Path::new(&dir).join(file)).map_err(|e| WalletError::Storage(format!("cannot copy {} [Error: {}]", file, e)))?;
+            }
+            println!("Will parts copied to {}", dir);
+            Ok(())
+        }
+        WillCommand::Distributed => {
+            let password = password()?;
+            let mut info = read_wallet(&password)?;
+            info.mark_will_distributed();
+            write_wallet(info, &password)
+        }
+        WillCommand::Initiate{package} => {
+            let claim = Claim::new(WillPackage::parse(&read_will_package(&package)?)?)?;
+            if TestnetBackend.transaction(&claim.initiation.txid)?.is_some(){
+                println!("The will initiation {} has already been broadcast", claim.initiation.txid);
+            }else{
+                println!("{}", claim.broadcast_initiation(&TestnetBackend)?.description());
+            }
+            Ok(())
+        }
+        WillCommand::Redeem{package, watch} => {
+            let claim = Claim::new(WillPackage::parse(&read_will_package(&package)?)?)?;
+            loop{
+                let status = claim.step(&TestnetBackend)?;
+                println!("{}", status.description());
+                if !watch || status.is_final(){
+                    return Ok(())
+                }
+                thread::sleep(Duration::from_secs(60));
+            }
+        }
+        WillCommand::Revoke{package} => {
+            let txid = match package{
+                Some(package) => broadcast_revocation(&GuardianPackage::parse(&read_will_package(&package)?)?, &TestnetBackend)?,
+                None => {
+                    let info = read_wallet(&password()?)?;
+                    let will = info.will.ok_or(WalletError::Policy(String::from("The wallet has no will")))?;
+                    TestnetBackend.broadcast(&will.revocation_hex)?
+                }
+            };
+            println!("Will revocation broadcast: {}", txid);
+            Ok(())
+        }
+    }
+}
+
+//a message given on the command line, or the contents of a file
+fn message_bytes(message: Option<String>, file: Option<String>) -> Result<Vec<u8>, WalletError>{
+    match (message, file){
+        (Some(message), None) => Ok(message.into_bytes()),
+        (None, Some(file)) => fs::read(&file).map_err(|e| WalletError::Storage(format!("cannot read {} [Error: {}]", file, e))),
+        _ => Err(WalletError::Parse(String::from("Give either a message or --file")))
+    }
+}
+
+//the wallet's secret key, refused for a watch-only wallet
+fn signing_key(info: &WalletInfo, password: &str) -> Result<SecretKey, WalletError>{
+    if info.is_watch_only(){
+        return Err(WalletError::Policy(String::from("A watch-only wallet has no key to sign with")))
+    }
+    SecretKey::from_str(&read_keys(password)?).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")))
+}
+
+#[derive(Subcommand)]
+enum WatchCommand{
+    /// Watch an xpub or tpub, an output descriptor, or addresses separated by commas
+    Create{
+        source: String,
+    },
+    /// Import a watch-only copy of a wallet written by watch export, with its will
+    Import{
+        file: String,
+    },
+    /// Write a watch-only copy of this wallet and its will for family or monitoring; it holds no key
+    Export{
+        file: String,
+    },
+    /// Show the watched addresses
+    Show,
+}
+
+fn watch(command: WatchCommand, password: &str) -> Result<(), WalletError>{
+    match command{
+        WatchCommand::Create{source} => {
+            let wallet = create_watch_only_wallet(WatchSource::parse(&source)?, password)?;
+            println!("Watching {} addresses from {}", wallet.watch_addresses.len(), wallet.address);
+            Ok(())
+        }
+        WatchCommand::Import{file} => {
+            let wallet = import_watch_only_wallet(&file, password)?;
+            println!("Watching {}{}", wallet.address, if wallet.will.is_some() { " and its will" } else { "" });
+            Ok(())
+        }
+        WatchCommand::Export{file} => {
+            write_watch_only(&file, &read_wallet(password)?)?;
+            println!("Watch-only wallet written to {}", file);
+            Ok(())
+        }
+        WatchCommand::Show => {
+            let info = read_wallet(password)?;
+            if !info.is_watch_only(){
+                println!("This wallet holds its key");
+            }
+            for address in info.addresses().iter(){
+                println!("{}", address);
+            }
+            Ok(())
+        }
+    }
+}
+
+//the local cache, or none when it cannot be opened, like while the GUI has it open
+fn cache(password: &str) -> Option<Arc<Cache>>{
+    match unlock_cache(password){
+        Ok(cache) => Some(cache),
+        Err(e) => {
+            eprintln!("Working without the local cache: {}", e);
+            None
+        }
+    }
+}
+
+//keeps the wallet's new will generation in the cache
+fn record_will(cache: Option<&Cache>, info: &WalletInfo) -> Result<(), WalletError>{
+    match (cache, &info.will){
+        (Some(cache), Some(will)) => cache.record_will(&info.address, will),
+        _ => Ok(())
+    }
+}
+
+#[derive(Subcommand)]
+enum CoinCommand{
+    /// Show each coin with its label, and whether it is frozen or in the current will
+    List,
+    /// Keep a coin out of sends and will initiations
+    Freeze{
+        /// txid:vout
+        coin: String,
+    },
+    Unfreeze{
+        /// txid:vout
+        coin: String,
+    },
+}
+
+fn coins(command: CoinCommand, password: &str) -> Result<(), WalletError>{
+    let mut info = read_wallet(password)?;
+    match command{
+        CoinCommand::List => {
+            let _ = cache(password);
+            let mut utxos = Vec::new();
+            for address in info.addresses().iter(){
+                utxos.append(&mut wallet_utxos(address)?);
+            }
+            let coins = list_coins(&utxos, &info)?;
+            for coin in coins.iter(){
+                let mut flags: Vec<&str> = Vec::new();
+                if coin.frozen{
+                    flags.push("frozen");
+                }
+                if coin.in_will{
+                    flags.push("in will");
+                }
+                println!("{}:{}  {} sats  {} confirmations  {}{}", coin.outpoint.txid, coin.outpoint.vout, coin.value, coin.confirmations,
+                    coin.label, if flags.is_empty() { String::new() } else { format!("  [{}]", flags.join(", ")) });
+            }
+            if info.will.is_some() && coins.iter().any(|coin| coin.frozen){
+                println!("Frozen coins are left out of the will; recreate it after freezing or unfreezing coins");
+            }
+            Ok(())
+        }
+        CoinCommand::Freeze{coin} => {
+            info.labels.freeze(&parse_outpoint(&coin)?, true);
+            write_wallet(info, password)
+        }
+        CoinCommand::Unfreeze{coin} => {
+            info.labels.freeze(&parse_outpoint(&coin)?, false);
+            write_wallet(info, password)
+        }
+    }
+}
+
+//reads txid:vout
+fn parse_outpoint(spec: &str) -> Result<Outpoint, WalletError>{
+    let (txid, vout) = spec.rsplit_once(':').ok_or(WalletError::Parse(format!("Coin must be txid:vout, got {}", spec)))?;
+    if txid.len() != 64 || txid.to_bytes().is_err(){
+        return Err(WalletError::Parse(format!("Invalid txid: {}", txid)))
+    }
+    let vout = vout.parse::<u32>().map_err(|_| WalletError::Parse(format!("Invalid output index: {}", vout)))?;
+    Ok(Outpoint::new(txid, vout))
+}
+
+#[derive(Subcommand)]
+enum LabelCommand{
+    /// Set a label; an empty label removes it
+    Set{
+        /// tx, addr, pubkey, input, output or xpub
+        label_type: LabelType,
+        /// txid, address, or txid:index for inputs and outputs
+        reference: String,
+        label: String,
+    },
+    /// Show every label
+    List,
+    /// Write the labels, and the inheritors' addresses, to a BIP329 file
+    Export{
+        file: String,
+    },
+    /// Merge the labels of a BIP329 file into the wallet
+    Import{
+        file: String,
+    },
+}
+
+fn label(command: LabelCommand, password: &str) -> Result<(), WalletError>{
+    let mut info = read_wallet(password)?;
+    match command{
+        LabelCommand::Set{label_type, reference, label} => {
+            info.labels.set(label_type, &reference, &label);
+            write_wallet(info, password)
+        }
+        LabelCommand::List => {
+            for label in info.labels.iter(){
+                println!("{} {}: {}", label.label_type, label.reference, label.label);
+            }
+            Ok(())
+        }
+        LabelCommand::Export{file} => {
+            let labels = info.export_labels();
+            write_labels(&file, &labels)?;
+            println!("{} labels written to {}", labels.len(), file);
+            Ok(())
+        }
+        LabelCommand::Import{file} => {
+            let imported = read_labels(&file, &mut info.labels)?;
+            write_wallet(info, password)?;
+            println!("{} labels imported", imported);
+            Ok(())
+        }
+    }
+}
+
+//reads name:address:percent[:locktime_blocks]
+fn parse_inheritor(spec: &str) -> Result<Inheritor, WalletError>{
+    let parts: Vec<&str> = spec.split(':').collect();
+    if parts.len() < 3 || parts.len() > 4{
+        return Err(WalletError::Parse(format!("Inheritor must be name:address:percent[:locktime_blocks], got {}", spec)))
+    }
+    decode_address(parts[1])?;
+    let percent = parts[2].trim_end_matches('%').parse::<f64>().map_err(|_| WalletError::Parse(format!("Invalid percent: {}", parts[2])))?;
+    let share = (percent * TOTAL_SHARES as f64 / 100.0).round();
+    if !(0.0..=TOTAL_SHARES as f64).contains(&share){
+        return Err(WalletError::Policy(format!("Invalid percent: {}", parts[2])))
+    }
+    let locktime = match parts.get(3){
+        Some(locktime) => vault_locktime(locktime.parse::<u32>().map_err(|_| WalletError::Parse(format!("Invalid locktime: {}", locktime)))?)? as u32,
+        None => 0
+    };
+    Ok(Inheritor::new(parts[0].to_string(), parts[1].to_string(), String::new(), share as u16, locktime))
+}
@@ -1,3 +1,5 @@
+use crate::blockchain_transaction::BlockchainTransaction;
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct BlockchainAddress {
@@ -11,5 +13,9 @@ pub struct BlockchainAddress {
     pub unconfirmed_balance: String,
     pub unconfirmed_txs: u64,
     pub txs: u64,
-    pub txids: Vec<String>
+    #[serde(default)]
+    pub txids: Vec<String>,
+    //only with details=txs
+    #[serde(default)]
+    pub transactions: Vec<BlockchainTransaction>,
 }
\ No newline at end of file
@@ -1,167 +1,402 @@
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
 use std::u64;
 
 use dotenv;
+use futures::future::try_join_all;
 use reqwest;
-use tokio;
-use serde_json::Result;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::Semaphore;
 
 use crate::blockchain_status::BlockchainStatus;
 use crate::blockchain_address::BlockchainAddress;
 use crate::blockchain_transaction::BlockchainTransaction;
 use crate::blockchain_utxo::UTXO;
 use crate::wallet_info::*;
+use crate::cache::wallet_cache;
+use crate::error::WalletError;
 
 
 const HOST_ROOT: &str = "https://btcbook.nownodes.io/api/";
 const HOST_ROOT_TESTNET: &str = "https://btcbook-testnet.nownodes.io/api/";
 
-#[tokio::main]
-pub async fn send_request(url: &str) -> String{
-    
-    let client = reqwest::Client::new();
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+//the block explorer rate limits each api key, so only this many requests are in flight at once
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+const TXID_PAGE_SIZE: u64 = 1000;
+pub const HISTORY_PAGE_SIZE: u64 = 20;
 
-    client
-        .get(url)
-        .header("api-key", dotenv::var("API_KEY").expect("No API_KEY found"))
+//one client and runtime for the whole wallet, so connections to the block explorer are reused
+struct Explorer{
+    runtime: Runtime,
+    client: reqwest::Client,
+    permits: Semaphore,
+}
+
+//a runtime or client that cannot be built is kept as the error, so every request reports it instead of panicking
+fn explorer() -> Result<&'static Explorer, WalletError>{
+    static EXPLORER: OnceLock<Result<Explorer, String>> = OnceLock::new();
+    EXPLORER.get_or_init(|| Ok(Explorer{
+        runtime: tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("ion-network")
+            .enable_all()
+            .build()
+            .map_err(|e| format!("cannot start the network runtime [Error: {}]", e))?,
+        client: reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("cannot build the network client [Error: {}]", e))?,
+        permits: Semaphore::new(MAX_CONCURRENT_REQUESTS),
+    })).as_ref().map_err(|e| WalletError::Network(e.clone()))
+}
+
+async fn request(url: String) -> Result<String, WalletError>{
+    let api_key = dotenv::var("API_KEY").map_err(|_| WalletError::Network(String::from("No API_KEY found")))?;
+    let explorer = explorer()?;
+    let _permit = explorer.permits.acquire().await.map_err(|e| WalletError::Network(e.to_string()))?;
+
+    Ok(explorer.client
+        .get(&url)
+        .header("api-key", api_key)
         .send()
-        .await
-        .expect("Failed to get response...")
+        .await?
         .text()
-        .await
-        .expect("Failed to convert payload...")
+        .await?)
+}
+
+//runs the request on the wallet's network runtime, so it can be awaited from any executor, like the GUI's
+pub async fn fetch(url: String) -> Result<String, WalletError>{
+    explorer()?.runtime.spawn(request(url)).await.map_err(|e| WalletError::Network(e.to_string()))?
+}
+
+//waits for network work from code that is not async, like the command line and the watcher threads
+//blocking inside an async runtime would stall or panic it, so from async code it fails and the async version must be awaited
+pub fn block_on<T>(future: impl Future<Output = Result<T, WalletError>>) -> Result<T, WalletError>{
+    if Handle::try_current().is_ok(){
+        return Err(WalletError::Network(String::from("A blocking network request was made from async code")))
+    }
+    explorer()?.runtime.block_on(future)
+}
+
+pub fn send_request(url: &str) -> Result<String, WalletError>{
+    block_on(fetch(url.to_string()))
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(url: String, name: &str) -> Result<T, WalletError>{
+    parse_response(&fetch(url).await?, name)
+}
+
+//reads a JSON answer from the block explorer
+fn parse_response<T: serde::de::DeserializeOwned>(response: &str, name: &str) -> Result<T, WalletError>{
+    serde_json::from_str(response).map_err(|e| WalletError::Parse(format!("cannot parse {} JSON [Error: {}]", name, e)))
+}
+
+//the block explorer gives amounts as strings of satoshis
+pub fn parse_satoshis(value: &str) -> Result<u64, WalletError>{
+    value.parse::<u64>().map_err(|_| WalletError::Parse(format!("Invalid amount of satoshis: {}", value)))
+}
+
+pub async fn blockchain_status() -> Result<BlockchainStatus, WalletError>{
+    fetch_json(HOST_ROOT.to_string(), "Blockchain Status").await
+}
+
+pub async fn blockchain_address(address: &str) -> Result<BlockchainAddress, WalletError>{
+    fetch_json(String::new() + HOST_ROOT + "v2/address/" + address, "Blockchain Address").await
 }
 
-pub fn blockchain_status_request() -> BlockchainStatus{
-    let response = send_request(HOST_ROOT);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Status JSON")
+pub async fn blockchain_transaction(txid: &str) -> Result<BlockchainTransaction, WalletError>{
+    fetch_json(String::new() + HOST_ROOT + "v2/tx/" + txid, "Blockchain Transaction").await
 }
 
-pub fn blockchain_address_request(address: &str) -> BlockchainAddress{
-    let url: String = String::new() + HOST_ROOT + "v2/address/" + address;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Address JSON")
+pub async fn blockchain_utxos(address: &str) -> Result<Vec<UTXO>, WalletError>{
+    fetch_json(String::new() + HOST_ROOT + "v2/utxo/" + address, "Blockchain UTXO").await
 }
 
-pub fn blockchain_transaction_request(txid: &str) -> BlockchainTransaction{
-    let url: String = String::new() + HOST_ROOT + "v2/tx/" + txid;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Transaction JSON")
+pub fn blockchain_status_request() -> Result<BlockchainStatus, WalletError>{
+    block_on(blockchain_status())
 }
 
-pub fn blockchain_utxo_request(address: &str) -> Vec<UTXO>{
-    let url: String = String::new() + HOST_ROOT + "v2/utxo/" + address;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain UTXO JSON")
+pub fn blockchain_address_request(address: &str) -> Result<BlockchainAddress, WalletError>{
+    block_on(blockchain_address(address))
 }
 
-pub fn get_address_balance(address: &str) -> u64{
+pub fn blockchain_transaction_request(txid: &str) -> Result<BlockchainTransaction, WalletError>{
+    block_on(blockchain_transaction(txid))
+}
+
+pub fn blockchain_utxo_request(address: &str) -> Result<Vec<UTXO>, WalletError>{
+    block_on(blockchain_utxos(address))
+}
+
+//sums every transaction of the address, fetching them together rather than one after another
+pub async fn address_balance(address: &str) -> Result<u64, WalletError>{
+    let blockchain_address = blockchain_address(address).await?;
+    let transactions = try_join_all(blockchain_address.txids.iter().map(|txid| blockchain_transaction(txid))).await?;
     let mut address_balance: i64 = 0;
-    let blockchain_address = blockchain_address_request(address);
-    // let mut i = 0;
-    for txid in blockchain_address.txids{
-        let transaction = blockchain_transaction_request(&txid);
-        address_balance += get_transaction_value(transaction, address);
+    for transaction in transactions{
+        address_balance += get_transaction_value(transaction, address)?;
     }
-    address_balance as u64
+    Ok(address_balance as u64)
+}
+
+pub fn get_address_balance(address: &str) -> Result<u64, WalletError>{
+    block_on(address_balance(address))
 }
 
-pub fn get_transaction_value(transaction: BlockchainTransaction, address: &str) -> i64{
-    let address = address.to_string();
+pub fn get_transaction_value(transaction: BlockchainTransaction, address: &str) -> Result<i64, WalletError>{
     let mut amount_transacted: i64 = 0;
     for vin in transaction.vin{
-        if vin.addresses[0] == address {
-            amount_transacted -= vin.value.parse::<i64>().unwrap();
+        if is_address(&vin.addresses, address) {
+            amount_transacted -= parse_satoshis(&vin.value)? as i64;
         }
     }
     for vout in transaction.vout{
-        if vout.addresses[0] == address {
-            amount_transacted += vout.value.parse::<i64>().unwrap();
+        if is_address(&vout.addresses, address) {
+            amount_transacted += parse_satoshis(&vout.value)? as i64;
         }
     }
-    amount_transacted
+    Ok(amount_transacted)
 }
 
+//inputs and outputs without an address, like OP_RETURN outputs, have an empty address list
+fn is_address(addresses: &[String], address: &str) -> bool{
+    addresses.iter().any(|other| other == address)
+}
 
+fn is_wallet_address(addresses: &[String], wallet: &[String]) -> bool{
+    addresses.iter().any(|address| wallet.contains(address))
+}
 
+pub async fn testnet_status() -> Result<BlockchainStatus, WalletError>{
+    fetch_json(HOST_ROOT_TESTNET.to_string(), "Blockchain Status").await
+}
 
+pub async fn testnet_address(address: &str) -> Result<BlockchainAddress, WalletError>{
+    fetch_json(String::new() + HOST_ROOT_TESTNET + "v2/address/" + address, "Blockchain Address").await
+}
 
+//the txids of the address's transactions in blocks from the height on and in the mempool, a page of 1000 at a time
+pub async fn testnet_address_since(address: &str, from_height: u64, page: u64) -> Result<BlockchainAddress, WalletError>{
+    fetch_json(format!("{}v2/address/{}?details=txids&from={}&page={}&pageSize={}", HOST_ROOT_TESTNET, address, from_height, page, TXID_PAGE_SIZE), "Blockchain Address").await
+}
 
-pub fn testnet_status_request() -> BlockchainStatus{
-    let response = send_request(HOST_ROOT_TESTNET);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Status JSON")
+pub async fn testnet_transaction(txid: &str) -> Result<BlockchainTransaction, WalletError>{
+    fetch_json(String::new() + HOST_ROOT_TESTNET + "v2/tx/" + txid, "Blockchain Transaction").await
 }
 
-pub fn testnet_address_request(address: &str) -> BlockchainAddress{
-    let url: String = String::new() + HOST_ROOT_TESTNET + "v2/address/" + address;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Address JSON")
+pub async fn testnet_utxos(address: &str) -> Result<Vec<UTXO>, WalletError>{
+    fetch_json(String::new() + HOST_ROOT_TESTNET + "v2/utxo/" + address, "Blockchain UTXO").await
 }
 
-pub fn testnet_transaction_request(txid: &str) -> BlockchainTransaction{
-    let url: String = String::new() + HOST_ROOT_TESTNET + "v2/tx/" + txid;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain Transaction JSON")
+pub async fn testnet_broadcast(transaction_raw_hex: &str) -> Result<String, WalletError>{
+    fetch(String::new() + HOST_ROOT_TESTNET + "v2/sendtx/" + transaction_raw_hex).await
 }
 
-pub fn testnet_utxo_request(address: &str) -> Vec<UTXO>{
-    let url: String = String::new() + HOST_ROOT_TESTNET + "v2/utxo/" + address;
-    let response = send_request(&url);
-    serde_json::from_str(&response).expect("cannot parse Blockchain UTXO JSON")
+//one page of the address's transactions, newest first; pages start at 1
+pub async fn testnet_history_page(address: &str, page: u64, page_size: u64) -> Result<HistoryPage, WalletError>{
+    let url = format!("{}v2/address/{}?details=txs&page={}&pageSize={}", HOST_ROOT_TESTNET, address, page.max(1), page_size);
+    let blockchain_address: BlockchainAddress = fetch_json(url, "Blockchain Address").await?;
+    let mut transactions: Vec<TransactionHistory> = Vec::new();
+    for transaction in blockchain_address.transactions{
+        transactions.push(get_transaction_history(transaction, &[address.to_string()])?);
+    }
+    Ok(HistoryPage::new(transactions, blockchain_address.page.max(1), blockchain_address.total_pages))
 }
 
-pub fn testnet_broadcast_transaction(transaction_raw_hex: &str) -> String{
-    let url: String = String::new() + HOST_ROOT_TESTNET + "v2/sendtx/" + transaction_raw_hex;
-    let response = send_request(&url);
-    response
+//the n most recent transactions of the address
+pub async fn testnet_history(address: &str, n: usize) -> Result<Vec<TransactionHistory>, WalletError>{
+    Ok(testnet_history_page(address, 1, n as u64).await?.transactions)
 }
 
+//every transaction of the addresses, each once with the net of all of them, newest first
+pub async fn testnet_full_history(addresses: &[String]) -> Result<Vec<TransactionHistory>, WalletError>{
+    let mut transactions: Vec<BlockchainTransaction> = Vec::new();
+    for address in addresses.iter(){
+        let mut page = 1;
+        loop{
+            let url = format!("{}v2/address/{}?details=txs&page={}&pageSize={}", HOST_ROOT_TESTNET, address, page, HISTORY_PAGE_SIZE);
+            let blockchain_address: BlockchainAddress = fetch_json(url, "Blockchain Address").await?;
+            for transaction in blockchain_address.transactions{
+                //a transaction between two of the addresses is listed for both
+                if !transactions.iter().any(|kept| kept.txid == transaction.txid){
+                    transactions.push(transaction);
+                }
+            }
+            if page >= blockchain_address.total_pages{
+                break
+            }
+            page += 1;
+        }
+    }
+    let mut history: Vec<TransactionHistory> = Vec::new();
+    for transaction in transactions{
+        history.push(get_transaction_history(transaction, addresses)?);
+    }
+    sort_newest_first(&mut history);
+    Ok(history)
+}
 
+//one page of the transactions of the addresses, paged by the block explorer when there is only one address
+pub async fn testnet_wallet_history_page(addresses: &[String], page: u64, page_size: u64) -> Result<HistoryPage, WalletError>{
+    match addresses{
+        [address] => testnet_history_page(address, page, page_size).await,
+        _ => Ok(HistoryPage::of(testnet_full_history(addresses).await?, page, page_size))
+    }
+}
 
+pub fn testnet_status_request() -> Result<BlockchainStatus, WalletError>{
+    block_on(testnet_status())
+}
 
-pub fn testnet_address_history(address: &str, n: usize) -> Vec<TransactionHistory>{
-    let mut result: Vec<TransactionHistory> = Vec::new();
-    let blockchain_address = testnet_address_request(address);
-    for i in 0..n{
-        let transaction = testnet_transaction_request(&blockchain_address.txids[i]);
-        let confs = transaction.confirmations;
-        let mut current = get_transaction_history(transaction, address);
-        current.confirmations = confs;
-        result.push(current)
-    }
-    result
+pub fn testnet_address_request(address: &str) -> Result<BlockchainAddress, WalletError>{
+    block_on(testnet_address(address))
 }
 
-pub fn get_transaction_history(transaction: BlockchainTransaction, address: &str) -> TransactionHistory{
-    let address = address.to_string();
-    let mut to_address: String = String::new();
-    let mut to: bool = true;
-    let mut value: u64 = 0;
-    for vin in transaction.vin.clone(){
-        if vin.addresses[0] == address {
-            to = true;
-            if transaction.vout.len() < 3{
-                for vout in transaction.vout.clone(){
-                    to_address = vout.addresses[0].clone();
-                }
-            }else{
-                to_address = format!("{} outputs...", transaction.vout.len());
-            }
-            value = vin.value.parse::<u64>().unwrap();
-            return TransactionHistory::new(to_address, to, value, 0)
+pub fn testnet_transaction_request(txid: &str) -> Result<BlockchainTransaction, WalletError>{
+    block_on(testnet_transaction(txid))
+}
+
+pub fn testnet_utxo_request(address: &str) -> Result<Vec<UTXO>, WalletError>{
+    block_on(testnet_utxos(address))
+}
+
+pub fn testnet_broadcast_transaction(transaction_raw_hex: &str) -> Result<String, WalletError>{
+    block_on(testnet_broadcast(transaction_raw_hex))
+}
+
+pub fn testnet_address_history(address: &str, n: usize) -> Result<Vec<TransactionHistory>, WalletError>{
+    block_on(testnet_history(address, n))
+}
+
+//the transaction from the side of the wallet's addresses: what they received minus what they spent, and who they paid or were paid by
+pub fn get_transaction_history(transaction: BlockchainTransaction, addresses: &[String]) -> Result<TransactionHistory, WalletError>{
+    let mut spent: u64 = 0;
+    let mut received: u64 = 0;
+    for vin in transaction.vin.iter(){
+        if is_wallet_address(&vin.addresses, addresses){
+            spent += parse_satoshis(&vin.value)?;
         }
     }
-    for vout in transaction.vout{
-        if vout.addresses[0] == address {
-            to = false;
-            if transaction.vin.len() < 3{
-                for vin in transaction.vin.clone(){
-                    to_address = vin.addresses[0].clone();
+    for vout in transaction.vout.iter(){
+        if is_wallet_address(&vout.addresses, addresses){
+            received += parse_satoshis(&vout.value)?;
+        }
+    }
+
+    let others: Vec<&Vec<String>> = match spent{
+        0 => transaction.vin.iter().map(|vin| &vin.addresses).collect(),
+        _ => transaction.vout.iter().map(|vout| &vout.addresses).collect(),
+    };
+    let mut counterparties: Vec<String> = Vec::new();
+    for other in others.into_iter().flatten(){
+        if !addresses.contains(other) && !counterparties.contains(other){
+            counterparties.push(other.clone());
+        }
+    }
+
+    let block_height = match transaction.confirmations{
+        0 => None,
+        _ => Some(transaction.block_height as u64),
+    };
+    let fee = parse_satoshis(&transaction.fees)?;
+    Ok(TransactionHistory::new(transaction.txid, counterparties, received as i64 - spent as i64, fee, block_height, transaction.block_time, transaction.confirmations))
+}
+
+//a source of chain data for watching a will, so the will monitor does not depend on one block explorer
+pub trait ChainBackend{
+    //height of the most recent block
+    fn tip_height(&self) -> Result<u64, WalletError>;
+    //the transaction if the backend has seen it in a block or in the mempool
+    fn transaction(&self, txid: &str) -> Result<Option<BlockchainTransaction>, WalletError>;
+    //broadcasts a signed transaction and returns its txid
+    fn broadcast(&self, transaction_hex: &str) -> Result<String, WalletError>;
+}
+
+pub struct TestnetBackend;
+
+impl ChainBackend for TestnetBackend{
+    fn tip_height(&self) -> Result<u64, WalletError>{
+        Ok(testnet_status_request()?.blockbook.best_height)
+    }
+
+    fn transaction(&self, txid: &str) -> Result<Option<BlockchainTransaction>, WalletError>{
+        if txid.is_empty(){
+            return Ok(None)
+        }
+        //always downloaded: the will is watched through whether its outputs are spent and how deep it is, which the
+        //cached copy does not keep up to date
+        let response = send_request(&(String::new() + HOST_ROOT_TESTNET + "v2/tx/" + txid))?;
+        match parse_response::<BlockchainTransaction>(&response, "Blockchain Transaction"){
+            Ok(transaction) => {
+                if let Some(cache) = wallet_cache(){
+                    cache.insert_transaction(&transaction)?;
                 }
-            }else{
-                to_address = format!("{} inputs...", transaction.vin.len());
+                Ok(Some(transaction))
             }
-            value = vout.value.parse::<u64>().unwrap();
+            Err(_) if is_not_found(&response) => Ok(None),
+            Err(e) => Err(blockbook_error(&response).map(WalletError::Network).unwrap_or(e))
         }
     }
-    TransactionHistory::new(to_address, to, value, 0)
-}
\ No newline at end of file
+    fn broadcast(&self, transaction_hex: &str) -> Result<String, WalletError>{
+        block_on(testnet_send(transaction_hex))
+    }
+}
+
+//the message of an error object from blockbook
+fn blockbook_error(response: &str) -> Option<String>{
+    serde_json::from_str::<serde_json::Value>(response).ok()?["error"].as_str().map(String::from)
+}
+
+//blockbook answers an unknown txid with "Transaction '<txid>' not found"; any other error, like a rate limit or a bad
+//api key, says nothing about whether the transaction exists
+fn is_not_found(response: &str) -> bool{
+    blockbook_error(response).is_some_and(|error| error.starts_with("Transaction '") && error.contains("' not found"))
+}
+
+//broadcasts a signed transaction and returns its txid, or the reason the block explorer refused it
+pub async fn testnet_send(transaction_hex: &str) -> Result<String, WalletError>{
+    let response: serde_json::Value = parse_response(&testnet_broadcast(transaction_hex).await?, "Broadcast")?;
+    match (response["result"].as_str(), response["error"]["message"].as_str().or(response["error"].as_str())){
+        (Some(txid), _) => Ok(txid.to_string()),
+        (_, Some(error)) => Err(WalletError::Network(error.to_string())),
+        _ => Err(WalletError::Parse(String::from("cannot parse Broadcast JSON")))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn only_an_unknown_txid_is_not_found(){
+        assert!(is_not_found(r#"{"error":"Transaction 'aa' not found"}"#));
+        assert!(is_not_found(r#"{"error":"Transaction 'aa' not found (No such mempool or blockchain transaction)"}"#));
+        assert!(!is_not_found(r#"{"error":"Too many requests"}"#));
+        assert!(!is_not_found(r#"{"error":"Invalid api-key"}"#));
+        assert!(!is_not_found("<html>502 Bad Gateway</html>"));
+        assert_eq!(blockbook_error(r#"{"error":"Too many requests"}"#), Some(String::from("Too many requests")));
+    }
+
+    #[test]
+    fn block_on_refuses_to_run_inside_a_runtime(){
+        assert_eq!(block_on(async { Ok(1) }), Ok(1));
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let result = runtime.block_on(async { block_on(async { Ok(1) }) });
+        assert!(matches!(result, Err(WalletError::Network(_))));
+    }
+
+    #[test]
+    fn change_to_another_wallet_address_is_not_a_payment(){
+        let transaction: BlockchainTransaction = serde_json::from_str(r#"{"txid":"ab","version":2,"vin":[{"txid":"x","n":0,"addresses":["receive"],"isAddress":true,"value":"1000"}],"vout":[{"value":"600","n":0,"hex":"00","addresses":["bob"],"isAddress":true},{"value":"300","n":1,"hex":"00","addresses":["change"],"isAddress":true}],"blockHeight":100,"confirmations":3,"blockTime":0,"size":1,"vsize":1,"value":"900","valueIn":"1000","fees":"100","hex":"00"}"#).unwrap();
+        let alone = get_transaction_history(transaction.clone(), &[String::from("receive")]).unwrap();
+        assert_eq!(alone.net, -1000);
+        assert_eq!(alone.counterparties, vec![String::from("bob"), String::from("change")]);
+        let wallet = get_transaction_history(transaction, &[String::from("receive"), String::from("change")]).unwrap();
+        assert_eq!(wallet.net, -700);
+        assert_eq!(wallet.counterparties, vec![String::from("bob")]);
+    }
+}
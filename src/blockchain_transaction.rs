@@ -1,4 +1,4 @@
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Vin {
     pub txid: String,
@@ -11,7 +11,7 @@ pub struct Vin {
     // pub hex: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Vout {
     pub value: String,
@@ -22,7 +22,7 @@ pub struct Vout {
     pub is_address: bool
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct BlockchainTransaction {
     pub txid: String,
@@ -0,0 +1,369 @@
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{join, try_join_all};
+use ring::aead::*;
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use secp256k1::SecretKey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::blockchain_info::*;
+use crate::blockchain_transaction::BlockchainTransaction;
+use crate::blockchain_utxo::UTXO;
+use crate::file::{read_keys, read_wallet};
+use crate::traits::*;
+use crate::wallet_info::*;
+use crate::error::WalletError;
+
+//where the caches are kept when ION_CACHE_DIR is not set, one directory per wallet
+const CACHE_DIR: &str = "wallet_cache";
+//blocks under the last synced height that are synced again, in case they were reorganized
+const REORG_DEPTH: u64 = 6;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+//the cache of the wallet unlocked last
+static WALLET_CACHE: Mutex<Option<Arc<Cache>>> = Mutex::new(None);
+
+//local store of the wallet's transactions, coins, block heights and wills, so the wallet works offline
+//and does not download the same transactions again
+//keys and values are encrypted with keys derived from the wallet's secret key
+pub struct Cache{
+    path: String,
+    db: sled::Db,
+    transactions: sled::Tree,
+    addresses: sled::Tree,
+    wills: sled::Tree,
+    key: LessSafeKey,
+    index_key: Vec<u8>,
+}
+
+//what the cache knows about an address as of its last sync
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AddressRecord{
+    pub balance: u64,
+    //newest first, like the block explorer lists them
+    pub txids: Vec<String>,
+    pub utxos: Vec<UTXO>,
+    //height of the chain tip at the last sync
+    pub synced_height: u64,
+}
+
+impl Cache{
+    pub fn open_at(path: &str, secret_key: &SecretKey) -> Result<Cache, WalletError>{
+        Cache::with_secret(path, open_db(path)?, &secret_key.secret_bytes())
+    }
+
+    //a watch-only wallet has no key, so its cache is encrypted with one stretched from the password
+    //with a salt kept in the cache, so the same password gives each cache a different key
+    pub fn open_watch_only_at(path: &str, password: &str) -> Result<Cache, WalletError>{
+        let db = open_db(path)?;
+        let secret = watch_only_secret(&db, password)?;
+        Cache::with_secret(path, db, &secret)
+    }
+
+    fn with_secret(path: &str, db: sled::Db, secret: &[u8]) -> Result<Cache, WalletError>{
+        let key_bytes = [b"ion wallet cache".to_vec(), secret.to_vec()].concat().sha256();
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key_bytes).map_err(|e| WalletError::Crypto(e.to_string()))?;
+        Ok(Cache{
+            path: path.to_string(),
+            transactions: db.open_tree("transactions")?,
+            addresses: db.open_tree("addresses")?,
+            wills: db.open_tree("wills")?,
+            db,
+            key: LessSafeKey::new(key),
+            index_key: [b"ion wallet cache index".to_vec(), secret.to_vec()].concat().sha256(),
+        })
+    }
+
+    pub fn path(&self) -> &str{
+        &self.path
+    }
+
+    //the stored name of a txid or address, so the file does not show which ones the wallet has
+    fn index(&self, name: &str) -> Vec<u8>{
+        [self.index_key.clone(), name.as_bytes().to_vec()].concat().sha256()
+    }
+
+    fn seal<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, WalletError>{
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).map_err(|_| WalletError::Crypto(String::from("cannot generate a nonce")))?;
+        let mut data = serde_json::to_vec(value)?;
+        self.key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data).map_err(|e| WalletError::Crypto(e.to_string()))?;
+        Ok([nonce.to_vec(), data].concat())
+    }
+
+    fn unseal<T: DeserializeOwned>(&self, sealed: &[u8]) -> Result<T, WalletError>{
+        if sealed.len() < NONCE_LEN{
+            return Err(WalletError::Storage(String::from("corrupt cache entry")))
+        }
+        let (nonce, data) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|e| WalletError::Crypto(e.to_string()))?;
+        let mut data = data.to_vec();
+        let plaintext = self.key.open_in_place(nonce, Aad::empty(), &mut data).map_err(|_| WalletError::Crypto(String::from("the cache belongs to another wallet key")))?;
+        serde_json::from_slice(plaintext).map_err(|e| WalletError::Storage(format!("corrupt cache entry [Error: {}]", e)))
+    }
+
+    fn get<T: DeserializeOwned>(&self, tree: &sled::Tree, name: &str) -> Result<Option<T>, WalletError>{
+        match tree.get(self.index(name))?{
+            Some(sealed) => Ok(Some(self.unseal(&sealed)?)),
+            None => Ok(None)
+        }
+    }
+
+    fn insert<T: Serialize>(&self, tree: &sled::Tree, name: &str, value: &T) -> Result<(), WalletError>{
+        tree.insert(self.index(name), self.seal(value)?)?;
+        Ok(())
+    }
+
+    pub fn transaction(&self, txid: &str) -> Result<Option<BlockchainTransaction>, WalletError>{
+        self.get(&self.transactions, &txid.to_lowercase())
+    }
+
+    pub fn insert_transaction(&self, transaction: &BlockchainTransaction) -> Result<(), WalletError>{
+        self.insert(&self.transactions, &transaction.txid.to_lowercase(), transaction)
+    }
+
+    //a transaction that was in a block when it was stored, so its inputs, outputs and block will not change
+    //its confirmations and the spent flags of its outputs are as of when it was stored, so nothing should read them
+    pub fn confirmed_transaction(&self, txid: &str) -> Result<Option<BlockchainTransaction>, WalletError>{
+        Ok(self.transaction(txid)?.filter(|transaction| transaction.confirmations > 0))
+    }
+
+    pub fn address(&self, address: &str) -> Result<AddressRecord, WalletError>{
+        Ok(self.get(&self.addresses, address)?.unwrap_or_default())
+    }
+
+    pub fn insert_utxos(&self, address: &str, utxos: Vec<UTXO>) -> Result<(), WalletError>{
+        let mut record = self.address(address)?;
+        record.utxos = utxos;
+        self.insert(&self.addresses, address, &record)
+    }
+
+    //the transactions of the addresses as of the last sync, each once with the net of all of them, newest first
+    pub fn full_history(&self, addresses: &[String]) -> Result<Vec<TransactionHistory>, WalletError>{
+        let (mut txids, mut synced_height): (Vec<String>, u64) = (Vec::new(), 0);
+        for address in addresses.iter(){
+            let record = self.address(address)?;
+            synced_height = synced_height.max(record.synced_height);
+            for txid in record.txids{
+                if !txids.contains(&txid){
+                    txids.push(txid);
+                }
+            }
+        }
+        let mut transactions: Vec<TransactionHistory> = Vec::new();
+        for txid in txids.iter(){
+            let transaction = self.transaction(txid)?.ok_or(WalletError::Storage(format!("transaction {} is missing from the cache", txid)))?;
+            let mut current = get_transaction_history(transaction, addresses)?;
+            //confirmations as of the last sync rather than when the transaction was stored
+            if let Some(height) = current.block_height{
+                current.confirmations = (synced_height + 1).saturating_sub(height);
+            }
+            transactions.push(current)
+        }
+        sort_newest_first(&mut transactions);
+        Ok(transactions)
+    }
+
+    //a page of the transactions of the addresses as of the last sync, newest first; pages start at 1
+    pub fn history_page(&self, addresses: &[String], page: u64, page_size: u64) -> Result<HistoryPage, WalletError>{
+        Ok(HistoryPage::of(self.full_history(addresses)?, page, page_size))
+    }
+
+    //keeps every will generation made for the address, replacing one with the same generation
+    pub fn record_will(&self, address: &str, will: &WillRecord) -> Result<(), WalletError>{
+        let mut wills = self.wills(address)?;
+        wills.retain(|kept| kept.generation != will.generation);
+        wills.push(will.clone());
+        wills.sort_by_key(|kept| kept.generation);
+        self.insert(&self.wills, address, &wills)
+    }
+
+    //every will generation the cache has seen for the address, oldest first
+    pub fn wills(&self, address: &str) -> Result<Vec<WillRecord>, WalletError>{
+        Ok(self.get(&self.wills, address)?.unwrap_or_default())
+    }
+
+    //true if the cached transaction was confirmed in a block below the height, so a sync from there does not list it again
+    fn settled_before(&self, txid: &str, height: u64) -> Result<bool, WalletError>{
+        Ok(self.confirmed_transaction(txid)?.is_some_and(|transaction| transaction.block_height > 0 && (transaction.block_height as u64) < height))
+    }
+
+    //downloads what changed since the last sync: transactions in recent blocks or the mempool, and the current coins
+    pub async fn sync(&self, address: &str) -> Result<AddressRecord, WalletError>{
+        let previous = self.address(address)?;
+        let tip = testnet_status().await?.blockbook.best_height;
+        let from = previous.synced_height.saturating_sub(REORG_DEPTH);
+        let (first, utxos) = join(testnet_address_since(address, from, 1), testnet_utxos(address)).await;
+        let mut changed = first?;
+        for page in 2..=changed.total_pages{
+            changed.txids.append(&mut testnet_address_since(address, from, page).await?.txids);
+        }
+
+        let mut missing: Vec<&String> = Vec::new();
+        for txid in changed.txids.iter(){
+            if self.confirmed_transaction(txid)?.is_none(){
+                missing.push(txid);
+            }
+        }
+        for transaction in try_join_all(missing.into_iter().map(|txid| testnet_transaction(txid))).await?{
+            self.insert_transaction(&transaction)?;
+        }
+
+        //transactions the explorer no longer lists since the reorg window are dropped, like ones reorganized out
+        let mut txids = changed.txids.clone();
+        for txid in previous.txids{
+            if !changed.txids.contains(&txid) && self.settled_before(&txid, from)?{
+                txids.push(txid);
+            }
+        }
+        let record = AddressRecord{
+            balance: parse_satoshis(&changed.balance)?,
+            txids,
+            utxos: utxos?,
+            synced_height: tip,
+        };
+        self.insert(&self.addresses, address, &record)?;
+        self.db.flush_async().await?;
+        Ok(record)
+    }
+}
+
+//the key of a watch-only cache, stretched from the password with the salt kept in the cache, made on first use
+fn watch_only_secret(db: &sled::Db, password: &str) -> Result<[u8; 32], WalletError>{
+    let salt = match db.get("salt")?{
+        Some(salt) => salt.to_vec(),
+        None => {
+            let mut salt = [0u8; SALT_LEN];
+            SystemRandom::new().fill(&mut salt).map_err(|_| WalletError::Crypto(String::from("cannot generate a salt")))?;
+            db.insert("salt", &salt)?;
+            salt.to_vec()
+        }
+    };
+    let mut secret = [0u8; 32];
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(), &salt, password.as_bytes(), &mut secret);
+    Ok(secret)
+}
+
+fn open_db(path: &str) -> Result<sled::Db, WalletError>{
+    sled::open(path).map_err(|e| WalletError::Storage(format!("cannot open the cache {} [Error: {}]", path, e)))
+}
+
+//the directory the wallet's cache is kept in, named after its address so each wallet has its own
+pub fn cache_path(info: &WalletInfo) -> String{
+    let root = dotenv::var("ION_CACHE_DIR").unwrap_or(String::from(CACHE_DIR));
+    let name: String = info.address.as_bytes().to_vec().sha256()[0..8].iter().map(|byte| format!("{:02x}", byte)).collect();
+    Path::new(&root).join(name).to_string_lossy().into_owned()
+}
+
+//opens the cache of the wallet unlocked by the password and makes it the one the wallet reads through,
+//replacing the cache of a wallet unlocked before
+pub fn unlock_cache(password: &str) -> Result<Arc<Cache>, WalletError>{
+    let info = read_wallet(password)?;
+    let path = cache_path(&info);
+    let mut current = WALLET_CACHE.lock().map_err(|_| WalletError::Storage(String::from("the cache lock is poisoned")))?;
+    if let Some(cache) = current.as_ref().filter(|cache| cache.path() == path){
+        return Ok(cache.clone())
+    }
+    *current = None;
+    let cache = Arc::new(match info.is_watch_only(){
+        true => Cache::open_watch_only_at(&path, password)?,
+        false => Cache::open_at(&path, &SecretKey::from_str(&read_keys(password)?).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")))?)?
+    });
+    *current = Some(cache.clone());
+    Ok(cache)
+}
+
+pub fn wallet_cache() -> Option<Arc<Cache>>{
+    WALLET_CACHE.lock().ok()?.clone()
+}
+
+//a transaction from the cache, downloading and keeping it when the cache does not have it
+pub fn wallet_transaction(txid: &str) -> Result<BlockchainTransaction, WalletError>{
+    if let Some(transaction) = wallet_cache().map(|cache| cache.transaction(txid)).transpose()?.flatten(){
+        return Ok(transaction)
+    }
+    let transaction = testnet_transaction_request(txid)?;
+    if let Some(cache) = wallet_cache(){
+        cache.insert_transaction(&transaction)?;
+    }
+    Ok(transaction)
+}
+
+//every transaction of the addresses, newest first, synced through the cache when it is open
+pub async fn wallet_full_history(cache: Option<&Cache>, addresses: &[String]) -> Result<Vec<TransactionHistory>, WalletError>{
+    match cache{
+        Some(cache) => {
+            for address in addresses.iter(){
+                cache.sync(address).await?;
+            }
+            cache.full_history(addresses)
+        }
+        None => testnet_full_history(addresses).await
+    }
+}
+
+//every will generation the wallet knows of, to find will transactions in its history
+pub fn known_wills(will_info: &WalletInfo) -> Vec<WillRecord>{
+    let mut wills = match wallet_cache(){
+        Some(cache) => cache.wills(&will_info.address).unwrap_or_default(),
+        None => vec![]
+    };
+    wills.extend(will_info.will.clone());
+    wills
+}
+
+//the coins of the address, from the cache when the block explorer cannot be reached
+pub fn wallet_utxos(address: &str) -> Result<Vec<UTXO>, WalletError>{
+    match (testnet_utxo_request(address), wallet_cache()){
+        (Ok(utxos), Some(cache)) => {
+            cache.insert_utxos(address, utxos.clone())?;
+            Ok(utxos)
+        }
+        (Err(WalletError::Network(e)), Some(cache)) => {
+            let record = cache.address(address)?;
+            if record.synced_height == 0{
+                return Err(WalletError::Network(e))
+            }
+            Ok(record.utxos)
+        }
+        (result, _) => result
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn transaction(txid: &str, block_height: i64, confirmations: u64) -> BlockchainTransaction{
+        serde_json::from_str(&format!(r#"{{"txid":"{}","version":2,"vin":[],"vout":[],"blockHeight":{},"confirmations":{},"blockTime":0,"size":1,"vsize":1,"value":"0","valueIn":"0","fees":"0","hex":"00"}}"#, txid, block_height, confirmations)).unwrap()
+    }
+
+    #[test]
+    fn watch_only_key_is_salted_per_cache(){
+        let first = sled::Config::new().temporary(true).open().unwrap();
+        let second = sled::Config::new().temporary(true).open().unwrap();
+        let key = watch_only_secret(&first, "password").unwrap();
+        //the salt is kept, so the same password opens the cache again
+        assert_eq!(watch_only_secret(&first, "password").unwrap(), key);
+        assert_ne!(watch_only_secret(&first, "wrong password").unwrap(), key);
+        assert_ne!(watch_only_secret(&second, "password").unwrap(), key);
+    }
+
+    #[test]
+    fn only_transactions_below_the_reorg_window_are_kept_without_the_explorer(){
+        let cache = Cache::with_secret("", sled::Config::new().temporary(true).open().unwrap(), &[7u8; 32]).unwrap();
+        cache.insert_transaction(&transaction("aa", 100, 10)).unwrap();
+        cache.insert_transaction(&transaction("bb", -1, 0)).unwrap();
+        assert!(cache.settled_before("aa", 106).unwrap());
+        assert!(!cache.settled_before("aa", 100).unwrap());
+        assert!(!cache.settled_before("bb", 106).unwrap());
+        assert!(!cache.settled_before("cc", 106).unwrap());
+    }
+}
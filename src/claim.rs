@@ -0,0 +1,214 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use crate::blockchain_info::{ChainBackend, TestnetBackend};
+use crate::decode_transaction::*;
+use crate::error::WalletError;
+
+//relative locktime flags of an input sequence (BIP68)
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000ffff;
+
+//the will parts an inheritor is given: the will initiation and a redemption for every tranche
+#[derive(Debug, Clone)]
+pub struct WillPackage {
+    pub generation: u32,
+    pub initiation_hex: String,
+    pub redemption_hexes: Vec<String>,
+    //descriptors of the vaults, missing from will parts made before they were written
+    pub vault_descriptors: Vec<String>,
+}
+
+    impl WillPackage{
+        //reads the "Will Initiation: hex", "Will Redemption (n Blocks): hex" and "Vault Descriptor (n Blocks): descriptor" lines of a will parts file
+        pub fn parse(contents: &str) -> Result<Self, WalletError>{
+            let mut generation = 0;
+            let mut initiation_hex: Option<String> = None;
+            let mut redemption_hexes: Vec<String> = Vec::new();
+            let mut vault_descriptors: Vec<String> = Vec::new();
+            for line in contents.lines().map(|line| line.trim()).filter(|line| !line.is_empty()){
+                let (label, value) = line.split_once(": ").ok_or(WalletError::Parse(format!("Unexpected line in will package: {}", line)))?;
+                if label == "Will Generation"{
+                    generation = value.parse::<u32>().map_err(|_| WalletError::Parse(format!("Invalid will generation: {}", value)))?;
+                }else if label == "Will Initiation"{
+                    initiation_hex = Some(value.to_string());
+                }else if label.starts_with("Will Redemption"){
+                    redemption_hexes.push(value.to_string());
+                }else if label.starts_with("Vault Descriptor"){
+                    vault_descriptors.push(value.to_string());
+                }else{
+                    return Err(WalletError::Parse(format!("Unexpected line in will package: {}", label)))
+                }
+            }
+            let initiation_hex = initiation_hex.ok_or(WalletError::Parse(String::from("The will package has no will initiation")))?;
+            if redemption_hexes.is_empty(){
+                return Err(WalletError::Parse(String::from("The will package has no will redemption")))
+            }
+            Ok(WillPackage{
+                generation,
+                initiation_hex,
+                redemption_hexes,
+                vault_descriptors,
+            })
+        }
+    }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedemptionStatus{
+    Waiting{redeemable_height: u64, blocks_left: u64},
+    Broadcast(String),
+    Redeemed(String),
+    //the vault was spent by something other than this redemption, most likely a revocation
+    Revoked,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimStatus{
+    //the initiation can never confirm, so the will has to be claimed from newer will parts
+    Invalid(String),
+    InitiationBroadcast(String),
+    Initiated{height: u64, redemptions: Vec<RedemptionStatus>},
+}
+
+impl ClaimStatus{
+    pub fn description(&self) -> String{
+        match self{
+            ClaimStatus::Invalid(reason) => format!("The will initiation is no longer valid: {}", reason),
+            ClaimStatus::InitiationBroadcast(txid) => format!("Will initiation {} broadcast, waiting for it to confirm", txid),
+            ClaimStatus::Initiated{height, redemptions} => {
+                let mut result = format!("Will initiated at block {}", height);
+                for (i, redemption) in redemptions.iter().enumerate(){
+                    result.push_str(&format!("\nRedemption {}: ", i + 1));
+                    result.push_str(&match redemption{
+                        RedemptionStatus::Waiting{redeemable_height, blocks_left} => format!("redeemable at block {} ({} blocks left)", redeemable_height, blocks_left),
+                        RedemptionStatus::Broadcast(txid) => format!("{} broadcast, waiting for it to confirm", txid),
+                        RedemptionStatus::Redeemed(txid) => format!("redeemed in {}", txid),
+                        RedemptionStatus::Revoked => String::from("the will was revoked"),
+                    });
+                }
+                result
+            }
+        }
+    }
+
+    //nothing is left to broadcast or wait for
+    pub fn is_final(&self) -> bool{
+        match self{
+            ClaimStatus::Invalid(_) => true,
+            ClaimStatus::InitiationBroadcast(_) => false,
+            ClaimStatus::Initiated{redemptions, ..} => redemptions.iter().all(|redemption| matches!(redemption, RedemptionStatus::Redeemed(_) | RedemptionStatus::Revoked)),
+        }
+    }
+}
+
+//an inheritor's claim on a will: broadcasts the initiation, then each redemption as soon as its relative timelock allows
+#[derive(Debug, Clone)]
+pub struct Claim {
+    pub package: WillPackage,
+    pub initiation: DecodedTransaction,
+    pub redemptions: Vec<DecodedTransaction>,
+}
+
+    impl Claim{
+        pub fn new(package: WillPackage) -> Result<Self, WalletError>{
+            let initiation = decode_transaction(&package.initiation_hex)?;
+            let mut redemptions: Vec<DecodedTransaction> = Vec::new();
+            for redemption_hex in package.redemption_hexes.iter(){
+                let redemption = decode_transaction(redemption_hex)?;
+                match redemption.inputs.first(){
+                    Some(input) if input.outpoint.txid == initiation.txid => (),
+                    _ => return Err(WalletError::Parse(String::from("A will redemption does not spend the will initiation")))
+                }
+                csv_delay(redemption.inputs[0].sequence)?;
+                redemptions.push(redemption);
+            }
+            Ok(Claim{
+                package,
+                initiation,
+                redemptions,
+            })
+        }
+
+        //checks the chain once and broadcasts whatever is ready
+        pub fn step(&self, backend: &impl ChainBackend) -> Result<ClaimStatus, WalletError>{
+            let initiation = match backend.transaction(&self.initiation.txid)?{
+                Some(initiation) => initiation,
+                None => return self.broadcast_initiation(backend)
+            };
+            if initiation.confirmations == 0{
+                return Ok(ClaimStatus::InitiationBroadcast(self.initiation.txid.clone()))
+            }
+
+            let height = initiation.block_height as u64;
+            let tip = backend.tip_height()?;
+            let mut redemptions: Vec<RedemptionStatus> = Vec::new();
+            for (redemption, redemption_hex) in self.redemptions.iter().zip(self.package.redemption_hexes.iter()){
+                let vault = redemption.inputs[0].outpoint.vout as usize;
+                redemptions.push(match backend.transaction(&redemption.txid)?{
+                    Some(seen) if seen.confirmations > 0 => RedemptionStatus::Redeemed(redemption.txid.clone()),
+                    Some(_) => RedemptionStatus::Broadcast(redemption.txid.clone()),
+                    None if initiation.vout.get(vault).and_then(|vout| vout.spent) == Some(true) => RedemptionStatus::Revoked,
+                    None => {
+                        //a relative timelock of n blocks lets the redemption into block height + n
+                        let redeemable_height = height + csv_delay(redemption.inputs[0].sequence)?;
+                        if tip + 1 >= redeemable_height{
+                            RedemptionStatus::Broadcast(backend.broadcast(redemption_hex)?)
+                        }else{
+                            RedemptionStatus::Waiting{redeemable_height, blocks_left: redeemable_height - tip - 1}
+                        }
+                    }
+                });
+            }
+            Ok(ClaimStatus::Initiated{height, redemptions})
+        }
+
+        //broadcasts the will initiation if every coin it spends is still there
+        pub fn broadcast_initiation(&self, backend: &impl ChainBackend) -> Result<ClaimStatus, WalletError>{
+            match self.check_initiation_inputs(backend){
+                Err(WalletError::Policy(reason)) => return Ok(ClaimStatus::Invalid(reason)),
+                Err(e) => return Err(e),
+                Ok(()) => (),
+            }
+            Ok(ClaimStatus::InitiationBroadcast(backend.broadcast(&self.package.initiation_hex)?))
+        }
+
+        //every coin the initiation spends has to exist and be unspent
+        fn check_initiation_inputs(&self, backend: &impl ChainBackend) -> Result<(), WalletError>{
+            for input in self.initiation.inputs.iter(){
+                let previous = backend.transaction(&input.outpoint.txid)?.ok_or(WalletError::Policy(format!("coin {}:{} does not exist", input.outpoint.txid, input.outpoint.vout)))?;
+                if previous.vout.get(input.outpoint.vout as usize).and_then(|vout| vout.spent) == Some(true){
+                    return Err(WalletError::Policy(format!("coin {}:{} has been spent since the will was made, ask for the latest will parts", input.outpoint.txid, input.outpoint.vout)))
+                }
+            }
+            Ok(())
+        }
+    }
+
+//the relative timelock in blocks set by an input sequence, as written by TimelockComponents::sequence
+pub fn csv_delay(sequence: u32) -> Result<u64, WalletError>{
+    if sequence & SEQUENCE_DISABLE_FLAG != 0{
+        return Ok(0)
+    }
+    if sequence & SEQUENCE_TYPE_FLAG != 0{
+        return Err(WalletError::Policy(String::from("Time based relative timelocks are not supported")))
+    }
+    Ok((sequence & SEQUENCE_LOCKTIME_MASK) as u64)
+}
+
+//steps a claim against the testnet backend every interval until it is final
+pub fn spawn_claim(claim: Claim, interval: Duration) -> Receiver<Result<ClaimStatus, WalletError>>{
+    let (status_sender, statuses) = mpsc::channel();
+    thread::spawn(move || {
+        loop{
+            let status = claim.step(&TestnetBackend);
+            let done = matches!(&status, Ok(status) if status.is_final());
+            if status_sender.send(status).is_err() || done{
+                return
+            }
+            thread::sleep(interval);
+        }
+    });
+    statuses
+}
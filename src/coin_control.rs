@@ -0,0 +1,87 @@
+use crate::blockchain_info::parse_satoshis;
+use crate::blockchain_utxo::UTXO;
+use crate::will_components::Outpoint;
+use crate::wallet_info::*;
+use crate::error::WalletError;
+
+//which of the wallet's coins a transaction may spend
+#[derive(Debug, Clone, Default)]
+pub struct CoinControl{
+    //coins that are never spent
+    pub frozen: Vec<Outpoint>,
+    //when not empty, the transaction spends exactly these coins
+    pub selected: Vec<Outpoint>,
+}
+
+impl CoinControl{
+    pub fn new(frozen: Vec<Outpoint>, selected: Vec<Outpoint>) -> Self{
+        CoinControl{
+            frozen,
+            selected,
+        }
+    }
+
+    pub fn is_frozen(&self, utxo: &UTXO) -> bool{
+        self.frozen.contains(&Outpoint::new(&utxo.txid, utxo.vout))
+    }
+
+    //the coins that are not frozen
+    pub fn spendable(&self, utxos: Vec<UTXO>) -> Vec<UTXO>{
+        utxos.into_iter().filter(|utxo| !self.is_frozen(utxo)).collect()
+    }
+
+    //the coins a transaction may spend: the selected ones if there are any, otherwise every coin that is not frozen
+    pub fn select(&self, utxos: Vec<UTXO>) -> Result<Vec<UTXO>, WalletError>{
+        let utxos = self.spendable(utxos);
+        if self.selected.is_empty(){
+            return Ok(utxos)
+        }
+        for outpoint in self.selected.iter(){
+            if !utxos.iter().any(|utxo| Outpoint::new(&utxo.txid, utxo.vout) == *outpoint){
+                return Err(WalletError::Policy(format!("The selected coin {}:{} is frozen or already spent", outpoint.txid, outpoint.vout)))
+            }
+        }
+        Ok(utxos.into_iter().filter(|utxo| self.selected.contains(&Outpoint::new(&utxo.txid, utxo.vout))).collect())
+    }
+
+    //manual selections spend every selected coin rather than stopping once there is enough
+    pub fn is_manual(&self) -> bool{
+        !self.selected.is_empty()
+    }
+}
+
+//one of the wallet's coins as the coin control view shows it
+#[derive(Debug, Clone)]
+pub struct Coin{
+    pub outpoint: Outpoint,
+    pub value: u64,
+    pub confirmations: u64,
+    pub label: String,
+    pub frozen: bool,
+    //the current will initiation spends it, so spending it makes the will stale
+    pub in_will: bool,
+}
+
+//the wallet's coins with their labels, and whether they are frozen or in the will
+pub fn list_coins(utxos: &[UTXO], will_info: &WalletInfo) -> Result<Vec<Coin>, WalletError>{
+    let mut coins: Vec<Coin> = Vec::new();
+    for utxo in utxos.iter(){
+        let outpoint = Outpoint::new(&utxo.txid, utxo.vout);
+        coins.push(Coin{
+            value: parse_satoshis(&utxo.value)?,
+            confirmations: utxo.confirmations,
+            label: will_info.labels.output(&outpoint.txid, outpoint.vout).unwrap_or("").to_string(),
+            frozen: will_info.labels.is_frozen(&outpoint),
+            in_will: will_info.will.as_ref().map(|will| will.initiation_outpoints.contains(&outpoint)).unwrap_or(false),
+            outpoint,
+        });
+    }
+    Ok(coins)
+}
+
+impl WalletInfo{
+    //the wallet's frozen coins, with nothing selected
+    pub fn coin_control(&self) -> CoinControl{
+        CoinControl::new(self.labels.frozen(), vec![])
+    }
+}
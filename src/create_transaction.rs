@@ -4,15 +4,21 @@ use std::u8;
 use num_bigint::*;
 use num_traits::{Zero, One};
 
-use crate::blockchain_info;
 use crate::blockchain_info::*;
 use crate::blockchain_status::BlockchainStatus;
 use crate::blockchain_address::BlockchainAddress;
 use crate::blockchain_transaction::BlockchainTransaction;
 use crate::blockchain_utxo::UTXO;
+use crate::cache::{wallet_transaction, wallet_utxos};
+use crate::coin_control::CoinControl;
+use crate::payment::{Payment, SendAmount};
 use crate::transaction_parts::*;
-use crate::will_components::TimelockComponents;
+use crate::psbt::Psbt;
+use crate::policy::{vault_policy, custom_vault_policy};
+use crate::script::{p2pkh_script, p2pk_script, p2sh_script, witness_program_script};
+use crate::will_components::{TimelockComponents, WillSpend, Tranche, WillParts, Redemption, split_by_shares, DUST_LIMIT};
 use crate::traits::*;
+use crate::error::WalletError;
 
 use bitcoin::util::hash::{Sha256dHash, Hash160};
 
@@ -26,109 +32,209 @@ use std::thread;
 
 
 const MAX32: u32 = 4294967295;
+const WILL_REDEMPTION_FEE: u64 = 250;
+
+//spends one of the wallet's coins, looking up its locking script in the transaction that created it
+fn utxo_vin(utxo: &UTXO, redeem_script_vector: Vec<&str>) -> Result<Vin, WalletError>{
+    let transaction: BlockchainTransaction = wallet_transaction(&utxo.txid)?;
+    let previous_output = transaction.vout.get(utxo.vout as usize).ok_or(WalletError::Parse(format!("Transaction {} has no output {}", utxo.txid, utxo.vout)))?;
+    Vin::new(&utxo.txid, utxo.vout, &previous_output.hex, redeem_script_vector, MAX32, parse_satoshis(&utxo.value)?)
+}
 
 
-pub fn create_transaction(to_address: &str, to_value: u64, fee: u64, my_address: &str, my_redeem_script: &str, secret_key: SecretKey) -> Result<SignedTransaction, String>{
+//pays every recipient from the coins the coin control allows, spending all of the selected ones, or all of them for a payment of the maximum,
+//and otherwise just enough of them
+pub fn create_transaction(payment: &Payment, my_address: &str, my_redeem_script: &str, secret_key: SecretKey, coins: &CoinControl) -> Result<SignedTransaction, WalletError>{
     let secp = Secp256k1::new();
-    let mut has_segwit = false;
-    let (destination_locking_script,output_is_segwit) = decode_address(to_address)?;
+    let (mut raw_transaction, has_segwit) = payment_transaction(payment, &[my_address.to_string()], vec![my_redeem_script], coins)?;
+    let n_vins = raw_transaction.vins.len();
+
+    //sign transaction
+    for i in 0..n_vins{
+        let legacy_unsigned_transaction = raw_transaction.clone().concat_legacy(i, 1).to_string();
 
-    if output_is_segwit{
-        has_segwit = true;
+        //create legacy signatures
+        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction))?;
+        let signature = secp.sign_ecdsa(&message, &secret_key);
+
+        raw_transaction.vins[i].sign( &format!("{}", signature), 1)?;
     }
+    
+    //combine all for final transaction
+    let witnesses = vec![];
+    let signed = SignedTransaction::new(raw_transaction,witnesses,has_segwit);
+    Ok(signed)
+}
+
+//the payment as an unsigned PSBT, for a watch-only wallet to hand to whoever holds the key
+//it spends the coins of every address and returns change to the first
+pub fn create_payment_psbt(payment: &Payment, addresses: &[String], coins: &CoinControl) -> Result<Psbt, WalletError>{
+    let (raw_transaction, _) = payment_transaction(payment, addresses, vec![], coins)?;
+    Ok(Psbt::new(raw_transaction))
+}
+
+//the unsigned transaction of a payment and whether it pays a segwit output
+fn payment_transaction(payment: &Payment, addresses: &[String], redeem_script_vector: Vec<&str>, coins: &CoinControl) -> Result<(RawTransaction, bool), WalletError>{
+    let destinations = payment.validate()?;
+    let has_segwit = destinations.iter().any(|(_, is_segwit)| *is_segwit);
 
-    let (my_locking_script,_) = decode_address(my_address)?;
+    let (my_locking_script,_) = decode_address(addresses.first().ok_or(WalletError::Policy(String::from("There is no address to spend from")))?)?;
 
     //create transaction inputs
+    let needed = payment.needed()?;
+    let spend_all = payment.is_max() || coins.is_manual();
     let mut input_total: u64 = 0;
     let mut vins: Vec<Vin> = Vec::new();
     
-    let utxos: Vec<UTXO> = blockchain_info::testnet_utxo_request(my_address);
-    for utxo in utxos{
-        let transaction: BlockchainTransaction = blockchain_info::testnet_transaction_request(&utxo.txid);
-        let vin = Vin::new(&utxo.txid, utxo.vout, &transaction.vout[utxo.vout as usize].hex, vec![my_redeem_script], MAX32, utxo.value.parse::<u64>().unwrap())?;
-        input_total += utxo.value.parse::<u64>().unwrap();
+    let mut utxos: Vec<UTXO> = Vec::new();
+    for address in addresses.iter(){
+        utxos.append(&mut wallet_utxos(address)?);
+    }
+    for utxo in coins.select(utxos)?{
+        let vin = utxo_vin(&utxo, redeem_script_vector.clone())?;
+        input_total += parse_satoshis(&utxo.value)?;
         vins.push(vin);
-        if input_total > to_value + fee{
+        if !spend_all && input_total >= needed{
             break;
         }
     }
 
-    //create transaction output to destination
+    //create transaction outputs to the recipients
+    let amounts = payment.amounts(input_total)?;
     let mut vouts: Vec<Vout> = Vec::new();
-    vouts.push(Vout::new(to_value, &destination_locking_script)?);
-
-    //return change
-    if let Some(subtotal) = to_value.checked_add(fee) {
-        if subtotal < input_total {
-            vouts.push(Vout::new(input_total - subtotal, &my_locking_script)?);
-        }else if subtotal > input_total{
-            return Err(String::from("Not enough coins"));
-        }
-    }else{
-        return Err(String::from(""));
+    for ((locking_script, _), amount) in destinations.iter().zip(amounts.iter()){
+        vouts.push(Vout::new(*amount, locking_script)?);
+    }
+    if let Some(data) = &payment.data{
+        vouts.push(Vout::op_return(data)?);
     }
 
-    //create unsigned transaction
-    let mut raw_transaction: RawTransaction = RawTransaction::new(2,vins.clone(),vouts,0);
-
-    //sign transaction
-    for i in 0..vins.len(){
-        let legacy_unsigned_transaction = raw_transaction.clone().concat_legacy(i, 1).to_string();
+    //return change; change below the dust limit goes to the fee
+    let change = input_total - amounts.iter().sum::<u64>() - payment.fee;
+    if change >= DUST_LIMIT{
+        vouts.push(Vout::new(change, &my_locking_script)?);
+    }
 
-        //create legacy signatures
-        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction)).unwrap();
-        let signature = secp.sign_ecdsa(&message, &secret_key);
+    //create unsigned transaction
+    Ok((RawTransaction::new(2,vins,vouts,0), has_segwit))
+}
 
-        raw_transaction.vins[i].sign( &format!("{}", signature), 1);
+//the most a payment can send to its recipient of the maximum from the coins of the addresses the coin control allows
+pub fn max_sendable(payment: &Payment, addresses: &[String], coins: &CoinControl) -> Result<u64, WalletError>{
+    let index = payment.recipients.iter().position(|recipient| recipient.amount == SendAmount::Max)
+        .ok_or(WalletError::Policy(String::from("No recipient is sent the maximum")))?;
+    let mut utxos: Vec<UTXO> = Vec::new();
+    for address in addresses.iter(){
+        utxos.append(&mut wallet_utxos(address)?);
     }
-    
-    //combine all for final transaction
-    let witnesses = vec![];
-    let signed = SignedTransaction::new(raw_transaction,witnesses,has_segwit);
-    Ok(signed)
+    let mut input_total: u64 = 0;
+    for utxo in coins.select(utxos)?{
+        input_total += parse_satoshis(&utxo.value)?;
+    }
+    Ok(payment.amounts(input_total)?[index])
 }
 
-pub fn predict_will_parts(child_addresses: Vec<String>, child_amounts: Vec<u64>, locktime_blocks: u16, previous_transaction: SignedTransaction, parent_address: &str, parent_pubkey: &str, parent_secretkey: SecretKey) -> (String, String){
+pub fn predict_will_parts(tranches: Vec<Tranche>, timelocks: Vec<TimelockComponents>, previous_transaction: SignedTransaction, spend: &WillSpend) -> Result<WillParts, WalletError>{
+    check_tranche_timelocks(&tranches, &timelocks)?;
+    let will_initiation = predict_will_initiation(previous_transaction, spend, vault_outputs(&tranches, &timelocks), 500)?;
+    will_parts(will_initiation, tranches, timelocks, &spend.address, spend.secret_key)
+}
+
+pub fn create_will_parts(tranches: Vec<Tranche>, timelocks: Vec<TimelockComponents>, spend: &WillSpend) -> Result<WillParts, WalletError>{
+    check_tranche_timelocks(&tranches, &timelocks)?;
+    let will_initiation = create_will_initiation(spend, vault_outputs(&tranches, &timelocks), 500)?;
+    will_parts(will_initiation, tranches, timelocks, &spend.address, spend.secret_key)
+}
 
+//creates one redemption per tranche, each spending its own vault output of the will initiation
+fn will_parts(will_initiation: SignedTransaction, tranches: Vec<Tranche>, timelocks: Vec<TimelockComponents>, parent_address: &str, parent_secretkey: SecretKey) -> Result<WillParts, WalletError>{
     let mut child_will_parts = String::new();
-    
-    let timelock = generate_timelock_components(parent_pubkey, locktime_blocks);
-    let will_initiation = predict_will_initiation(previous_transaction, parent_address, parent_secretkey, &timelock.locking_script.to_string(), parent_pubkey, 500).unwrap();
+    let mut redemptions: Vec<Redemption> = Vec::new();
+    let mut vault_descriptors: Vec<String> = Vec::new();
     child_will_parts.push_str(&format!("Will Initiation: {}\n\n",will_initiation.clone().concat().to_string()));
-    let will_redemption = create_will_redemption(will_initiation.clone(), timelock.clone(), child_amounts, child_addresses).unwrap();
-    child_will_parts.push_str(&format!("Will Redemption: {}\n\n",will_redemption.clone().concat().to_string()));
+    for (i, tranche) in tranches.into_iter().enumerate(){
+        let vault_satoshis = will_initiation.vouts[i].value.to_int_le();
+        let amounts = tranche.amounts(vault_satoshis.checked_sub(WILL_REDEMPTION_FEE).ok_or(WalletError::InsufficientFunds{needed: WILL_REDEMPTION_FEE, available: vault_satoshis})?)?;
+        let will_redemption = create_will_redemption(will_initiation.clone(), i as u32, timelocks[i].clone(), amounts, tranche.addresses)?;
+        redemptions.push(Redemption::new(tranche.locktime_blocks, will_redemption.txid()));
+        child_will_parts.push_str(&format!("Will Redemption ({} Blocks): {}\n\n", tranche.locktime_blocks, will_redemption.concat().to_string()));
+        //inheritors can watch their vault with any descriptor wallet
+        let vault_descriptor = timelocks[i].descriptor().to_string();
+        child_will_parts.push_str(&format!("Vault Descriptor ({} Blocks): {}\n\n", tranche.locktime_blocks, vault_descriptor));
+        vault_descriptors.push(vault_descriptor);
+    }
 
-    let will_revocation = create_will_revocation(parent_secretkey, will_initiation.clone(), timelock, parent_address, 250).unwrap();
-    let guardian_will_parts = format!("Will Revocation: {}",will_revocation.concat().to_string());
+    let initiation_outpoints = will_initiation.outpoints();
+    let initiation_txid = will_initiation.txid();
+    //the parent's revocation and the guardians' co-signed one spend the vaults in the same transaction
+    let revocation = create_will_revocation(parent_secretkey, will_initiation.clone(), timelocks.clone(), parent_address, 250)?;
+    let guardian_will_parts = guardian_will_parts(parent_secretkey, will_initiation, timelocks, parent_address)?;
 
-    (child_will_parts, guardian_will_parts)
+    Ok(WillParts::new(child_will_parts, guardian_will_parts, initiation_outpoints, initiation_txid, redemptions, revocation.txid(), revocation.concat().to_string())
+        .with_vault_descriptors(vault_descriptors))
 }
 
-pub fn create_will_parts(child_addresses: Vec<String>, child_amounts: Vec<u64>, locktime_blocks: u16, parent_address: &str, parent_pubkey: &str, parent_secretkey: SecretKey) -> (String, String){
+//generates a separate timelock vault for every tranche of inheritors
+pub fn generate_tranche_timelocks(parent_pubkey: &str, tranches: &[Tranche], guardian_pubkeys: Vec<String>, guardian_threshold: u8, vault_policy: &str) -> Result<Vec<TimelockComponents>, WalletError>{
+    let mut timelocks = Vec::new();
+    for tranche in tranches{
+        timelocks.push(generate_timelock_components(parent_pubkey, guardian_pubkeys.clone(), guardian_threshold, tranche.locktime_blocks, vault_policy)?);
+    }
+    Ok(timelocks)
+}
 
-    let mut child_will_parts = String::new();
-    
-    let timelock = generate_timelock_components(parent_pubkey, locktime_blocks);
-    let will_initiation = create_will_initiation(parent_address, parent_secretkey, &timelock.locking_script.to_string(), parent_pubkey, 500).unwrap();
-    child_will_parts.push_str(&format!("Will Initiation: {}\n\n",will_initiation.clone().concat().to_string()));
-    let will_redemption = create_will_redemption(will_initiation.clone(), timelock.clone(), child_amounts, child_addresses).unwrap();
-    child_will_parts.push_str(&format!("Will Redemption: {}\n\n",will_redemption.clone().concat().to_string()));
+//the vaults must be the ones generated for the tranches: one each, with the tranche's delay
+fn check_tranche_timelocks(tranches: &[Tranche], timelocks: &[TimelockComponents]) -> Result<(), WalletError>{
+    if tranches.len() != timelocks.len() || tranches.iter().zip(timelocks.iter()).any(|(tranche, timelock)| tranche.locktime_blocks != timelock.locktime_blocks()){
+        return Err(WalletError::Policy(String::from("The vaults do not match the tranches of inheritors; generate them again")))
+    }
+    Ok(())
+}
 
-    let will_revocation = create_will_revocation(parent_secretkey, will_initiation.clone(), timelock, parent_address, 250).unwrap();
-    let guardian_will_parts = format!("Will Revocation: {}",will_revocation.concat().to_string());
+//pairs each vault locking script with the share of the will held by its tranche
+pub fn vault_outputs(tranches: &[Tranche], timelocks: &[TimelockComponents]) -> Vec<(String, u64)>{
+    let mut result = Vec::new();
+    for (tranche, timelock) in tranches.iter().zip(timelocks.iter()){
+        result.push((timelock.locking_script.to_string(), tranche.total_share()));
+    }
+    result
+}
+
+//creates the will initiation outputs, splitting everything left after the fee between the vaults by share
+fn vault_vouts(input_satoshis: u64, fee: u64, vaults: Vec<(String, u64)>) -> Result<Vec<Vout>, WalletError>{
+    let spendable = input_satoshis.checked_sub(fee).ok_or(WalletError::InsufficientFunds{needed: fee, available: input_satoshis})?;
+    let shares: Vec<u64> = vaults.iter().map(|(_, share)| *share).collect();
+    let mut vouts: Vec<Vout> = Vec::new();
+    for ((locking_script, _), satoshis) in vaults.iter().zip(split_by_shares(spendable, &shares)?){
+        if satoshis < WILL_REDEMPTION_FEE + DUST_LIMIT{
+            return Err(WalletError::Policy(String::from("Not enough coins to fund every inheritor")))
+        }
+        vouts.push(Vout::new(satoshis, locking_script)?);
+    }
+    Ok(vouts)
+}
 
-    (child_will_parts, guardian_will_parts)
+//guardians holding keys get an unsigned revocation PSBT to co-sign, so no single guardian file can revoke the will on its own
+pub fn guardian_will_parts(parent_secretkey: SecretKey, will_initiation: SignedTransaction, timelocks: Vec<TimelockComponents>, parent_address: &str) -> Result<String, WalletError>{
+    if timelocks[0].has_guardians(){
+        let (threshold, n_guardians) = (timelocks[0].guardian_threshold, timelocks[0].guardian_pubkeys.len());
+        let revocation_psbt = create_will_revocation_psbt(will_initiation, timelocks, parent_address, 250)?;
+        Ok(format!("Will Revocation PSBT ({} of {} guardians): {}", threshold, n_guardians, revocation_psbt.to_base64()))
+    }else{
+        let will_revocation = create_will_revocation(parent_secretkey, will_initiation, timelocks, parent_address, 250)?;
+        Ok(format!("Will Revocation: {}",will_revocation.concat().to_string()))
+    }
 }
 
-pub fn predict_will_initiation(prev_transaction: SignedTransaction, my_address: &str, secret_key: SecretKey, timelock_locking_script: &str, my_redeem_script: &str, fee: u64) -> Result<SignedTransaction, String>{
+pub fn predict_will_initiation(prev_transaction: SignedTransaction, spend: &WillSpend, vaults: Vec<(String, u64)>, fee: u64) -> Result<SignedTransaction, WalletError>{
     let secp = Secp256k1::new();
+    let (my_address, secret_key, my_redeem_script) = (spend.address.as_str(), spend.secret_key, spend.pubkey.as_str());
     
     //create transaction inputs
     let mut input_satoshis: u64 = 0;
     let mut vins: Vec<Vin> = Vec::new();
     let (my_locking_script, wallet_is_segwit) = decode_address(my_address)?;
-    let prev_txid = sha256d(&prev_transaction.clone().concat().to_string()).reverse().to_string();
+    let prev_txid = prev_transaction.txid();
 
     let mut consumed_inputs = Vec::new();
     for vin in prev_transaction.clone().vins{
@@ -136,25 +242,25 @@ pub fn predict_will_initiation(prev_transaction: SignedTransaction, my_address:
     }
 
     for (i, vout) in prev_transaction.vouts.iter().enumerate(){
-        if vout.locking_script == my_locking_script.to_bytes()?{
+        if vout.locking_script == my_locking_script.to_bytes().map_err(WalletError::Parse)?{
             let input_sat = vout.value.to_int_le();
             vins.push(Vin::new(&prev_txid, i as u32, &my_locking_script, vec![my_redeem_script], MAX32, input_sat)?);
             input_satoshis += input_sat;
         }
     }
-    let utxos: Vec<UTXO> = blockchain_info::testnet_utxo_request(my_address);
+    let utxos: Vec<UTXO> = spend.coins.spendable(wallet_utxos(my_address)?);
     
     for utxo in utxos.iter().filter(|&x| !consumed_inputs.contains(&x.txid.to_uppercase())){
-        let transaction: BlockchainTransaction = blockchain_info::testnet_transaction_request(&utxo.txid);
-        let input_sat = utxo.value.parse::<u64>().unwrap();
-        let vin = Vin::new(&utxo.txid, utxo.vout, &transaction.vout[utxo.vout as usize].hex, vec![my_redeem_script], MAX32, input_sat)?;
-        input_satoshis += input_sat;
+        let vin = utxo_vin(utxo, vec![my_redeem_script])?;
+        input_satoshis += parse_satoshis(&utxo.value)?;
         vins.push(vin);
     }
 
-    //create transaction output to timelock vault
-    let satoshis = input_satoshis - fee;
-    let mut vouts: Vec<Vout> = vec![Vout::new(satoshis, timelock_locking_script)?];
+    //create transaction outputs to timelock vaults, with the data after them so each vault keeps the index of its tranche
+    let mut vouts: Vec<Vout> = vault_vouts(input_satoshis, fee, vaults)?;
+    if let Some(data) = &spend.data{
+        vouts.push(Vout::op_return(data)?);
+    }
 
     //create unsigned transaction
     let mut raw_transaction: RawTransaction = RawTransaction::new(2,vins.clone(),vouts,0);
@@ -164,9 +270,9 @@ pub fn predict_will_initiation(prev_transaction: SignedTransaction, my_address:
         let legacy_unsigned_transaction = raw_transaction.clone().concat_legacy(i, 1).to_string();
 
         //create legacy signatures
-        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction)).unwrap();
+        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction))?;
         let signature = secp.sign_ecdsa(&message, &secret_key);
-        raw_transaction.vins[i].sign( &format!("{}", signature), 1);
+        raw_transaction.vins[i].sign( &format!("{}", signature), 1)?;
     }
     
     //combine all for final transaction
@@ -176,26 +282,28 @@ pub fn predict_will_initiation(prev_transaction: SignedTransaction, my_address:
 }
 
 
-pub fn create_will_initiation(my_address: &str, secret_key: SecretKey, timelock_locking_script: &str, my_redeem_script: &str, fee: u64) -> Result<SignedTransaction, String>{
+pub fn create_will_initiation(spend: &WillSpend, vaults: Vec<(String, u64)>, fee: u64) -> Result<SignedTransaction, WalletError>{
     let secp = Secp256k1::new();
+    let (my_address, secret_key, my_redeem_script) = (spend.address.as_str(), spend.secret_key, spend.pubkey.as_str());
     
     //create transaction inputs
     let mut input_satoshis: u64 = 0;
     let mut vins: Vec<Vin> = Vec::new();
     let (_,wallet_is_segwit) = decode_address(my_address)?;
     
-    let utxos: Vec<UTXO> = blockchain_info::testnet_utxo_request(my_address);
-    // let utxo = utxos[1].clone();
+    //frozen coins stay out of the will and remain the parent's to spend
+    let utxos: Vec<UTXO> = spend.coins.spendable(wallet_utxos(my_address)?);
     for utxo in utxos{
-        let transaction: BlockchainTransaction = blockchain_info::testnet_transaction_request(&utxo.txid);
-        let vin = Vin::new(&utxo.txid, utxo.vout, &transaction.vout[utxo.vout as usize].hex, vec![my_redeem_script], MAX32, utxo.value.parse::<u64>().unwrap())?;
-        input_satoshis += utxo.value.parse::<u64>().unwrap();
+        let vin = utxo_vin(&utxo, vec![my_redeem_script])?;
+        input_satoshis += parse_satoshis(&utxo.value)?;
         vins.push(vin);
     }
 
-    //create transaction output to timelock vault
-    let satoshis = input_satoshis - fee;
-    let mut vouts: Vec<Vout> = vec![Vout::new(satoshis, timelock_locking_script)?];
+    //create transaction outputs to timelock vaults, with the data after them so each vault keeps the index of its tranche
+    let mut vouts: Vec<Vout> = vault_vouts(input_satoshis, fee, vaults)?;
+    if let Some(data) = &spend.data{
+        vouts.push(Vout::op_return(data)?);
+    }
 
     //create unsigned transaction
     let mut raw_transaction: RawTransaction = RawTransaction::new(2,vins.clone(),vouts,0);
@@ -205,9 +313,9 @@ pub fn create_will_initiation(my_address: &str, secret_key: SecretKey, timelock_
         let legacy_unsigned_transaction = raw_transaction.clone().concat_legacy(i, 1).to_string();
 
         //create legacy signatures
-        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction)).unwrap();
+        let message = Message::from_slice(&sha256d(&legacy_unsigned_transaction))?;
         let signature = secp.sign_ecdsa(&message, &secret_key);
-        raw_transaction.vins[i].sign( &format!("{}", signature), 1);
+        raw_transaction.vins[i].sign( &format!("{}", signature), 1)?;
     }
     
     //combine all for final transaction
@@ -216,19 +324,23 @@ pub fn create_will_initiation(my_address: &str, secret_key: SecretKey, timelock_
     Ok(signed)
 }
 
-pub fn create_will_redemption(will_initiation: SignedTransaction, timelock_vault: TimelockComponents, child_amounts: Vec<u64>, child_addresses: Vec<String>) -> Result<SignedTransaction, String>{
+pub fn create_will_redemption(will_initiation: SignedTransaction, vault_index: u32, timelock_vault: TimelockComponents, child_amounts: Vec<u64>, child_addresses: Vec<String>) -> Result<SignedTransaction, WalletError>{
     let secp = Secp256k1::new();
 
     //create transaction inputs
     let mut vins: Vec<Vin> = Vec::new();
     // Reverse TXID
-    vins.push(Vin::new(&sha256d(&will_initiation.clone().concat_legacy().to_string()).reverse().to_string(), 0,
+    vins.push(Vin::new(&sha256d(&will_initiation.clone().concat_legacy().to_string()).reverse().to_string(), vault_index,
     &timelock_vault.locking_script.to_string(), vec![&timelock_vault.witness_script.to_string()],
-    bytes_le_to_int(timelock_vault.sequence()) as u32, bytes_le_to_int(will_initiation.vouts[0].value.to_vec()))?);
+    bytes_le_to_int(timelock_vault.sequence()) as u32, bytes_le_to_int(will_initiation.vouts[vault_index as usize].value.to_vec()))?);
 
     //create transaction outputs
     let mut vouts: Vec<Vout> = Vec::new();
     for (i, val) in child_amounts.iter().enumerate(){
+        //inheritors whose share is dust receive no output
+        if *val == 0{
+            continue
+        }
         let (out_script,_) = decode_address(&child_addresses[i])?;
         vouts.push(Vout::new(child_amounts[i], &out_script)?);
     }
@@ -241,67 +353,88 @@ pub fn create_will_redemption(will_initiation: SignedTransaction, timelock_vault
     unsigned.change_vin_p2wsh(vins[0].clone(), 1, 0);
     //TEST
 
-    let message = Message::from_slice(&unsigned.clone().concat().sha256d()).unwrap();
+    let message = Message::from_slice(&unsigned.clone().concat().sha256d())?;
     let signature= secp.sign_ecdsa(&message, &timelock_vault.single_use_private_key);
 
-    //push empty witness for #1 p2pk transaction
-    witnesses.push(Some(Witness::new(vec![&format!("{}", signature)],
-    vec![StackItem::OP([0u8]), vins[0].redeem_script[0].clone()], 1)?));
+    //the single-use key's branch, once the vault is as old as its locktime
+    let stack = timelock_vault.witness(&[(timelock_vault.single_use_public_key, signature.serialize_der().to_vec())], timelock_vault.locktime_blocks() as u32)?;
+    witnesses.push(Some(Witness::new(vec![], stack, 1)?));
 
     //combine all for final transaction
     let signed = SignedTransaction::new(raw_transaction,witnesses,true);
     Ok(signed)
 }
 
-pub fn create_will_revocation(parent_secretkey: SecretKey, will_initiation: SignedTransaction, timelock_vault: TimelockComponents, return_address: &str, fee: u64) -> Result<SignedTransaction, String>{
+pub fn create_will_revocation(parent_secretkey: SecretKey, will_initiation: SignedTransaction, timelock_vaults: Vec<TimelockComponents>, return_address: &str, fee: u64) -> Result<SignedTransaction, WalletError>{
     let secp = Secp256k1::new();
-    let input_satoshis = bytes_le_to_int(will_initiation.vouts[0].value.to_vec());
-
-    //create transaction inputs
-    let mut vins: Vec<Vin> = Vec::new();
-    // Reverse TXID
-    vins.push(Vin::new(&sha256d(&will_initiation.clone().concat_legacy().to_string()).reverse().to_string(), 0,
-    &timelock_vault.locking_script.to_string(), vec![&timelock_vault.witness_script.to_string()],
-    4294967295, input_satoshis)?);
-
-    //create transaction outputs
-    let mut vouts: Vec<Vout> = Vec::new();
-    let (refund_script,_) = decode_address(return_address)?;
-    vouts.push(Vout::new(input_satoshis - fee, &refund_script)?);
-
-    let mut raw_transaction: RawTransaction = RawTransaction::new(2,vins.clone(),vouts,0);
+    let raw_transaction = will_revocation_transaction(will_initiation, timelock_vaults.clone(), return_address, fee)?;
+    let vins = raw_transaction.vins.clone();
+    let parent_pubkey = PublicKey::from_secret_key(&secp, &parent_secretkey);
 
     //create segwit signatures
     let mut witnesses: Vec<Option<Witness>> = Vec::new();
-    let mut unsigned: UnsignedSegwitTransaction = UnsignedSegwitTransaction::new(raw_transaction.clone(), 0, 1);
-    unsigned.change_vin_p2wsh(vins[0].clone(), 1, 0);
+    for (i, timelock_vault) in timelock_vaults.iter().enumerate(){
+        let mut unsigned: UnsignedSegwitTransaction = UnsignedSegwitTransaction::new(raw_transaction.clone(), i, 1);
+        unsigned.change_vin_p2wsh(vins[i].clone(), 1, 0);
 
-    let message = Message::from_slice(&unsigned.clone().concat().sha256d()).unwrap();
-    let signature= secp.sign_ecdsa(&message, &parent_secretkey);
+        let message = Message::from_slice(&unsigned.clone().concat().sha256d())?;
+        let signature= secp.sign_ecdsa(&message, &parent_secretkey);
 
-    //push empty witness for #1 p2pk transaction
-    witnesses.push(Some(Witness::new(vec![&format!("{}", signature)],
-    vec![StackItem::OP([1u8]), vins[0].redeem_script[0].clone()], 1)?)); // OP_True = 0x51 = 81
+        //the parent's key revokes without waiting
+        let stack = timelock_vault.witness(&[(parent_pubkey, signature.serialize_der().to_vec())], 0)?;
+        witnesses.push(Some(Witness::new(vec![], stack, 1)?));
+    }
 
     //combine all for final transaction
     let signed = SignedTransaction::new(raw_transaction,witnesses,true);
     Ok(signed)
 }
 
+//creates the unsigned revocation for guardians to co-sign; it spends the vaults exactly like the parent's revocation
+pub fn create_will_revocation_psbt(will_initiation: SignedTransaction, timelock_vaults: Vec<TimelockComponents>, return_address: &str, fee: u64) -> Result<Psbt, WalletError>{
+    if !timelock_vaults.iter().all(|vault| vault.has_guardians()){
+        return Err(WalletError::Policy(String::from("The will vault has no guardian keys to co-sign a revocation")))
+    }
+    Ok(Psbt::new(will_revocation_transaction(will_initiation, timelock_vaults, return_address, fee)?))
+}
+
+//builds the unsigned transaction returning every vault to the parent
+fn will_revocation_transaction(will_initiation: SignedTransaction, timelock_vaults: Vec<TimelockComponents>, return_address: &str, fee: u64) -> Result<RawTransaction, WalletError>{
+    let initiation_txid = sha256d(&will_initiation.clone().concat_legacy().to_string()).reverse().to_string();
+
+    //create transaction inputs
+    let mut input_satoshis: u64 = 0;
+    let mut vins: Vec<Vin> = Vec::new();
+    for (i, timelock_vault) in timelock_vaults.iter().enumerate(){
+        let vault_satoshis = bytes_le_to_int(will_initiation.vouts[i].value.to_vec());
+        vins.push(Vin::new(&initiation_txid, i as u32,
+        &timelock_vault.locking_script.to_string(), vec![&timelock_vault.witness_script.to_string()],
+        4294967295, vault_satoshis)?);
+        input_satoshis += vault_satoshis;
+    }
+
+    //create transaction outputs
+    let mut vouts: Vec<Vout> = Vec::new();
+    let (refund_script,_) = decode_address(return_address)?;
+    vouts.push(Vout::new(input_satoshis.checked_sub(fee).ok_or(WalletError::InsufficientFunds{needed: fee, available: input_satoshis})?, &refund_script)?);
+
+    Ok(RawTransaction::new(2,vins,vouts,0))
+}
+
 //generates the single-use public and private keys, as well as bitcoin address of timelock vault
-pub fn generate_timelock_components (parent_pubkey: &str, locktime_blocks: u16) -> TimelockComponents{
+//with guardian keys the revocation branch can be taken by the parent or by any m of the n guardians
+//the vault is the standard one when the policy is empty, and otherwise compiled from the wallet's own policy
+pub fn generate_timelock_components (parent_pubkey: &str, guardian_pubkeys: Vec<String>, guardian_threshold: u8, locktime_blocks: u16, policy: &str) -> Result<TimelockComponents, WalletError>{
     let secp = Secp256k1::new();
     let (secret_key, public_key) = secp.generate_keypair(&mut OsRng);
-    let locktime = if locktime_blocks >= 1 && locktime_blocks <= 16{
-        format!("{:X}", (80 + locktime_blocks))
-    }else{
-        int_to_bytes_le(locktime_blocks).to_string().varint()
+
+    //the witness script is compiled from the vault's policy, and its witnesses are satisfactions of the miniscript
+    let policy = match policy.trim().is_empty(){
+        true => vault_policy(parent_pubkey, &guardian_pubkeys, guardian_threshold, &public_key.to_string(), locktime_blocks)?,
+        false => custom_vault_policy(policy, parent_pubkey, &guardian_pubkeys, guardian_threshold, &public_key.to_string(), locktime_blocks)?
     };
-    let witness_script = script(["OP_IF", &parent_pubkey.varint(),  "OP_checksig", "OP_ELSE", &locktime, "OP_CHECKSEQUENCEVERIFY", "OP_DROP", &public_key.to_string().varint(), "OP_checksig", "OP_ENDIF"].to_vec());
-    let locking_script = wrap_p2wsh(witness_script.to_bytes().unwrap());
-    
-    let components = TimelockComponents::new(secret_key, public_key, locktime_blocks, witness_script.to_bytes().unwrap(), locking_script).unwrap();
-    components
+    let miniscript = policy.compile()?;
+    TimelockComponents::new(secret_key, locktime_blocks, miniscript, guardian_pubkeys, guardian_threshold)
 }
 
 pub fn generate_new_wallet (){
@@ -312,11 +445,7 @@ pub fn generate_new_wallet (){
 }
 
 pub fn wrap_p2wsh(redeem_script: Vec<u8>) -> Vec<u8>{
-    let mut result = vec![0u8];
-    let mut script_hash = redeem_script.sha256();
-    result.append(&mut varint(script_hash.len()));
-    result.append(&mut script_hash);
-    result
+    witness_program_script(0, &redeem_script.sha256()).into_bytes()
 }
 
 pub fn wrap_p2pkh(pubkey: &str) -> String{
@@ -333,125 +462,141 @@ pub fn wrap_p2pkh_testnet(pubkey: &str) -> String{
     hex_to_base58(&first.to_string())
 }
 
-pub fn decode_address(address: &str) -> Result<(String, bool), String>{
-    address_to_lockingscript(&base58_to_hex(address)?)
+pub fn wrap_p2sh_testnet(redeem_script: &[u8]) -> String{
+    let mut first = vec![196u8];
+    first.append(&mut hash160(&redeem_script.to_vec().to_string()));
+    first.append(&mut sha256d(&first.to_string())[0..4].to_vec());
+    hex_to_base58(&first.to_string())
+}
+
+const BECH32_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+//the checksum constants of bech32 for witness version 0 and of bech32m (BIP350) for later versions
+const BECH32_CONST: u32 = 1;
+const BECH32M_CONST: u32 = 0x2bc830a3;
+
+fn bech32_polymod(values: &[u8]) -> u32{
+    let generators = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for value in values.iter(){
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ *value as u32;
+        for (i, generator) in generators.iter().enumerate(){
+            if (top >> i) & 1 == 1{
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+fn bech32_hrp_expand(hrp: &str) -> Vec<u8>{
+    let mut result: Vec<u8> = hrp.bytes().map(|byte| byte >> 5).collect();
+    result.push(0);
+    result.extend(hrp.bytes().map(|byte| byte & 31));
+    result
+}
+
+//regroups bits, like the 8 bit bytes of a witness program into the 5 bit words of an address
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, WalletError>{
+    let (mut accumulator, mut bits, mut result) = (0u32, 0u32, Vec::new());
+    let max = (1u32 << to) - 1;
+    for value in data.iter(){
+        accumulator = (accumulator << from) | *value as u32;
+        bits += from;
+        while bits >= to{
+            bits -= to;
+            result.push(((accumulator >> bits) & max) as u8);
+        }
+    }
+    if pad && bits > 0{
+        result.push(((accumulator << (to - bits)) & max) as u8);
+    }else if !pad && (bits >= from || (accumulator << (to - bits)) & max != 0){
+        return Err(WalletError::InvalidAddress(String::from("Invalid padding in segwit address")))
+    }
+    Ok(result)
+}
+
+//encodes a witness program as a bech32 address, or bech32m for witness version 1 and up
+pub fn wrap_segwit(hrp: &str, version: u8, program: &[u8]) -> String{
+    let mut data = vec![version];
+    data.append(&mut convert_bits(program, 8, 5, true).unwrap_or_default());
+    let constant = if version == 0 { BECH32_CONST } else { BECH32M_CONST };
+    let checksum = bech32_polymod(&[bech32_hrp_expand(hrp), data.clone(), vec![0u8;6]].concat()) ^ constant;
+    data.extend((0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8));
+    let words: String = data.iter().map(|word| BECH32_CHARSET[*word as usize] as char).collect();
+    format!("{}1{}", hrp, words)
+}
+
+pub fn wrap_segwit_testnet(version: u8, program: &[u8]) -> String{
+    wrap_segwit("tb", version, program)
+}
+
+//decodes a bc1 or tb1 address into its witness version and program
+pub fn decode_segwit_address(address: &str) -> Result<(u8, Vec<u8>), WalletError>{
+    if address.to_lowercase() != address && address.to_uppercase() != address{
+        return Err(WalletError::InvalidAddress(String::from("Segwit addresses cannot mix upper and lower case")))
+    }
+    let address = address.to_lowercase();
+    let (hrp, words) = address.rsplit_once('1').ok_or(WalletError::InvalidAddress(format!("Invalid segwit address: {}", address)))?;
+    if hrp != "bc" && hrp != "tb"{
+        return Err(WalletError::InvalidAddress(format!("Unknown segwit address prefix: {}", hrp)))
+    }
+    let data = words.chars().map(|c| BECH32_CHARSET.iter().position(|&d| d as char == c).map(|position| position as u8))
+        .collect::<Option<Vec<u8>>>().ok_or(WalletError::InvalidAddress(format!("Invalid character in segwit address: {}", address)))?;
+    if data.len() < 7{
+        return Err(WalletError::InvalidAddress(format!("Segwit address is too short: {}", address)))
+    }
+    let version = data[0];
+    let constant = if version == 0 { BECH32_CONST } else { BECH32M_CONST };
+    if version > 16 || bech32_polymod(&[bech32_hrp_expand(hrp), data.clone()].concat()) != constant{
+        return Err(WalletError::InvalidAddress(String::from("Address Checksum Failed. Please check that the input address is correct")))
+    }
+    let program = convert_bits(&data[1..data.len() - 6], 5, 8, false)?;
+    if program.len() < 2 || program.len() > 40 || (version == 0 && program.len() != 20 && program.len() != 32){
+        return Err(WalletError::InvalidAddress(format!("Invalid witness program length: {}", program.len())))
+    }
+    Ok((version, program))
+}
+
+pub fn decode_address(address: &str) -> Result<(String, bool), WalletError>{
+    //segwit addresses are bech32 rather than base58
+    let prefix = address.get(0..3).unwrap_or("").to_lowercase();
+    if prefix == "bc1" || prefix == "tb1"{
+        let (version, program) = decode_segwit_address(address)?;
+        return Ok((witness_program_script(version, &program).into_bytes().to_string(), true))
+    }
+    address_to_lockingscript(&base58_to_hex(address).map_err(WalletError::InvalidAddress)?)
 }
 
 //takes in address, and returns locking script to use in transaction output, and a bool representing whether the transaction is segwit or not
-pub fn address_to_lockingscript(address: &str) -> Result<(String, bool), String>{
+pub fn address_to_lockingscript(address: &str) -> Result<(String, bool), WalletError>{
+    if address.len() < 2{
+        return Err(WalletError::InvalidAddress(String::from("Parse Address Error")))
+    }
     let (first_byte,address) = address.split_at(2);
     
     match first_byte{
-        "00" => return Ok((script(vec!["OP_dup", "OP_hash160", &remove_checksum("00", address)?.varint(), "OP_equalverify", "OP_checksig"]),false)), //p2pkh
-        "6F" => return Ok((script(vec!["OP_dup", "OP_hash160", &remove_checksum("6F", address)?.varint(), "OP_equalverify", "OP_checksig"]),false)), //p2pkh testnet
-        "02" => return Ok(((String::from("02") + address).varint() + &script(vec!["OP_checksig"]),false)), //compressed pubkey, positive y
-        "03" => return Ok(((String::from("03") + address).varint() + &script(vec!["OP_checksig"]),false)), //compressed pubkey, negative y
-        "04" => return Ok(((String::from("04") + address).varint() + &script(vec!["OP_checksig"]),false)), //uncompressed pubkey
-        "05" => return Ok((script(["OP_hash160", &remove_checksum("05", address)?.varint(), "OP_equal"].to_vec()),false)), //p2sh
-        _ => return Err(format!("Parse Address Error"))
+        "00" => Ok((p2pkh_script(&remove_checksum("00", address)?.to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //p2pkh
+        "6F" => Ok((p2pkh_script(&remove_checksum("6F", address)?.to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //p2pkh testnet
+        "02" => Ok((p2pk_script(&(String::from("02") + address).to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //compressed pubkey, positive y
+        "03" => Ok((p2pk_script(&(String::from("03") + address).to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //compressed pubkey, negative y
+        "04" => Ok((p2pk_script(&(String::from("04") + address).to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //uncompressed pubkey
+        "05" => Ok((p2sh_script(&remove_checksum("05", address)?.to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //p2sh
+        "C4" => Ok((p2sh_script(&remove_checksum("C4", address)?.to_bytes().map_err(WalletError::InvalidAddress)?).into_bytes().to_string(),false)), //p2sh testnet
+        _ => Err(WalletError::InvalidAddress(String::from("Parse Address Error")))
     }
 }
 
-pub fn remove_checksum(prefix: &str, address: &str) -> Result<String, String>{
+pub fn remove_checksum(prefix: &str, address: &str) -> Result<String, WalletError>{
+    if address.len() != 48{
+        return Err(WalletError::InvalidAddress(String::from("Address Length Error. Please check that the input address is correct")));
+    }
     let (address,checksum) = address.split_at(40);
-    if sha256d(&(prefix.to_string() + address))[0..4].to_string() == checksum.to_string(){
-        return Ok(address.to_string());
+    if sha256d(&(prefix.to_string() + address))[0..4].to_string() == checksum{
+        Ok(address.to_string())
     }else{
-        return Err(format!("Address Checksum Failed. Please check that the input address is correct"));
-    }
-}
-
-pub fn script (script: Vec<&str>) -> String{
-    let result: Vec<&str> = script.iter().map(|&word| {
-        match word.to_ascii_uppercase().as_ref() {
-            "OP_FALSE" => "00",
-            "OP_0" => "00",
-            "OP_PUSHDATA1" => "4c",
-            "OP_PUSHDATA2" => "4d",
-            "OP_PUSHDATA4" => "4e",
-            "OP_1NEGATE" => "4f",
-            "OP_TRUE" => "51",
-            "OP_1" => "51",
-            "OP_2" => "52",
-            "OP_3" => "53",
-            "OP_4" => "54",
-            "OP_5" => "55",
-            "OP_6" => "56",
-            "OP_7" => "57",
-            "OP_8" => "58",
-            "OP_9" => "59",
-            "OP_10" => "5a",
-            "OP_11" => "5b",
-            "OP_12" => "5c",
-            "OP_13" => "5d",
-            "OP_14" => "5e",
-            "OP_15" => "5f",
-            "OP_16" => "60",
-            "OP_NOP" => "61",
-            "OP_IF" => "63",
-            "OP_NOTIF" => "64",
-            "OP_ELSE" => "67",
-            "OP_ENDIF" => "68",
-            "OP_VERIFY" => "69",
-            "OP_RETURN" => "6a",
-            "OP_TOALTSTACK" => "6b",
-            "OP_FROMALTSTACK" => "6c",
-            "OP_IFDUP" => "73",
-            "OP_DEPTH" => "74",
-            "OP_DROP" => "75",
-            "OP_DUP" => "76",
-            "OP_NIP" => "77",
-            "OP_OVER" => "78",
-            "OP_PICK" => "79",
-            "OP_ROLL" => "7a",
-            "OP_ROT" => "7b",
-            "OP_SWAP" => "7c",
-            "OP_TUCK" => "7d",
-            "OP_2DROP" => "6d",
-            "OP_2DUP" => "6e",
-            "OP_3DUP" => "6f",
-            "OP_2OVER" => "70",
-            "OP_2ROT" => "71",
-            "OP_2SWAP" => "72",
-            "OP_SIZE" => "82",
-            "OP_EQUAL" => "87",
-            "OP_EQUALVERIFY" => "88",
-            "OP_1ADD" => "8b",
-            "OP_1SUB" => "8c",
-            "OP_NEGATE" => "8f",
-            "OP_ABS" => "90",
-            "OP_NOT" => "91",
-            "OP_0NOTEQUAL" => "92",
-            "OP_ADD" => "93",
-            "OP_SUB" => "94",
-            "OP_BOOLAND" => "9a",
-            "OP_BOOLOR" => "9b",
-            "OP_NUMEQUAL" => "9c",
-            "OP_NUMEQUALVERIFY" => "9d",
-            "OP_NUMNOTEQUAL" => "9e",
-            "OP_LESSTHAN" => "9f",
-            "OP_GREATERTHAN" => "a0",
-            "OP_LESSTHANOREQUAL" => "a1",
-            "OP_GREATERTHANOREQUAL" => "a2",
-            "OP_MIN" => "a3",
-            "OP_MAX" => "a4",
-            "OP_WITHIN" => "a5",
-            "OP_RIPEMD160" => "a6",
-            "OP_SHA1" => "a7",
-            "OP_SHA256" => "a8",
-            "OP_HASH160" => "a9",
-            "OP_HASH256" => "aa",
-            "OP_CODESEPARATOR" => "ab",
-            "OP_CHECKSIG" => "ac",
-            "OP_CHECKSIGVERIFY" => "ad",
-            "OP_CHECKMULTISIG" => "ae",
-            "OP_CHECKMULTISIGVERIFY" => "af",
-            "OP_CHECKLOCKTIMEVERIFY" => "b1",
-            "OP_CHECKSEQUENCEVERIFY" => "b2",
-            _ => word,
-        }
-    }).collect();
-    result.concat()
+        Err(WalletError::InvalidAddress(String::from("Address Checksum Failed. Please check that the input address is correct")))
+    }
 }
 
 pub fn hex_to_base58(hex: &str) -> String{
@@ -474,7 +619,7 @@ pub fn hex_to_base58(hex: &str) -> String{
 pub fn hex_to_int (hexinput: &str) -> Result<BigUint, String>{
     let mut result: BigUint = Zero::zero();
     for char in hexinput.chars(){
-        result = result*16u8 + char_to_int(char).unwrap();
+        result = result*16u8 + char_to_int(char)?;
     }
     Ok(result)
 }
@@ -576,14 +721,3 @@ pub fn bytes_le_to_int(bytes: Vec<u8>) -> u64 {
     }
     num
 }
-
-pub fn int_to_bytes_le(num: u16) -> Vec<u8>{
-    let mut num = num;
-    let mut result: Vec<u8> = Vec::new();
-    let pow = 256;
-    while num > 0 {
-        result.push((num % pow) as u8);
-        num /= 256;
-    }
-    result
-}
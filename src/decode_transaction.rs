@@ -0,0 +1,130 @@
+use crate::traits::*;
+use crate::script::{Script, Instruction, Opcode};
+use crate::will_components::Outpoint;
+use crate::error::WalletError;
+
+#[derive(Debug, Clone)]
+pub struct DecodedInput {
+    pub outpoint: Outpoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecodedOutput {
+    pub value: u64,
+    pub locking_script: Vec<u8>,
+}
+
+    impl DecodedOutput{
+        //the data of an OP_RETURN output with a single push
+        pub fn op_return_data(&self) -> Option<Vec<u8>>{
+            match self.locking_script.as_slice(){
+                [0x6a] => Some(vec![]),
+                [0x6a, 0x4c, len, data @ ..] | [0x6a, len @ 0..=75, data @ ..] if data.len() == *len as usize => Some(data.to_vec()),
+                _ => None
+            }
+        }
+    }
+
+//a transaction read back from its serialized hex
+#[derive(Debug, Clone)]
+pub struct DecodedTransaction {
+    pub txid: String,
+    pub version: u32,
+    pub inputs: Vec<DecodedInput>,
+    pub outputs: Vec<DecodedOutput>,
+    pub locktime: u32,
+    pub size: usize,
+    pub vsize: usize,
+}
+
+    impl DecodedTransaction{
+        pub fn is_segwit(&self) -> bool{
+            self.inputs.iter().any(|input| !input.witness.is_empty())
+        }
+    }
+
+//parses a legacy or segwit transaction, checking that every byte is used
+pub fn decode_transaction(transaction_hex: &str) -> Result<DecodedTransaction, WalletError>{
+    let bytes = transaction_hex.trim().to_bytes().map_err(WalletError::Parse)?;
+    let mut pos = 0;
+    //the txid commits to everything except the segwit marker, flag and witnesses
+    let mut stripped: Vec<u8> = Vec::new();
+
+    let version_bytes = read_bytes(&bytes, &mut pos, 4).map_err(WalletError::Parse)?;
+    stripped.extend(&version_bytes);
+    let has_witness = bytes.len() > pos + 1 && bytes[pos] == 0 && bytes[pos + 1] == 1;
+    if has_witness{
+        pos += 2;
+    }
+
+    let start = pos;
+    let input_count = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+    let mut inputs: Vec<DecodedInput> = Vec::new();
+    for _ in 0..input_count{
+        let txid = read_bytes(&bytes, &mut pos, 32).map_err(WalletError::Parse)?.reverse();
+        let vout = read_bytes(&bytes, &mut pos, 4).map_err(WalletError::Parse)?.to_int_le() as u32;
+        let script_length = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+        let script_sig = read_bytes(&bytes, &mut pos, script_length).map_err(WalletError::Parse)?;
+        let sequence = read_bytes(&bytes, &mut pos, 4).map_err(WalletError::Parse)?.to_int_le() as u32;
+        inputs.push(DecodedInput{
+            outpoint: Outpoint::new(&txid.to_string(), vout),
+            script_sig,
+            sequence,
+            witness: vec![],
+        });
+    }
+
+    let output_count = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+    let mut outputs: Vec<DecodedOutput> = Vec::new();
+    for _ in 0..output_count{
+        let value = read_bytes(&bytes, &mut pos, 8).map_err(WalletError::Parse)?.to_int_le();
+        let script_length = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+        outputs.push(DecodedOutput{
+            value,
+            locking_script: read_bytes(&bytes, &mut pos, script_length).map_err(WalletError::Parse)?,
+        });
+    }
+    stripped.extend(&bytes[start..pos]);
+
+    let witness_start = pos;
+    if has_witness{
+        for input in inputs.iter_mut(){
+            let item_count = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+            for _ in 0..item_count{
+                let item_length = read_varint(&bytes, &mut pos).map_err(WalletError::Parse)?;
+                input.witness.push(read_bytes(&bytes, &mut pos, item_length).map_err(WalletError::Parse)?);
+            }
+        }
+    }
+    let witness_size = pos - witness_start + if has_witness { 2 } else { 0 };
+
+    let locktime_bytes = read_bytes(&bytes, &mut pos, 4).map_err(WalletError::Parse)?;
+    stripped.extend(&locktime_bytes);
+    if pos != bytes.len(){
+        return Err(WalletError::Parse(format!("Transaction has {} unexpected bytes at the end", bytes.len() - pos)))
+    }
+
+    Ok(DecodedTransaction{
+        txid: stripped.sha256d().reverse().to_string().to_lowercase(),
+        version: version_bytes.to_int_le() as u32,
+        inputs,
+        outputs,
+        locktime: locktime_bytes.to_int_le() as u32,
+        size: bytes.len(),
+        vsize: (stripped.len() * 4 + witness_size).div_ceil(4),
+    })
+}
+
+//relative timelock in blocks pushed just before the OP_CHECKSEQUENCEVERIFY of a witness script
+pub fn script_csv_delay(script: &[u8]) -> Option<u64>{
+    let instructions = Script::from_bytes(script.to_vec()).instructions().ok()?;
+    let delay = instructions.iter().position(|instruction| *instruction == Instruction::Op(Opcode::OP_CHECKSEQUENCEVERIFY))?;
+    match instructions[..delay].last()?{
+        Instruction::Push(data) if data.len() <= 8 => Some(data.to_vec().to_int_le()),
+        Instruction::Op(opcode) if (0x51..=0x60).contains(&(*opcode as u8)) => Some((*opcode as u8 - 0x50) as u64),
+        _ => None
+    }
+}
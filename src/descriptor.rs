@@ -0,0 +1,602 @@
+use std::fmt;
+use std::str::FromStr;
+
+use secp256k1::{Secp256k1, PublicKey, Scalar};
+
+use crate::create_transaction::{decode_address, hash160, wrap_p2pkh_testnet, wrap_p2sh_testnet, wrap_p2wsh, wrap_segwit_testnet};
+use crate::watch_only::{Xpub, WatchSource, parse_path};
+use crate::wallet_info::WalletInfo;
+use crate::will_components::{TimelockComponents, MAX_LOCKTIME_BLOCKS};
+use crate::policy::vault_policy;
+use crate::script::{Script, Opcode, p2pkh_script, p2sh_script, witness_program_script};
+use crate::traits::*;
+use crate::error::WalletError;
+
+//BIP380 checksum alphabets: every character a descriptor may use, and the characters of the checksum
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+//most keys a multi() can have in P2WSH
+pub(crate) const MAX_MULTI_KEYS: usize = 20;
+
+fn checksum_polymod(c: u64, value: u64) -> u64{
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7ffffffff) << 5) ^ value;
+    for (i, generator) in [0xf5dee51989, 0xa9fdca3312, 0x1bab10e32d, 0x3706b1677a, 0x644d626ffd].iter().enumerate(){
+        if (c0 >> i) & 1 == 1{
+            c ^= generator;
+        }
+    }
+    c
+}
+
+//the 8 character checksum that follows a # at the end of a descriptor
+pub fn descriptor_checksum(descriptor: &str) -> Result<String, WalletError>{
+    let (mut c, mut class, mut class_count) = (1u64, 0u64, 0);
+    for character in descriptor.chars(){
+        let position = INPUT_CHARSET.find(character).ok_or(WalletError::Parse(format!("Invalid character in descriptor: {}", character)))? as u64;
+        c = checksum_polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3{
+            c = checksum_polymod(c, class);
+            (class, class_count) = (0, 0);
+        }
+    }
+    if class_count > 0{
+        c = checksum_polymod(c, class);
+    }
+    for _ in 0..8{
+        c = checksum_polymod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..8).map(|i| CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize] as char).collect())
+}
+
+//BIP340 tagged hash
+pub(crate) fn tagged_hash(tag: &str, data: &[u8]) -> Vec<u8>{
+    let tag = tag.as_bytes().to_vec().sha256();
+    [tag.clone(), tag, data.to_vec()].concat().sha256()
+}
+
+//splits the arguments of a descriptor function at the commas outside of brackets
+pub(crate) fn split_arguments(arguments: &str) -> Vec<&str>{
+    let (mut result, mut depth, mut start) = (Vec::new(), 0, 0);
+    for (i, character) in arguments.char_indices(){
+        match character{
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(arguments[start..i].trim());
+                start = i + 1;
+            }
+            _ => ()
+        }
+    }
+    result.push(arguments[start..].trim());
+    result
+}
+
+//the blocks of an older(); only block delays that fit the 16 bits BIP68 keeps for them are understood, as larger
+//values would set the time flag or bits a relative timelock ignores
+pub(crate) fn parse_older(argument: &str) -> Result<u32, WalletError>{
+    match argument.trim().parse::<u32>(){
+        Ok(blocks) if (1..=MAX_LOCKTIME_BLOCKS).contains(&blocks) => Ok(blocks),
+        _ => Err(WalletError::Parse(format!("older() takes 1 to {} blocks, not {}", MAX_LOCKTIME_BLOCKS, argument)))
+    }
+}
+
+//splits name(arguments) into the name and the arguments
+pub(crate) fn call(expression: &str) -> Result<(&str, &str), WalletError>{
+    expression.trim().strip_suffix(')').and_then(|expression| expression.split_once('('))
+        .ok_or(WalletError::Parse(format!("Invalid descriptor expression: {}", expression)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum KeySource{
+    Single(PublicKey),
+    //the extended key, the path below it and whether it ends in a wildcard
+    Extended(Xpub, Vec<u32>, bool),
+}
+
+//a key in a descriptor: a hex public key or an xpub with a path, after an optional origin like [d34db33f/44'/1'/0']
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorKey{
+    //the key as written, with its origin
+    text: String,
+    source: KeySource,
+}
+
+impl FromStr for DescriptorKey{
+    type Err = WalletError;
+
+    fn from_str(key: &str) -> Result<Self, WalletError>{
+        DescriptorKey::parse(key, false)
+    }
+}
+
+impl DescriptorKey{
+    //x-only keys are the 32 byte keys of tr()
+    fn parse(text: &str, x_only: bool) -> Result<Self, WalletError>{
+        let text = text.trim();
+        let (origin, key) = match text.strip_prefix('['){
+            Some(rest) => {
+                let (origin, key) = rest.split_once(']').ok_or(WalletError::Parse(format!("Unclosed key origin: {}", text)))?;
+                (format!("[{}]", origin), key)
+            }
+            None => (String::new(), text)
+        };
+        if key.starts_with("xpub") || key.starts_with("tpub"){
+            let (xpub, path) = key.split_once('/').unwrap_or((key, ""));
+            let wildcard = path == "*" || path.ends_with("/*");
+            let source = KeySource::Extended(Xpub::from_str(xpub)?, parse_path(path.trim_end_matches('*'))?, wildcard);
+            return Ok(DescriptorKey{ text: text.to_string(), source })
+        }
+        let hex = match key.len(){
+            64 if x_only => format!("02{}", key),
+            66 => key.to_string(),
+            _ => return Err(WalletError::Parse(format!("Not a compressed public key: {}", key)))
+        };
+        let pubkey = PublicKey::from_str(&hex).map_err(|_| WalletError::Parse(format!("Invalid public key: {}", key)))?;
+        Ok(DescriptorKey{ text: origin + &key.to_lowercase(), source: KeySource::Single(pubkey) })
+    }
+
+    //the public key, at index for a key ending in a wildcard
+    pub fn pubkey(&self, index: u32) -> Result<PublicKey, WalletError>{
+        match &self.source{
+            KeySource::Single(pubkey) => Ok(*pubkey),
+            KeySource::Extended(xpub, path, false) => Ok(xpub.derive_path(path)?.public_key),
+            KeySource::Extended(xpub, path, true) => Ok(xpub.derive_path(path)?.derive(index)?.public_key),
+        }
+    }
+
+    pub fn has_wildcard(&self) -> bool{
+        matches!(self.source, KeySource::Extended(_, _, true))
+    }
+}
+
+impl fmt::Display for DescriptorKey{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", self.text)
+    }
+}
+
+//the miniscript fragments a wsh() descriptor can be made of
+#[derive(Debug, Clone, PartialEq)]
+pub enum Miniscript{
+    //<key> OP_CHECKSIG
+    Pk(DescriptorKey),
+    //k of the keys with OP_CHECKMULTISIG
+    Multi(usize, Vec<DescriptorKey>),
+    //a relative timelock of n blocks with OP_CHECKSEQUENCEVERIFY
+    Older(u32),
+    //the first must succeed, then the second decides
+    AndV(Box<Miniscript>, Box<Miniscript>),
+    //OP_IF first OP_ELSE second OP_ENDIF, the spender choosing the branch
+    OrI(Box<Miniscript>, Box<Miniscript>),
+    //the second is only tried when the first fails
+    OrD(Box<Miniscript>, Box<Miniscript>),
+    //the v: wrapper, failing the script instead of leaving false
+    Verify(Box<Miniscript>),
+}
+
+//basic miniscript types: B leaves true or false on the stack, V leaves nothing and fails the script instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind{
+    B,
+    V,
+}
+
+impl FromStr for Miniscript{
+    type Err = WalletError;
+
+    fn from_str(miniscript: &str) -> Result<Self, WalletError>{
+        let miniscript = Miniscript::parse(miniscript)?;
+        if miniscript.kind()? != Kind::B{
+            return Err(WalletError::Policy(format!("{} leaves nothing on the stack, so it cannot be a whole script", miniscript)))
+        }
+        Ok(miniscript)
+    }
+}
+
+impl Miniscript{
+    fn parse(expression: &str) -> Result<Self, WalletError>{
+        let expression = expression.trim();
+        //wrappers come before a colon, like v:pk(K)
+        if let Some((wrappers, inner)) = expression.split_once(':').filter(|(wrappers, _)| !wrappers.contains('(')){
+            let mut miniscript = Miniscript::parse(inner)?;
+            for wrapper in wrappers.chars().rev(){
+                miniscript = match wrapper{
+                    'v' => Miniscript::Verify(Box::new(miniscript)),
+                    _ => return Err(WalletError::Policy(format!("Unsupported miniscript wrapper {}:", wrapper)))
+                };
+            }
+            return Ok(miniscript)
+        }
+        let (fragment, arguments) = call(expression)?;
+        let arguments = split_arguments(arguments);
+        let two = |arguments: &[&str]| -> Result<(Box<Miniscript>, Box<Miniscript>), WalletError>{
+            match arguments{
+                [first, second] => Ok((Box::new(Miniscript::parse(first)?), Box::new(Miniscript::parse(second)?))),
+                _ => Err(WalletError::Parse(format!("{}() takes two arguments", fragment)))
+            }
+        };
+        match fragment{
+            "pk" => match arguments[..]{
+                [key] => Ok(Miniscript::Pk(DescriptorKey::from_str(key)?)),
+                _ => Err(WalletError::Parse(String::from("pk() takes one key")))
+            },
+            "multi" => {
+                let threshold = arguments[0].parse::<usize>().map_err(|_| WalletError::Parse(format!("Invalid multi() threshold: {}", arguments[0])))?;
+                let keys = arguments[1..].iter().map(|key| DescriptorKey::from_str(key)).collect::<Result<Vec<DescriptorKey>, WalletError>>()?;
+                if threshold < 1 || threshold > keys.len() || keys.len() > MAX_MULTI_KEYS{
+                    return Err(WalletError::Policy(format!("Cannot require {} of {} keys", threshold, keys.len())))
+                }
+                Ok(Miniscript::Multi(threshold, keys))
+            }
+            "older" => Ok(Miniscript::Older(parse_older(arguments[0])?)),
+            "and_v" => two(&arguments).map(|(first, second)| Miniscript::AndV(first, second)),
+            "or_i" => two(&arguments).map(|(first, second)| Miniscript::OrI(first, second)),
+            "or_d" => two(&arguments).map(|(first, second)| Miniscript::OrD(first, second)),
+            _ => Err(WalletError::Policy(format!("Unsupported miniscript fragment {}()", fragment)))
+        }
+    }
+
+    //checks the fragments fit together and returns what the expression leaves on the stack
+    fn kind(&self) -> Result<Kind, WalletError>{
+        match self{
+            Miniscript::Pk(_) | Miniscript::Multi(_, _) | Miniscript::Older(_) => Ok(Kind::B),
+            Miniscript::Verify(inner) => match inner.kind()?{
+                Kind::B => Ok(Kind::V),
+                Kind::V => Err(WalletError::Policy(format!("{} already fails instead of leaving false", inner)))
+            },
+            Miniscript::AndV(first, second) => match first.kind()?{
+                Kind::V => second.kind(),
+                Kind::B => Err(WalletError::Policy(format!("The first argument of and_v() must be wrapped in v:, not {}", first)))
+            },
+            Miniscript::OrI(first, second) => match (first.kind()?, second.kind()?){
+                (first, second) if first == second => Ok(first),
+                _ => Err(WalletError::Policy(format!("Both branches of {} must be the same type", self)))
+            },
+            Miniscript::OrD(first, second) => match (first.as_ref(), second.kind()?){
+                //or_d needs a first argument that can fail without failing the script
+                (Miniscript::Pk(_) | Miniscript::Multi(_, _), Kind::B) => Ok(Kind::B),
+                _ => Err(WalletError::Policy(format!("{} needs pk() or multi() then an expression leaving true or false", self)))
+            },
+        }
+    }
+
+    //the script, deriving keys that end in a wildcard at index
+    pub fn encode(&self, index: u32) -> Result<Vec<u8>, WalletError>{
+        Ok(match self{
+            Miniscript::Pk(key) => Script::new().push_key(&key.pubkey(index)?).push_opcode(Opcode::OP_CHECKSIG).into_bytes(),
+            Miniscript::Multi(threshold, keys) => {
+                let mut script = Script::new().push_int(*threshold as i64);
+                for key in keys.iter(){
+                    script = script.push_key(&key.pubkey(index)?);
+                }
+                script.push_int(keys.len() as i64).push_opcode(Opcode::OP_CHECKMULTISIG).into_bytes()
+            }
+            Miniscript::Older(blocks) => Script::new().push_int(*blocks as i64).push_opcode(Opcode::OP_CHECKSEQUENCEVERIFY).into_bytes(),
+            Miniscript::AndV(first, second) => [first.encode(index)?, second.encode(index)?].concat(),
+            Miniscript::OrI(first, second) => Script::new().push_opcode(Opcode::OP_IF).append(Script::from_bytes(first.encode(index)?)).push_opcode(Opcode::OP_ELSE)
+                .append(Script::from_bytes(second.encode(index)?)).push_opcode(Opcode::OP_ENDIF).into_bytes(),
+            Miniscript::OrD(first, second) => Script::from_bytes(first.encode(index)?).push_opcode(Opcode::OP_IFDUP).push_opcode(Opcode::OP_NOTIF)
+                .append(Script::from_bytes(second.encode(index)?)).push_opcode(Opcode::OP_ENDIF).into_bytes(),
+            Miniscript::Verify(inner) => {
+                let mut script = inner.encode(index)?;
+                //fragments ending in OP_CHECKSIG or OP_CHECKMULTISIG use their verify opcode rather than an OP_VERIFY
+                match script.last_mut(){
+                    Some(opcode) if *opcode == Opcode::OP_CHECKSIG as u8 => *opcode = Opcode::OP_CHECKSIGVERIFY as u8,
+                    Some(opcode) if *opcode == Opcode::OP_CHECKMULTISIG as u8 => *opcode = Opcode::OP_CHECKMULTISIGVERIFY as u8,
+                    _ => script.push(Opcode::OP_VERIFY as u8)
+                }
+                script
+            }
+        })
+    }
+
+    //a witness for the script, bottom of the stack first and without the script itself; signatures come from sign,
+    //and older() is met when the input has waited age blocks
+    pub fn satisfy(&self, index: u32, sign: &dyn Fn(&PublicKey) -> Option<Vec<u8>>, age: u32) -> Result<Vec<Vec<u8>>, WalletError>{
+        self.satisfaction(index, sign, age)?.ok_or(WalletError::Policy(format!("{} cannot be satisfied with these signatures after {} blocks", self, age)))
+    }
+
+    fn satisfaction(&self, index: u32, sign: &dyn Fn(&PublicKey) -> Option<Vec<u8>>, age: u32) -> Result<Option<Vec<Vec<u8>>>, WalletError>{
+        Ok(match self{
+            Miniscript::Pk(key) => sign(&key.pubkey(index)?).map(|signature| vec![signature]),
+            Miniscript::Multi(threshold, keys) => {
+                //signatures in the order of their keys, after the empty item OP_CHECKMULTISIG pops by mistake
+                let mut signatures: Vec<Vec<u8>> = vec![vec![]];
+                for key in keys.iter(){
+                    if signatures.len() <= *threshold{
+                        signatures.extend(sign(&key.pubkey(index)?));
+                    }
+                }
+                if signatures.len() == threshold + 1 { Some(signatures) } else { None }
+            }
+            Miniscript::Older(blocks) => if age >= *blocks { Some(vec![]) } else { None },
+            //the first fragment runs first, so its witness goes on top
+            Miniscript::AndV(first, second) => match (first.satisfaction(index, sign, age)?, second.satisfaction(index, sign, age)?){
+                (Some(first), Some(second)) => Some([second, first].concat()),
+                _ => None
+            },
+            Miniscript::OrI(first, second) => shortest(
+                first.satisfaction(index, sign, age)?.map(|witness| [witness, vec![vec![1]]].concat()),
+                second.satisfaction(index, sign, age)?.map(|witness| [witness, vec![vec![]]].concat())),
+            Miniscript::OrD(first, second) => shortest(
+                first.satisfaction(index, sign, age)?,
+                match (second.satisfaction(index, sign, age)?, first.dissatisfaction()){
+                    (Some(second), Some(first)) => Some([second, first].concat()),
+                    _ => None
+                }),
+            Miniscript::Verify(inner) => inner.satisfaction(index, sign, age)?,
+        })
+    }
+
+    //a witness making the fragment leave false, which or_d() needs of its first argument
+    fn dissatisfaction(&self) -> Option<Vec<Vec<u8>>>{
+        match self{
+            Miniscript::Pk(_) => Some(vec![vec![]]),
+            Miniscript::Multi(threshold, _) => Some(vec![vec![]; threshold + 1]),
+            _ => None
+        }
+    }
+
+    pub fn has_wildcard(&self) -> bool{
+        match self{
+            Miniscript::Pk(key) => key.has_wildcard(),
+            Miniscript::Multi(_, keys) => keys.iter().any(|key| key.has_wildcard()),
+            Miniscript::Older(_) => false,
+            Miniscript::AndV(first, second) | Miniscript::OrI(first, second) | Miniscript::OrD(first, second) => first.has_wildcard() || second.has_wildcard(),
+            Miniscript::Verify(inner) => inner.has_wildcard(),
+        }
+    }
+}
+
+//the smaller of two witnesses, preferring the first
+fn shortest(first: Option<Vec<Vec<u8>>>, second: Option<Vec<Vec<u8>>>) -> Option<Vec<Vec<u8>>>{
+    let size = |witness: &Vec<Vec<u8>>| witness.iter().map(|item| item.len() + 1).sum::<usize>();
+    match (first, second){
+        (Some(first), Some(second)) => Some(if size(&second) < size(&first) { second } else { first }),
+        (first, second) => first.or(second)
+    }
+}
+
+impl fmt::Display for Miniscript{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Miniscript::Pk(key) => write!(f, "pk({})", key),
+            Miniscript::Multi(threshold, keys) => write!(f, "multi({},{})", threshold, keys.iter().map(|key| key.to_string()).collect::<Vec<String>>().join(",")),
+            Miniscript::Older(blocks) => write!(f, "older({})", blocks),
+            Miniscript::AndV(first, second) => write!(f, "and_v({},{})", first, second),
+            Miniscript::OrI(first, second) => write!(f, "or_i({},{})", first, second),
+            Miniscript::OrD(first, second) => write!(f, "or_d({},{})", first, second),
+            Miniscript::Verify(inner) => write!(f, "v:{}", inner),
+        }
+    }
+}
+
+//an output script descriptor (BIP380-386), which other wallets can import to watch or recover the same coins
+#[derive(Debug, Clone, PartialEq)]
+pub enum Descriptor{
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    ShWpkh(DescriptorKey),
+    Wsh(Miniscript),
+    //a taproot output spent with its key alone
+    Tr(DescriptorKey),
+    Addr(String),
+}
+
+impl FromStr for Descriptor{
+    type Err = WalletError;
+
+    //a descriptor, checked against its checksum when it has one
+    fn from_str(descriptor: &str) -> Result<Self, WalletError>{
+        let descriptor = descriptor.trim();
+        let body = match descriptor.split_once('#'){
+            Some((body, checksum)) => {
+                let expected = descriptor_checksum(body)?;
+                if checksum != expected{
+                    return Err(WalletError::Parse(format!("Descriptor checksum {} does not match, expected {}", checksum, expected)))
+                }
+                body
+            }
+            None => descriptor
+        };
+        let (function, inner) = call(body)?;
+        match function{
+            "pkh" => Ok(Descriptor::Pkh(DescriptorKey::from_str(inner)?)),
+            "wpkh" => Ok(Descriptor::Wpkh(DescriptorKey::from_str(inner)?)),
+            "sh" => match call(inner)?{
+                ("wpkh", key) => Ok(Descriptor::ShWpkh(DescriptorKey::from_str(key)?)),
+                (function, _) => Err(WalletError::Policy(format!("Only sh(wpkh()) is supported, not sh({}())", function)))
+            },
+            "wsh" => Ok(Descriptor::Wsh(Miniscript::from_str(inner)?)),
+            "tr" => match inner.contains(','){
+                true => Err(WalletError::Policy(String::from("Only key path tr() descriptors are supported, without a script tree"))),
+                false => Ok(Descriptor::Tr(DescriptorKey::parse(inner, true)?))
+            },
+            "addr" => {
+                decode_address(inner)?;
+                Ok(Descriptor::Addr(inner.to_string()))
+            }
+            _ => Err(WalletError::Policy(format!("Unsupported descriptor {}()", function)))
+        }
+    }
+}
+
+impl Descriptor{
+    fn body(&self) -> String{
+        match self{
+            Descriptor::Pkh(key) => format!("pkh({})", key),
+            Descriptor::Wpkh(key) => format!("wpkh({})", key),
+            Descriptor::ShWpkh(key) => format!("sh(wpkh({}))", key),
+            Descriptor::Wsh(miniscript) => format!("wsh({})", miniscript),
+            Descriptor::Tr(key) => format!("tr({})", key),
+            Descriptor::Addr(address) => format!("addr({})", address),
+        }
+    }
+
+    //true when the descriptor describes a range of addresses rather than one
+    pub fn has_wildcard(&self) -> bool{
+        match self{
+            Descriptor::Pkh(key) | Descriptor::Wpkh(key) | Descriptor::ShWpkh(key) | Descriptor::Tr(key) => key.has_wildcard(),
+            Descriptor::Wsh(miniscript) => miniscript.has_wildcard(),
+            Descriptor::Addr(_) => false,
+        }
+    }
+
+    //the script a P2WSH output commits to
+    pub fn witness_script(&self, index: u32) -> Result<Option<Vec<u8>>, WalletError>{
+        match self{
+            Descriptor::Wsh(miniscript) => Ok(Some(miniscript.encode(index)?)),
+            _ => Ok(None)
+        }
+    }
+
+    //the locking script of the output at index
+    pub fn script_pubkey(&self, index: u32) -> Result<Vec<u8>, WalletError>{
+        Ok(match self{
+            Descriptor::Pkh(key) => p2pkh_script(&hash160(&key.pubkey(index)?.to_string())).into_bytes(),
+            Descriptor::Wpkh(key) => witness_program_script(0, &hash160(&key.pubkey(index)?.to_string())).into_bytes(),
+            Descriptor::ShWpkh(key) => {
+                let redeem_script = Descriptor::Wpkh(key.clone()).script_pubkey(index)?;
+                p2sh_script(&hash160(&redeem_script.to_string())).into_bytes()
+            }
+            Descriptor::Wsh(miniscript) => wrap_p2wsh(miniscript.encode(index)?),
+            Descriptor::Tr(key) => witness_program_script(1, &taproot_output_key(&key.pubkey(index)?)?).into_bytes(),
+            Descriptor::Addr(address) => decode_address(address)?.0.to_bytes().map_err(WalletError::InvalidAddress)?,
+        })
+    }
+
+    //the testnet address of the output at index
+    pub fn address(&self, index: u32) -> Result<String, WalletError>{
+        Ok(match self{
+            Descriptor::Pkh(key) => wrap_p2pkh_testnet(&key.pubkey(index)?.to_string()),
+            Descriptor::Wpkh(key) => wrap_segwit_testnet(0, &hash160(&key.pubkey(index)?.to_string())),
+            Descriptor::ShWpkh(key) => wrap_p2sh_testnet(&Descriptor::Wpkh(key.clone()).script_pubkey(index)?),
+            Descriptor::Wsh(miniscript) => wrap_segwit_testnet(0, &miniscript.encode(index)?.sha256()),
+            Descriptor::Tr(key) => wrap_segwit_testnet(1, &taproot_output_key(&key.pubkey(index)?)?),
+            Descriptor::Addr(address) => address.clone(),
+        })
+    }
+
+    //the first count addresses of a ranged descriptor, or its one address
+    pub fn addresses(&self, count: u32) -> Result<Vec<String>, WalletError>{
+        match self.has_wildcard(){
+            true => (0..count).map(|i| self.address(i)).collect(),
+            false => Ok(vec![self.address(0)?])
+        }
+    }
+
+    //the first public key of a single key descriptor
+    pub fn first_pubkey(&self) -> Result<Option<String>, WalletError>{
+        match self{
+            Descriptor::Pkh(key) | Descriptor::Wpkh(key) | Descriptor::ShWpkh(key) | Descriptor::Tr(key) => Ok(Some(key.pubkey(0)?.to_string())),
+            Descriptor::Wsh(_) | Descriptor::Addr(_) => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for Descriptor{
+    //the descriptor with its checksum
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let body = self.body();
+        write!(f, "{}#{}", body, descriptor_checksum(&body).map_err(|_| fmt::Error)?)
+    }
+}
+
+//the key a key path taproot output pays to: the internal key tweaked by its own hash (BIP86)
+fn taproot_output_key(internal_key: &PublicKey) -> Result<Vec<u8>, WalletError>{
+    let (internal_key, _) = internal_key.x_only_public_key();
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &internal_key.serialize()).try_into().unwrap())
+        .map_err(|_| WalletError::Crypto(String::from("Invalid taproot tweak")))?;
+    let (output_key, _) = internal_key.add_tweak(&Secp256k1::verification_only(), &tweak)?;
+    Ok(output_key.serialize().to_vec())
+}
+
+//the will vault's descriptor, compiled from its policy
+pub fn vault_descriptor(parent_pubkey: &str, guardian_pubkeys: &[String], guardian_threshold: u8, single_use_pubkey: &str, locktime_blocks: u16) -> Result<Descriptor, WalletError>{
+    vault_policy(parent_pubkey, guardian_pubkeys, guardian_threshold, single_use_pubkey, locktime_blocks)?.descriptor()
+}
+
+impl TimelockComponents{
+    //the descriptor of this vault, which other wallets can import to watch it
+    pub fn descriptor(&self) -> Descriptor{
+        Descriptor::Wsh(self.miniscript.clone())
+    }
+}
+
+impl WalletInfo{
+    //the descriptors of the wallet's coins: its key, or what a watch-only wallet watches, with an xpub's
+    //receive and change chains and one addr() for each address of a list
+    pub fn descriptors(&self) -> Result<Vec<Descriptor>, WalletError>{
+        match &self.watch{
+            None => Ok(vec![Descriptor::Pkh(DescriptorKey::from_str(&self.pubkey)?)]),
+            Some(WatchSource::Xpub(xpub)) => Ok(vec![Descriptor::from_str(&format!("pkh({}/0/*)", xpub))?, Descriptor::from_str(&format!("pkh({}/1/*)", xpub))?]),
+            Some(WatchSource::Descriptor(descriptor)) => Ok(vec![Descriptor::from_str(descriptor)?]),
+            Some(WatchSource::Addresses(addresses)) => Ok(addresses.iter().map(|address| Descriptor::Addr(address.clone())).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const KEY: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    //BIP32 test vector 2
+    const XPUB: &str = "xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB";
+
+    #[test]
+    fn checksum_matches_bip380(){
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_ne!(descriptor_checksum("raw(deedbeef)").unwrap(), "89f8spxm");
+        assert!(descriptor_checksum("raw(Ü)").is_err());
+    }
+
+    #[test]
+    fn checksum_is_checked_when_present(){
+        let descriptor = Descriptor::from_str(&format!("pkh({})", KEY)).unwrap();
+        let text = descriptor.to_string();
+        assert_eq!(Descriptor::from_str(&text).unwrap(), descriptor);
+        let (body, checksum) = text.split_once('#').unwrap();
+        assert!(Descriptor::from_str(&format!("{}#{}", body, &checksum[1..])).is_err());
+        assert!(Descriptor::from_str(&format!("{}#{}x", body, checksum)).is_err());
+        assert!(Descriptor::from_str(&format!("{}#", body)).is_err());
+        assert!(Descriptor::from_str(&format!("{}##{}", body, checksum)).is_err());
+    }
+
+    #[test]
+    fn ranged_keys_derive_from_the_xpub(){
+        let descriptor = Descriptor::from_str(&format!("pkh([d34db33f/44'/1'/0']{}/*)", XPUB)).unwrap();
+        assert!(descriptor.has_wildcard());
+        //m/0 of BIP32 test vector 2
+        assert_eq!(descriptor.first_pubkey().unwrap().unwrap(), "02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea");
+        assert_eq!(descriptor.addresses(3).unwrap().len(), 3);
+        assert!(Descriptor::from_str(&format!("pkh({}/0'/*)", XPUB)).is_err());
+    }
+
+    #[test]
+    fn older_only_takes_block_delays(){
+        assert!(Miniscript::from_str(&format!("and_v(v:pk({}),older(65535))", KEY)).is_ok());
+        for blocks in ["0", "65536", "4194305", "2147483647"]{
+            assert!(Miniscript::from_str(&format!("and_v(v:pk({}),older({}))", KEY, blocks)).is_err());
+        }
+    }
+
+    #[test]
+    fn taproot_key_path_matches_bip86(){
+        let descriptor = Descriptor::from_str("tr(cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115)").unwrap();
+        assert_eq!(descriptor.script_pubkey(0).unwrap().to_string().to_lowercase(), "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+    }
+
+    #[test]
+    fn vault_descriptor_builds_the_vault_script(){
+        let vault = vault_descriptor(KEY, &[], 1, "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798", 144).unwrap();
+        assert!(vault.to_string().starts_with(&format!("wsh(or_i(pk({}),and_v(v:older(144),pk(", KEY)));
+        assert_eq!(Descriptor::from_str(&vault.to_string()).unwrap(), vault);
+        assert_eq!(vault.script_pubkey(0).unwrap(), wrap_p2wsh(vault.witness_script(0).unwrap().unwrap()));
+    }
+}
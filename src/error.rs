@@ -0,0 +1,64 @@
+use std::fmt;
+
+//everything that can go wrong in the wallet, grouped by where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub enum WalletError{
+    //the block explorer could not be reached, or answered with an error
+    Network(String),
+    //data that could not be read: hex, JSON, transactions, PSBTs, will packages
+    Parse(String),
+    //keys, signatures and the wallet file encryption
+    Crypto(String),
+    InsufficientFunds{needed: u64, available: u64},
+    InvalidAddress(String),
+    //wallet files that could not be read or written
+    Storage(String),
+    //a will or transaction the wallet refuses to build, like shares that do not add up to 100%
+    Policy(String),
+}
+
+impl fmt::Display for WalletError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            WalletError::Network(e) => write!(f, "Network error: {}", e),
+            WalletError::Parse(e) => write!(f, "Parse error: {}", e),
+            WalletError::Crypto(e) => write!(f, "Crypto error: {}", e),
+            WalletError::InsufficientFunds{needed, available} => write!(f, "Not enough coins: {} satoshis needed but only {} available", needed, available),
+            WalletError::InvalidAddress(e) => write!(f, "Invalid address: {}", e),
+            WalletError::Storage(e) => write!(f, "Storage error: {}", e),
+            WalletError::Policy(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for WalletError{}
+
+impl From<serde_json::Error> for WalletError{
+    fn from(e: serde_json::Error) -> Self{
+        WalletError::Parse(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for WalletError{
+    fn from(e: std::io::Error) -> Self{
+        WalletError::Storage(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for WalletError{
+    fn from(e: reqwest::Error) -> Self{
+        WalletError::Network(e.to_string())
+    }
+}
+
+impl From<sled::Error> for WalletError{
+    fn from(e: sled::Error) -> Self{
+        WalletError::Storage(e.to_string())
+    }
+}
+
+impl From<secp256k1::Error> for WalletError{
+    fn from(e: secp256k1::Error) -> Self{
+        WalletError::Crypto(e.to_string())
+    }
+}
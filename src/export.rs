@@ -0,0 +1,176 @@
+use crate::file::read_file;
+use crate::labels::Labels;
+use crate::wallet_info::*;
+use crate::error::WalletError;
+
+//a source of bitcoin prices for the fiat columns of an export
+pub trait PriceSource{
+    //currency code of the prices, like USD
+    fn currency(&self) -> &str;
+    //price of one bitcoin at a unix time, if the source knows it
+    fn price_at(&self, time: u64) -> Result<Option<f64>, WalletError>;
+}
+
+//daily prices from a CSV file of date,price lines like 2024-01-31,42000.50
+//a header line like date,USD names the currency; a transaction gets the price of its day, or of the last day before it
+pub struct CsvPriceFile{
+    currency: String,
+    //days since 1970-01-01 and the price, sorted by day
+    prices: Vec<(i64, f64)>,
+}
+
+impl CsvPriceFile{
+    pub fn open(path: &str) -> Result<Self, WalletError>{
+        CsvPriceFile::parse(&read_file(path)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, WalletError>{
+        let mut currency = String::from("FIAT");
+        let mut prices: Vec<(i64, f64)> = Vec::new();
+        for (i, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()){
+            let (date, price) = line.split_once(',').ok_or(WalletError::Parse(format!("price line {} is not date,price", i + 1)))?;
+            match (parse_date(date.trim()), price.trim().parse::<f64>()){
+                (Some(day), Ok(price)) => prices.push((day, price)),
+                (None, Err(_)) if i == 0 => currency = price.trim().to_string(),
+                _ => return Err(WalletError::Parse(format!("invalid price on line {}: {}", i + 1, line)))
+            }
+        }
+        prices.sort_by_key(|(day, _)| *day);
+        Ok(CsvPriceFile{currency, prices})
+    }
+
+    pub fn with_currency(mut self, currency: &str) -> Self{
+        self.currency = currency.to_string();
+        self
+    }
+}
+
+impl PriceSource for CsvPriceFile{
+    fn currency(&self) -> &str{
+        &self.currency
+    }
+
+    fn price_at(&self, time: u64) -> Result<Option<f64>, WalletError>{
+        let day = (time / 86400) as i64;
+        Ok(match self.prices.partition_point(|(price_day, _)| *price_day <= day){
+            0 => None,
+            i => Some(self.prices[i - 1].1)
+        })
+    }
+}
+
+//YYYY-MM-DD as days since 1970-01-01, after Howard Hinnant's days_from_civil
+fn parse_date(date: &str) -> Option<i64>{
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day){
+        return None
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146097 + day_of_era - 719468)
+}
+
+//fiat value of a transaction at the price of its day
+#[derive(Serialize, Debug, Clone)]
+pub struct FiatValue{
+    pub currency: String,
+    pub price: f64,
+    pub net: f64,
+    pub fee: f64,
+}
+
+//one transaction as the finance export writes it
+#[derive(Serialize, Debug, Clone)]
+pub struct ExportRow{
+    pub txid: String,
+    pub date: String,
+    pub time: u64,
+    //none while pending
+    pub block_height: Option<u64>,
+    pub net_sats: i64,
+    //the fee the wallet paid, so 0 for coins it received
+    pub fee_sats: u64,
+    pub label: String,
+    pub counterparties: Vec<String>,
+    pub will: Option<WillTransaction>,
+    pub fiat: Option<FiatValue>,
+}
+
+//the history as export rows, valued with the price source when there is one
+pub fn export_rows(history: &[TransactionHistory], labels: &Labels, prices: Option<&dyn PriceSource>) -> Result<Vec<ExportRow>, WalletError>{
+    let mut rows: Vec<ExportRow> = Vec::new();
+    for transaction in history.iter(){
+        let fee_sats = if transaction.is_sent() { transaction.fee } else { 0 };
+        let fiat = match prices{
+            Some(prices) => prices.price_at(transaction.time)?.map(|price| FiatValue{
+                currency: prices.currency().to_string(),
+                price,
+                net: transaction.net as f64 / 100000000.0 * price,
+                fee: fee_sats as f64 / 100000000.0 * price,
+            }),
+            None => None
+        };
+        rows.push(ExportRow{
+            txid: transaction.txid.clone(),
+            date: transaction.date(),
+            time: transaction.time,
+            block_height: transaction.block_height,
+            net_sats: transaction.net,
+            fee_sats,
+            label: labels.transaction(&transaction.txid).unwrap_or("").to_string(),
+            counterparties: transaction.counterparties.clone(),
+            will: transaction.will,
+            fiat,
+        });
+    }
+    Ok(rows)
+}
+
+pub fn to_json(rows: &[ExportRow]) -> Result<String, WalletError>{
+    Ok(serde_json::to_string_pretty(rows)?)
+}
+
+//one line per transaction; the fiat columns are only there when a row has a price
+pub fn to_csv(rows: &[ExportRow]) -> String{
+    let currency = rows.iter().find_map(|row| row.fiat.as_ref().map(|fiat| fiat.currency.clone()));
+    let mut header = vec![String::from("txid"), String::from("date"), String::from("block_height"), String::from("net_sats"), String::from("fee_sats"),
+        String::from("label"), String::from("counterparties"), String::from("will")];
+    if let Some(currency) = &currency{
+        header.extend([format!("price_{}", currency), format!("net_{}", currency), format!("fee_{}", currency)]);
+    }
+    let mut csv = header.join(",") + "\n";
+    for row in rows.iter(){
+        let mut fields = vec![
+            row.txid.clone(),
+            row.date.clone(),
+            row.block_height.map(|height| height.to_string()).unwrap_or(String::from("pending")),
+            row.net_sats.to_string(),
+            row.fee_sats.to_string(),
+            csv_field(&row.label),
+            csv_field(&row.counterparties.join(" ")),
+            row.will.map(|will| will.description().to_string()).unwrap_or_default(),
+        ];
+        if currency.is_some(){
+            match &row.fiat{
+                Some(fiat) => fields.extend([format!("{:.2}", fiat.price), format!("{:.2}", fiat.net), format!("{:.2}", fiat.fee)]),
+                None => fields.extend([String::new(), String::new(), String::new()]),
+            }
+        }
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+//quotes a field with commas, quotes or line breaks
+fn csv_field(field: &str) -> String{
+    if field.contains([',', '"', '\n', '\r']){
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }else{
+        field.to_string()
+    }
+}
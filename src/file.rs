@@ -2,109 +2,128 @@ use std::fs::File;
 use std::io::prelude::*;
 use crate::wallet_info::*;
 use crate::traits::*;
+use crate::will_components::WillParts;
+use crate::labels::Labels;
+use crate::export::{ExportRow, to_csv, to_json};
+use crate::error::WalletError;
 
 extern crate ring;
 use ring::aead::*;
 
-pub fn write_file(filename: &str, data: String){
-    let mut file = File::create(filename).expect("cannot create file");
-    file.write_all(data.as_bytes()).expect("cannot write to file");
+pub fn write_file(filename: &str, data: String) -> Result<(), WalletError>{
+    let mut file = File::create(filename).map_err(|e| WalletError::Storage(format!("cannot create {} [Error: {}]", filename, e)))?;
+    file.write_all(data.as_bytes()).map_err(|e| WalletError::Storage(format!("cannot write to {} [Error: {}]", filename, e)))
 }
 
-pub fn read_file(filename: &str) -> String{
-    let mut file = File::open(filename).expect("cannot open file");
+pub fn read_file(filename: &str) -> Result<String, WalletError>{
+    let mut file = File::open(filename).map_err(|e| WalletError::Storage(format!("cannot open {} [Error: {}]", filename, e)))?;
     let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("cannot read file");
-    contents
+    file.read_to_string(&mut contents).map_err(|e| WalletError::Storage(format!("cannot read {} [Error: {}]", filename, e)))?;
+    Ok(contents)
 }
 
-pub fn write_wallet(wallet: WalletInfo, password: &str){
-    let json = serde_json::to_string(&wallet).unwrap();
-    let contents = encrypt(json.as_bytes(), password).unwrap();
+pub fn write_wallet(wallet: WalletInfo, password: &str) -> Result<(), WalletError>{
+    let json = serde_json::to_string(&wallet)?;
+    let contents = encrypt(json.as_bytes(), password)?;
     let content_string = contents.to_string();
-    write_file("wallet_info.json", content_string);
+    write_file("wallet_info.json", content_string)
 }
 
-pub fn read_wallet(password: &str) -> Result<WalletInfo, String>{
-    let mut file = match File::open("wallet_info.json"){
-        Ok(x) => x,
-        Err(e) => return Err(format!("cannot open file [Error: {}]", e))
-    };
-    let mut contents = String::new();
-    match file.read_to_string(&mut contents){
-        Ok(_) => (),
-        Err(e) => return Err(format!("cannot read file [Error: {}]", e))
-    };
-    let decrypted_contents = String::from_utf8(
-        decrypt(
-            &contents.to_bytes().map_err(|e| format!("corrupt wallet file [Error: {}]", e))?,
-            password
-        )
-        .map_err(|_| format!("invalid password"))?
-    )
-        .unwrap();
-    Ok(serde_json::from_str(&decrypted_contents).map_err(|e| format!("cannot parse JSON from file [Error: {}", e))?)
-}
-
-pub fn write_transaction_history(wallet: Vec<TransactionHistory>, password: &str){
-    let json = serde_json::to_string(&wallet).unwrap();
-    let contents = encrypt(json.as_bytes(), password).unwrap();
+pub fn read_wallet(password: &str) -> Result<WalletInfo, WalletError>{
+    let decrypted_contents = read_encrypted("wallet_info.json", password)?;
+    serde_json::from_str(&decrypted_contents).map_err(|e| WalletError::Storage(format!("cannot parse JSON from wallet_info.json [Error: {}]", e)))
+}
+
+pub fn write_transaction_history(wallet: Vec<TransactionHistory>, password: &str) -> Result<(), WalletError>{
+    let json = serde_json::to_string(&wallet)?;
+    let contents = encrypt(json.as_bytes(), password)?;
     let content_string = contents.to_string();
-    write_file("transaction_history.json", content_string);
+    write_file("transaction_history.json", content_string)
 }
 
-pub fn read_transaction_history(password: &str) -> Result<Vec<TransactionHistory>, String>{
-    let mut file = match File::open("transaction_history.json"){
-        Ok(x) => x,
-        Err(e) => return Err(format!("cannot open file [Error: {}]", e))
-    };
-    let mut contents = String::new();
-    match file.read_to_string(&mut contents){
-        Ok(_) => (),
-        Err(e) => return Err(format!("cannot read file [Error: {}]", e))
-    };
-    let decrypted_contents = String::from_utf8(
-        decrypt(
-            &contents.to_bytes().map_err(|e| format!("corrupt transaction history file [Error: {}]", e))?,
-            password
-        )
-        .map_err(|_| format!("invalid password for transaction history file"))?
-    )
-        .unwrap();
-    Ok(serde_json::from_str(&decrypted_contents).map_err(|e| format!("cannot parse JSON from file [Error: {}", e))?)
+pub fn read_transaction_history(password: &str) -> Result<Vec<TransactionHistory>, WalletError>{
+    let decrypted_contents = read_encrypted("transaction_history.json", password)?;
+    serde_json::from_str(&decrypted_contents).map_err(|e| WalletError::Storage(format!("cannot parse JSON from transaction_history.json [Error: {}]", e)))
+}
+
+//reads and decrypts a JSON file written by write_wallet or write_transaction_history
+fn read_encrypted(filename: &str, password: &str) -> Result<String, WalletError>{
+    let contents = read_file(filename)?;
+    let bytes = contents.to_bytes().map_err(|e| WalletError::Storage(format!("corrupt {} [Error: {}]", filename, e)))?;
+    String::from_utf8(decrypt(&bytes, password)?).map_err(|_| WalletError::Storage(format!("corrupt {}: not UTF-8 after decrypting", filename)))
+}
+
+
+pub fn write_will_parts(will_parts: WillParts, generation: u32) -> Result<(), WalletError>{
+    let header = format!("Will Generation: {}\n\n", generation);
+    write_will_child(header.clone() + &will_parts.child_will_parts)?;
+    write_will_guardian(header + &will_parts.guardian_will_parts)
+}
+
+//reads a will parts file handed to an inheritor or guardian
+pub fn read_will_package(path: &str) -> Result<String, WalletError>{
+    read_file(path)
+}
+
+pub fn write_will_child(child_will_parts: String) -> Result<(), WalletError>{
+    write_file("will_parts(Child).json", child_will_parts)
+}
+
+pub fn write_will_guardian(guardian_will_parts: String) -> Result<(), WalletError>{
+    write_file("will_parts(Guardian).json", guardian_will_parts)
 }
 
+//a watch-only copy of a wallet is plain JSON: it holds no key, and the people it is for need no password to import it
+pub fn write_watch_only(path: &str, wallet: &WalletInfo) -> Result<(), WalletError>{
+    write_file(path, serde_json::to_string_pretty(&wallet.watch_only())?)
+}
 
-pub fn write_will_parts(will_parts: (String, String)){
-    let (child_will_parts, guardian_will_parts) = will_parts;
-    write_will_child(child_will_parts);
-    write_will_guardian(guardian_will_parts);
+pub fn read_watch_only(path: &str) -> Result<WalletInfo, WalletError>{
+    let wallet: WalletInfo = serde_json::from_str(&read_file(path)?).map_err(|e| WalletError::Parse(format!("cannot parse JSON from {} [Error: {}]", path, e)))?;
+    if !wallet.is_watch_only(){
+        return Err(WalletError::Policy(format!("{} is not a watch-only wallet", path)))
+    }
+    Ok(wallet)
 }
 
-pub fn write_will_child(child_will_parts: String){
-    write_file("will_parts(Child).json", child_will_parts);
+//sha256 of a file, as hex, for committing to a document in the will initiation's OP_RETURN output
+pub fn file_commitment(path: &str) -> Result<String, WalletError>{
+    Ok(std::fs::read(path)?.sha256().to_string())
 }
 
-pub fn write_will_guardian(guardian_will_parts: String){
-    write_file("will_parts(Guardian).json", guardian_will_parts);
+//writes the history for accounting, as JSON when the path ends in .json and CSV otherwise
+pub fn write_history_export(path: &str, rows: &[ExportRow]) -> Result<(), WalletError>{
+    let contents = match path.to_lowercase().ends_with(".json"){
+        true => to_json(rows)?,
+        false => to_csv(rows)
+    };
+    write_file(path, contents)
 }
 
+//labels are written as plain BIP329 JSON lines so other wallets can import them
+pub fn write_labels(path: &str, labels: &Labels) -> Result<(), WalletError>{
+    write_file(path, labels.to_bip329()?)
+}
 
-pub fn write_keys(key: &str, password: &str){
-    let key_bytes = key.to_bytes().unwrap();
-    let contents = encrypt(&key_bytes, password).unwrap().to_string();
+//merges the labels of a BIP329 file into the wallet's, returning how many were imported
+pub fn read_labels(path: &str, labels: &mut Labels) -> Result<usize, WalletError>{
+    labels.import_bip329(&read_file(path)?)
+}
+
+
+pub fn write_keys(key: &str, password: &str) -> Result<(), WalletError>{
+    let key_bytes = key.to_bytes().map_err(|_| WalletError::Crypto(String::from("secret key is not a valid hexadecimal string")))?;
+    let contents = encrypt(&key_bytes, password)?.to_string();
     write_file("wallet_keys.json", contents)
 }
 
-pub fn read_keys(password: &str) -> String{
-    let mut file = File::open("wallet_keys.json").expect("cannot open file");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).expect("cannot read file");
-    let key_bytes = contents.to_bytes().expect("corrupted key file: key is not a valid hexadecimal string");
-    decrypt(&key_bytes, password).expect("invalid password").to_string()
+pub fn read_keys(password: &str) -> Result<String, WalletError>{
+    let contents = read_file("wallet_keys.json")?;
+    let key_bytes = contents.to_bytes().map_err(|_| WalletError::Storage(String::from("corrupted key file: key is not a valid hexadecimal string")))?;
+    Ok(decrypt(&key_bytes, password)?.to_string())
 }
 
-fn encrypt(plaintext: &[u8], key: &str) -> Result<Vec<u8>, String> {
+fn encrypt(plaintext: &[u8], key: &str) -> Result<Vec<u8>, WalletError> {
     let nonce_data = [124; 12]; // Just an example
     let mut data = plaintext.to_owned();
     
@@ -114,7 +133,7 @@ fn encrypt(plaintext: &[u8], key: &str) -> Result<Vec<u8>, String> {
     
     let key = match UnboundKey::new(&CHACHA20_POLY1305, &key){
         Ok(val) => val,
-        Err(err) => return Err(format!("{}",err))
+        Err(err) => return Err(WalletError::Crypto(format!("{}",err)))
     };
     let key = LessSafeKey::new(key);
     
@@ -122,12 +141,12 @@ fn encrypt(plaintext: &[u8], key: &str) -> Result<Vec<u8>, String> {
     let nonce = Nonce::assume_unique_for_key(nonce_data);
     match key.seal_in_place_append_tag(nonce, Aad::empty(), &mut data){
         Ok(_) => (),
-        Err(err) => return Err(format!("{}",err))
+        Err(err) => return Err(WalletError::Crypto(format!("{}",err)))
     };
     Ok(data)
 }
 
-fn decrypt(ciphertext: &[u8], key: &str) -> Result<Vec<u8>, String> {
+fn decrypt(ciphertext: &[u8], key: &str) -> Result<Vec<u8>, WalletError> {
     // Create a new P-256 decryptor
     let nonce_data = [124; 12]; // Just an example
     let mut data = ciphertext.to_owned();
@@ -138,15 +157,16 @@ fn decrypt(ciphertext: &[u8], key: &str) -> Result<Vec<u8>, String> {
 
     let key = match UnboundKey::new(&CHACHA20_POLY1305, &key){
         Ok(val) => val,
-        Err(err) => return Err(format!("{}",err))
+        Err(err) => return Err(WalletError::Crypto(format!("{}",err)))
     };
     let key = LessSafeKey::new(key);
     
     // decoding
     let nonce = Nonce::assume_unique_for_key(nonce_data);
+    //a wrong password fails the authentication tag
     let data = match key.open_in_place(nonce, Aad::empty(), &mut data){
         Ok(val) => val,
-        Err(err) => return Err(format!("{}",err))
+        Err(_) => return Err(WalletError::Crypto(String::from("invalid password")))
     };
     Ok(data.to_owned())
 }
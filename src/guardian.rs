@@ -0,0 +1,214 @@
+use std::sync::mpsc::{self, Sender, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use secp256k1::SecretKey;
+
+use crate::blockchain_info::{ChainBackend, TestnetBackend};
+use crate::decode_transaction::*;
+use crate::psbt::Psbt;
+use crate::traits::*;
+use crate::error::WalletError;
+
+//the revocation a guardian is given: signed by the parent, or a PSBT for the guardians to co-sign
+#[derive(Debug, Clone)]
+pub enum Revocation{
+    Signed(String),
+    Psbt(Psbt),
+}
+
+//one will vault output the revocation spends
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedVault {
+    pub vout: u32,
+    pub csv_delay: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct GuardianPackage {
+    pub generation: u32,
+    pub revocation: Revocation,
+    pub initiation_txid: String,
+    pub vaults: Vec<WatchedVault>,
+}
+
+    impl GuardianPackage{
+        //reads the "Will Revocation: hex" or "Will Revocation PSBT (m of n guardians): base64" line of a will parts file
+        pub fn parse(contents: &str) -> Result<Self, WalletError>{
+            let mut generation = 0;
+            let mut revocation: Option<Revocation> = None;
+            for line in contents.lines().map(|line| line.trim()).filter(|line| !line.is_empty()){
+                let (label, value) = line.split_once(": ").ok_or(WalletError::Parse(format!("Unexpected line in will package: {}", line)))?;
+                if label == "Will Generation"{
+                    generation = value.parse::<u32>().map_err(|_| WalletError::Parse(format!("Invalid will generation: {}", value)))?;
+                }else if label == "Will Revocation"{
+                    revocation = Some(Revocation::Signed(value.to_string()));
+                }else if label.starts_with("Will Revocation PSBT"){
+                    revocation = Some(Revocation::Psbt(Psbt::from_base64(value)?));
+                }else{
+                    return Err(WalletError::Parse(format!("Unexpected line in will package: {}", label)))
+                }
+            }
+            let revocation = revocation.ok_or(WalletError::Parse(String::from("The will package has no will revocation")))?;
+
+            //every input of the revocation spends a vault of the will initiation through its witness script
+            let mut spends: Vec<(String, u32, Vec<u8>)> = Vec::new();
+            match &revocation{
+                Revocation::Signed(revocation_hex) => {
+                    for input in decode_transaction(revocation_hex)?.inputs{
+                        let witness_script = input.witness.last().cloned().ok_or(WalletError::Parse(String::from("The will revocation has no witness script")))?;
+                        spends.push((input.outpoint.txid, input.outpoint.vout, witness_script));
+                    }
+                }
+                Revocation::Psbt(psbt) => {
+                    for (vin, input) in psbt.unsigned_transaction.vins.iter().zip(psbt.inputs.iter()){
+                        let witness_script = input.witness_script.clone().ok_or(WalletError::Parse(String::from("The will revocation PSBT has no witness script")))?;
+                        spends.push((vin.txid.reverse().to_string().to_lowercase(), vin.vout.to_vec().to_int_le() as u32, witness_script));
+                    }
+                }
+            }
+
+            let initiation_txid = spends.first().ok_or(WalletError::Parse(String::from("The will revocation spends nothing")))?.0.clone();
+            let mut vaults: Vec<WatchedVault> = Vec::new();
+            for (txid, vout, witness_script) in spends{
+                if txid != initiation_txid{
+                    return Err(WalletError::Parse(String::from("The will revocation spends more than one will initiation")))
+                }
+                let csv_delay = script_csv_delay(&witness_script).ok_or(WalletError::Parse(String::from("The will vault script has no relative timelock")))?;
+                vaults.push(WatchedVault{vout, csv_delay});
+            }
+            Ok(GuardianPackage{
+                generation,
+                revocation,
+                initiation_txid,
+                vaults,
+            })
+        }
+
+        pub fn revocation_txid(&self) -> Result<String, WalletError>{
+            match &self.revocation{
+                Revocation::Signed(revocation_hex) => Ok(decode_transaction(revocation_hex)?.txid),
+                Revocation::Psbt(psbt) => Ok(psbt.txid()),
+            }
+        }
+
+        //the signed revocation, once the parent's signature or enough guardian signatures are present
+        pub fn revocation_hex(&self) -> Result<String, WalletError>{
+            match &self.revocation{
+                Revocation::Signed(revocation_hex) => Ok(revocation_hex.clone()),
+                Revocation::Psbt(psbt) => Ok(psbt.clone().finalize_guardian_revocation()?.concat().to_string()),
+            }
+        }
+
+        pub fn sign(&mut self, secret_key: SecretKey) -> Result<usize, WalletError>{
+            match &mut self.revocation{
+                Revocation::Psbt(psbt) => psbt.sign(secret_key),
+                Revocation::Signed(_) => Err(WalletError::Policy(String::from("The will revocation is already signed by the parent"))),
+            }
+        }
+
+        //adds the signatures from another guardian's copy of the revocation PSBT
+        pub fn combine(&mut self, psbt_base64: &str) -> Result<(), WalletError>{
+            match &mut self.revocation{
+                Revocation::Psbt(psbt) => psbt.combine(Psbt::from_base64(psbt_base64.trim())?),
+                Revocation::Signed(_) => Err(WalletError::Policy(String::from("The will revocation is already signed by the parent"))),
+            }
+        }
+
+        //only the unsigned guardian PSBT can change its fee, every guardian then has to sign the new one
+        pub fn bump_fee(&mut self, fee: u64) -> Result<(), WalletError>{
+            match &mut self.revocation{
+                Revocation::Psbt(psbt) => psbt.bump_fee(fee),
+                Revocation::Signed(_) => Err(WalletError::Policy(String::from("The parent's signature fixes the fee of this revocation"))),
+            }
+        }
+
+        pub fn can_bump_fee(&self) -> bool{
+            matches!(self.revocation, Revocation::Psbt(_))
+        }
+
+        pub fn psbt_base64(&self) -> Option<String>{
+            match &self.revocation{
+                Revocation::Psbt(psbt) => Some(psbt.clone().to_base64()),
+                Revocation::Signed(_) => None,
+            }
+        }
+    }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardianStatus{
+    //the will has not been initiated, nothing to do
+    Waiting,
+    //the will was initiated and the revocation has to confirm before the redeemable height
+    Initiated{height: u64, redeemable_height: u64, blocks_left: u64},
+    //the timelock has opened and the inheritors can redeem at any time
+    Redeemable{height: u64, redeemable_height: u64},
+    Revoked(String),
+    //a vault was spent by something other than this revocation: a redemption, or a revocation with another fee
+    VaultSpent,
+}
+
+impl GuardianStatus{
+    pub fn description(&self) -> String{
+        match self{
+            GuardianStatus::Waiting => String::from("The will has not been initiated. No action is needed."),
+            GuardianStatus::Initiated{height, redeemable_height, blocks_left} => format!("The will was initiated at block {}. {} blocks remain to revoke it before block {}.", height, blocks_left, redeemable_height),
+            GuardianStatus::Redeemable{height, redeemable_height} => format!("The will was initiated at block {} and has been redeemable since block {}.", height, redeemable_height),
+            GuardianStatus::Revoked(txid) => format!("The will was revoked in {}", txid),
+            GuardianStatus::VaultSpent => String::from("The will vault has already been spent"),
+        }
+    }
+
+    //the revocation can still beat the redemption
+    pub fn needs_action(&self) -> bool{
+        matches!(self, GuardianStatus::Initiated{..} | GuardianStatus::Redeemable{..})
+    }
+}
+
+//checks the vault outputs of the will initiation
+pub fn guardian_status(package: &GuardianPackage, backend: &impl ChainBackend) -> Result<GuardianStatus, WalletError>{
+    let revocation_txid = package.revocation_txid()?;
+    if backend.transaction(&revocation_txid)?.is_some(){
+        return Ok(GuardianStatus::Revoked(revocation_txid))
+    }
+    let initiation = match backend.transaction(&package.initiation_txid)?{
+        Some(initiation) => initiation,
+        None => return Ok(GuardianStatus::Waiting)
+    };
+    if package.vaults.iter().any(|vault| initiation.vout.get(vault.vout as usize).and_then(|vout| vout.spent) == Some(true)){
+        return Ok(GuardianStatus::VaultSpent)
+    }
+
+    let tip = backend.tip_height()?;
+    //an initiation still in the mempool confirms in the next block at the earliest
+    let height = if initiation.confirmations > 0 { initiation.block_height as u64 } else { tip + 1 };
+    let redeemable_height = height + package.vaults.iter().map(|vault| vault.csv_delay).min().unwrap_or(0);
+    match redeemable_height.checked_sub(tip + 1){
+        Some(blocks_left) if blocks_left > 0 => Ok(GuardianStatus::Initiated{height, redeemable_height, blocks_left}),
+        _ => Ok(GuardianStatus::Redeemable{height, redeemable_height}),
+    }
+}
+
+//broadcasts the revocation and returns its txid
+pub fn broadcast_revocation(package: &GuardianPackage, backend: &impl ChainBackend) -> Result<String, WalletError>{
+    backend.broadcast(&package.revocation_hex()?)
+}
+
+//checks the vaults against the testnet backend every interval, following the latest copy of the package sent to it
+pub fn spawn_guardian_watch(package: GuardianPackage, interval: Duration) -> (Sender<GuardianPackage>, Receiver<Result<GuardianStatus, WalletError>>){
+    let (package_sender, packages) = mpsc::channel();
+    let (status_sender, statuses) = mpsc::channel();
+    thread::spawn(move || {
+        let mut package = package;
+        loop{
+            for latest in packages.try_iter(){
+                package = latest;
+            }
+            if status_sender.send(guardian_status(&package, &TestnetBackend)).is_err(){
+                return
+            }
+            thread::sleep(interval);
+        }
+    });
+    (package_sender, statuses)
+}
@@ -0,0 +1,213 @@
+use crate::wallet_info::*;
+use crate::will_components::Outpoint;
+use crate::error::WalletError;
+
+//what a BIP329 label is attached to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelType{
+    Tx,
+    Addr,
+    Pubkey,
+    //a transaction input, referenced as txid:vin
+    Input,
+    //a transaction output or coin, referenced as txid:vout
+    Output,
+    Xpub,
+}
+
+impl LabelType{
+    pub const ALL: [LabelType; 6] = [LabelType::Tx, LabelType::Addr, LabelType::Pubkey, LabelType::Input, LabelType::Output, LabelType::Xpub];
+
+    //txids are compared in lowercase, like BIP329 writes them
+    fn normalize(&self, reference: &str) -> String{
+        match self{
+            LabelType::Tx | LabelType::Input | LabelType::Output => reference.trim().to_lowercase(),
+            _ => reference.trim().to_string()
+        }
+    }
+}
+
+impl std::fmt::Display for LabelType{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let name = match self{
+            LabelType::Tx => "tx",
+            LabelType::Addr => "addr",
+            LabelType::Pubkey => "pubkey",
+            LabelType::Input => "input",
+            LabelType::Output => "output",
+            LabelType::Xpub => "xpub",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for LabelType{
+    type Err = WalletError;
+
+    fn from_str(name: &str) -> Result<Self, WalletError>{
+        LabelType::ALL.iter().copied().find(|label_type| label_type.to_string() == name)
+            .ok_or(WalletError::Parse(format!("Unknown label type: {}", name)))
+    }
+}
+
+//one line of a BIP329 label file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Label{
+    #[serde(rename = "type")]
+    pub label_type: LabelType,
+    #[serde(rename = "ref")]
+    pub reference: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    //only for outputs: false when the coin is frozen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spendable: Option<bool>,
+}
+
+impl Label{
+    pub fn new(label_type: LabelType, reference: &str, label: &str) -> Self{
+        Label{
+            label_type,
+            reference: label_type.normalize(reference),
+            label: label.to_string(),
+            origin: None,
+            spendable: None,
+        }
+    }
+}
+
+//the user's labels on transactions, addresses, coins and anything else BIP329 can label
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Labels{
+    labels: Vec<Label>,
+}
+
+impl Labels{
+    pub fn get(&self, label_type: LabelType, reference: &str) -> Option<&str>{
+        let reference = label_type.normalize(reference);
+        self.labels.iter()
+            .find(|label| label.label_type == label_type && label.reference == reference)
+            .map(|label| label.label.as_str())
+    }
+
+    //an empty label removes it, unless it freezes a coin
+    pub fn set(&mut self, label_type: LabelType, reference: &str, label: &str){
+        self.insert(Label::new(label_type, reference, label));
+    }
+
+    //adds the label, replacing the text of one on the same reference but keeping its origin and spendable flag
+    pub fn insert(&mut self, label: Label){
+        let position = self.labels.iter().position(|kept| kept.label_type == label.label_type && kept.reference == label.reference);
+        match position{
+            Some(i) => {
+                let kept = &mut self.labels[i];
+                kept.label = label.label;
+                kept.origin = label.origin.or(kept.origin.take());
+                kept.spendable = label.spendable.or(kept.spendable);
+                if kept.label.is_empty() && kept.spendable != Some(false){
+                    self.labels.remove(i);
+                }
+            }
+            None if label.label.is_empty() && label.spendable != Some(false) => (),
+            None => self.labels.push(label),
+        }
+    }
+
+    pub fn transaction(&self, txid: &str) -> Option<&str>{
+        self.get(LabelType::Tx, txid)
+    }
+
+    pub fn address(&self, address: &str) -> Option<&str>{
+        self.get(LabelType::Addr, address)
+    }
+
+    pub fn output(&self, txid: &str, vout: u32) -> Option<&str>{
+        self.get(LabelType::Output, &format!("{}:{}", txid, vout))
+    }
+
+    //frozen coins are output labels that are not spendable, so other BIP329 wallets keep them frozen
+    pub fn freeze(&mut self, outpoint: &Outpoint, frozen: bool){
+        let reference = format!("{}:{}", outpoint.txid, outpoint.vout);
+        if frozen{
+            let mut label = Label::new(LabelType::Output, &reference, self.output(&outpoint.txid, outpoint.vout).unwrap_or(""));
+            label.spendable = Some(false);
+            self.insert(label);
+        }else{
+            for label in self.labels.iter_mut().filter(|label| label.label_type == LabelType::Output && label.reference == reference){
+                label.spendable = None;
+            }
+            self.labels.retain(|label| !label.label.is_empty() || label.spendable == Some(false));
+        }
+    }
+
+    pub fn is_frozen(&self, outpoint: &Outpoint) -> bool{
+        self.frozen().contains(outpoint)
+    }
+
+    pub fn frozen(&self) -> Vec<Outpoint>{
+        let mut frozen: Vec<Outpoint> = Vec::new();
+        for label in self.labels.iter().filter(|label| label.label_type == LabelType::Output && label.spendable == Some(false)){
+            if let Some((txid, vout)) = label.reference.rsplit_once(':'){
+                if let Ok(vout) = vout.parse::<u32>(){
+                    frozen.push(Outpoint::new(txid, vout));
+                }
+            }
+        }
+        frozen
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Label>{
+        self.labels.iter()
+    }
+
+    pub fn len(&self) -> usize{
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.labels.is_empty()
+    }
+
+    //one JSON object per line, as BIP329 exports them
+    pub fn to_bip329(&self) -> Result<String, WalletError>{
+        let mut lines = String::new();
+        for label in self.labels.iter(){
+            lines.push_str(&serde_json::to_string(label)?);
+            lines.push('\n');
+        }
+        Ok(lines)
+    }
+
+    //merges a BIP329 export into these labels, its labels replacing ours on the same reference
+    //lines of types this wallet does not know are skipped, as BIP329 asks; returns how many labels were imported
+    pub fn import_bip329(&mut self, jsonl: &str) -> Result<usize, WalletError>{
+        let mut imported = 0;
+        for (i, line) in jsonl.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()){
+            let value: serde_json::Value = serde_json::from_str(line).map_err(|e| WalletError::Parse(format!("label line {} is not JSON [Error: {}]", i + 1, e)))?;
+            if value["type"].as_str().map(|name| name.parse::<LabelType>().is_err()).unwrap_or(true){
+                continue
+            }
+            let mut label: Label = serde_json::from_value(value).map_err(|e| WalletError::Parse(format!("invalid label on line {} [Error: {}]", i + 1, e)))?;
+            label.reference = label.label_type.normalize(&label.reference);
+            self.insert(label);
+            imported += 1;
+        }
+        Ok(imported)
+    }
+}
+
+impl WalletInfo{
+    //the wallet's labels plus the inheritors' addresses, named after the inheritor unless the user labeled them
+    pub fn export_labels(&self) -> Labels{
+        let mut labels = self.labels.clone();
+        for inheritor in self.inheritors.iter(){
+            if labels.address(&inheritor.address).is_none(){
+                labels.set(LabelType::Addr, &inheritor.address, &format!("Inheritor {}", inheritor.name));
+            }
+        }
+        labels
+    }
+}
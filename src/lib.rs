@@ -0,0 +1,44 @@
+//! Bitcoin wallet and will builder behind the ION Wallet GUI and command line.
+//!
+//! The `wallet`, `will`, `transaction`, `backend` and `storage` modules are the API for other programs;
+//! the modules they are built from stay public for the binaries in this package.
+
+#[macro_use]
+extern crate serde_derive;
+
+pub mod wallet;
+pub mod will;
+pub mod transaction;
+pub mod backend;
+pub mod storage;
+pub mod error;
+
+pub use error::WalletError;
+
+pub mod traits;
+pub mod wallet_info;
+pub mod labels;
+pub mod export;
+pub mod file;
+pub mod cache;
+pub mod blockchain_info;
+pub mod blockchain_status;
+pub mod blockchain_address;
+pub mod blockchain_transaction;
+pub mod blockchain_utxo;
+pub mod create_transaction;
+pub mod coin_control;
+pub mod payment;
+pub mod watch_only;
+pub mod descriptor;
+pub mod policy;
+pub mod script;
+pub mod message;
+pub mod transaction_parts;
+pub mod will_components;
+pub mod psbt;
+pub mod will_status;
+pub mod revocation_watcher;
+pub mod decode_transaction;
+pub mod claim;
+pub mod guardian;
@@ -1,9 +1,6 @@
-#[macro_use]
-extern crate serde_derive;
-
 use iced::theme;
 use iced::widget::{container, horizontal_space, vertical_space};
-use iced::{Color, Element, Length, Sandbox, Settings};
+use iced::{executor, Application, Color, Command, Element, Length, Sandbox, Settings, Theme};
 use iced::widget::{self, button, row, column, text, text_input, scrollable, checkbox, pick_list};
 use iced::alignment::{self, Alignment};
 use secp256k1::rand::rngs::*;
@@ -12,6 +9,12 @@ use secp256k1::*;
 use sha2::{Sha256, Digest};
 
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{Sender, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use futures::channel::oneshot;
 
 use numeric_input::numeric_input;
 use crate::write_file::write_file;
@@ -19,29 +22,21 @@ use number_input::number_input;
 use number_input_1::number_input_1;
 use number_input_2::number_input_2;
 
-mod traits;
-mod wallet_info;
-mod file;
-mod blockchain_info;
-mod blockchain_status;
-mod blockchain_address;
-mod blockchain_transaction;
-mod blockchain_utxo;
-mod create_transaction;
-mod transaction_parts;
-mod will_components;
-
-use crate::blockchain_info::*;
-use crate::traits::*;
-use crate::wallet_info::*;
-use crate::file::*;
-use crate::blockchain_status::BlockchainStatus;
-use crate::blockchain_address::BlockchainAddress;
-use crate::blockchain_transaction::BlockchainTransaction;
-use crate::blockchain_utxo::UTXO;
-use crate::create_transaction::*;
-use crate::transaction_parts::*;
-use crate::will_components::*;
+use ion_wallet::backend::*;
+use ion_wallet::blockchain_info::*;
+use ion_wallet::traits::*;
+use ion_wallet::wallet_info::*;
+use ion_wallet::labels::*;
+use ion_wallet::export::*;
+use ion_wallet::file::*;
+use ion_wallet::cache::*;
+use ion_wallet::coin_control::*;
+use ion_wallet::payment::*;
+use ion_wallet::will_components::Outpoint;
+use ion_wallet::create_transaction::*;
+use ion_wallet::transaction_parts::*;
+use ion_wallet::will::*;
+use ion_wallet::WalletError;
 
 
 fn main() -> iced::Result {
@@ -54,19 +49,45 @@ struct App{
     set_will: SetWill,
     transaction_history: ViewTransactionHistory,
     send_transaction: SendTransaction,
+    will_stale: bool,
+    regenerate_password: String,
+    error: String,
+    progress: String,
+    will_state: WillState,
+    watcher: Option<(Sender<WatcherCommand>, Receiver<WatchEvent>)>,
+    watch_event: WatchEvent,
+    claim_inheritance: ClaimInheritance,
+    guardian_mode: GuardianMode,
+    cache: Option<Arc<Cache>>,
+    //the wallet's coins as of the last refresh
+    utxos: Vec<UTXO>,
 }
 
-impl Sandbox for App{
+impl Application for App{
+    type Executor = executor::Default;
     type Message = Message;
+    type Theme = Theme;
+    type Flags = ();
 
-    fn new() -> Self{
-        App {
+    fn new(_flags: ()) -> (Self, Command<Message>){
+        (App {
             info: WalletInfo::new_empty(),
             launch: Launch::new(),
             set_will: SetWill::new(),
             transaction_history: ViewTransactionHistory::new(),
             send_transaction: SendTransaction::new(),
-        }
+            will_stale: false,
+            regenerate_password: String::new(),
+            error: String::new(),
+            progress: String::new(),
+            will_state: WillState::NoWill,
+            watcher: None,
+            watch_event: WatchEvent::Quiet,
+            claim_inheritance: ClaimInheritance::new(),
+            guardian_mode: GuardianMode::new(),
+            cache: None,
+            utxos: Vec::new(),
+        }, Command::none())
     }
 
     fn title(&self) -> String{
@@ -76,7 +97,11 @@ impl Sandbox for App{
     fn view(&self) -> Element<Message>{
         let mut master = row![];
 
-        if self.launch.locked(){
+        if self.claim_inheritance.is_on(){
+            master = master.push(self.claim_inheritance.view());
+        }else if self.guardian_mode.is_on(){
+            master = master.push(self.guardian_mode.view().map(Message::GuardianMessage));
+        }else if self.launch.locked(){
             master = master.push(self.launch.view());
         }else{
             let balance = row![
@@ -91,7 +116,45 @@ impl Sandbox for App{
             let mut inheritors_list: String = String::new();
             for (i,inheritor) in self.info.inheritors.iter().enumerate(){
                 inheritors_list.push_str(&format!("Inheritor{}: {}\n", i+1, inheritor.address.shorten(14)));
-                inheritors_list.push_str(&format!("{} BTC\n\n", inheritor.value as f64 / 100000000.0));
+                inheritors_list.push_str(&format!("{} (≈{} BTC)", inheritor.share_percent(), (self.info.value as f64 * inheritor.share as f64 / 10000.0) / 100000000.0));
+                inheritors_list.push_str(&format!(" after {} Blocks\n\n", inheritor.locktime_blocks(self.info.locktime)));
+            }
+
+            let mut will_status = column![
+                text("Will Status:").size(25),
+                text(self.will_state.description()),
+            ].spacing(10);
+            if let Some(alert) = self.will_state.alert(){
+                will_status = will_status.push(text(alert).size(25).style(Color::from([1.0, 0.0, 0.0])));
+            }
+            match &self.watch_event{
+                WatchEvent::InitiationDetected{blocks_left, ..} => {
+                    will_status = will_status.push(text(format!("If you do not check in within {} blocks: {}", blocks_left, self.info.revocation_settings.action)));
+                    will_status = will_status.push(button("Check In").on_press(Message::CheckIn));
+                }
+                WatchEvent::Notify(x) => will_status = will_status.push(text(x).style(Color::from([1.0, 0.0, 0.0]))),
+                WatchEvent::Broadcast(txid) => will_status = will_status.push(text(format!("Will revocation broadcast: {}", txid))),
+                WatchEvent::Failed(e) => will_status = will_status.push(text(format!("Revocation watcher: {}", e))),
+                WatchEvent::Quiet => (),
+            }
+            will_status = will_status.push(text("If the will is initiated and you do not check in:"));
+            will_status = will_status.push(row![
+                pick_list(&RevocationAction::ALL[..], Some(self.info.revocation_settings.action), Message::SetRevocationAction),
+                text("within"),
+                number_input_2(self.info.revocation_settings.window_blocks, Message::SetRevocationWindow),
+                text("Blocks"),
+            ].spacing(10).align_items(Alignment::Center));
+            if self.will_stale && self.info.is_watch_only(){
+                will_status = will_status.push(text("The coins have changed since the will was made, so the will initiation can no longer be broadcast. The wallet owner must regenerate the will.").style(Color::from([1.0, 0.0, 0.0])));
+            }else if self.will_stale{
+                will_status = will_status.push(text("Your coins have changed since the will was made, so the will initiation can no longer be broadcast. Enter your password to regenerate the will.").style(Color::from([1.0, 0.0, 0.0])));
+                will_status = will_status.push(text_input("Password", &self.regenerate_password, Message::TypeRegeneratePassword).password());
+                will_status = will_status.push(button("Regenerate Will").on_press(Message::RegenerateWill));
+            }
+            let outdated = self.info.outdated_holders();
+            if !outdated.is_empty(){
+                will_status = will_status.push(text(format!("Give the latest will parts (Generation {}) to: {}", self.info.will_generation(), outdated.join(", "))));
+                will_status = will_status.push(button("Mark Will Parts Distributed").on_press(Message::MarkWillDistributed));
             }
 
             let redemption_period = column![
@@ -99,19 +162,28 @@ impl Sandbox for App{
                 text(format!("{} Blocks (≈{} Days)", self.info.locktime, self.info.locktime/144)),
             ].spacing(10);
             
+            let address = match self.info.addresses().len(){
+                1 => format!("Address:\n{}", self.info.address),
+                n => format!("Address:\n{}\nand {} more", self.info.address, n - 1)
+            };
             let info_column = column![
-                text(format!("Address:\n{}", self.info.address)).size(25),
+                text(if self.info.is_watch_only() { "Watch-only: this wallet has no key and cannot sign" } else { "" }),
+                text(address).size(25),
                 text(inheritors_list).size(25),
                 redemption_period,
+                will_status,
             ].spacing(40).width(Length::Units(400));
 
-            let buttons = column![
+            let mut buttons = column![
                 vertical_space(Length::Units(40)),
                 button("Update").on_press(Message::Update),
-                button("Send Coins").on_press(Message::OpenSendTransaction),
-                button("Set Will / Change Will").on_press(Message::OpenSetWill),
-                button("View Transaction History").on_press(Message::OpenHistory),
+                button(if self.info.is_watch_only() { "Create Unsigned Transaction" } else { "Send Coins" }).on_press(Message::OpenSendTransaction),
             ].spacing(30).width(Length::Units(260)).align_items(Alignment::End);
+            //a watch-only wallet cannot sign a will
+            if !self.info.is_watch_only(){
+                buttons = buttons.push(button("Set Will / Change Will").on_press(Message::OpenSetWill));
+            }
+            buttons = buttons.push(button("View Transaction History").on_press(Message::OpenHistory));
             
             let double = row![
                 info_column,
@@ -120,10 +192,16 @@ impl Sandbox for App{
             ].width(Length::Units(710)).spacing(10);
             
 
-            let main_content = column![
+            let mut main_content = column![
                 container(balance).width(Length::Units(740)).center_x(),
-                double,
             ].height(Length::Units(800)).spacing(1);
+            if !self.progress.is_empty(){
+                main_content = main_content.push(container(text(&self.progress)).width(Length::Units(740)).center_x());
+            }
+            if !self.error.is_empty(){
+                main_content = main_content.push(container(text(&self.error).style(Color::from([1.0, 0.0, 0.0]))).width(Length::Units(740)).center_x());
+            }
+            main_content = main_content.push(double);
 
             let main = container(main_content).width(Length::Units(740)).center_y();
 
@@ -133,7 +211,7 @@ impl Sandbox for App{
                 master = master.push(self.set_will.view().map(Message::SetWillMessage))
             }
             if self.transaction_history.is_on(){
-                master = master.push(self.transaction_history.view())
+                master = master.push(self.transaction_history.view(&self.info.labels))
             }
             if self.send_transaction.is_on(){
                 master = master.push(self.send_transaction.view().map(Message::TransactionMessage))
@@ -147,20 +225,39 @@ impl Sandbox for App{
         total
     }
 
-    fn update(&mut self, message: Message){
+    fn update(&mut self, message: Message) -> Command<Message>{
+        let mut command = Command::none();
+        if let Some((_, events)) = &self.watcher{
+            for event in events.try_iter(){
+                self.watch_event = event;
+            }
+        }
+        self.claim_inheritance.receive();
+        self.guardian_mode.receive();
         match message{
             Message::OpenSetWill => {
+                self.set_will.initiation_data = self.info.initiation_data.clone();
                 self.set_will.on()
             }
             Message::SetWillMessage(x) => {
                 match x {
+                    //the vaults are generated here, where the wallet's public key is, and the will is built with them on Finish
+                    SetWillMessage::Review => {
+                        match self.will_info().and_then(|info| will_vaults(&info)){
+                            Ok(vaults) => self.set_will.review(&self.info.pubkey, vaults),
+                            Err(e) => self.set_will.set_error(e.to_string())
+                        }
+                    }
                     SetWillMessage::Finish => {
-                        self.info.inheritors = self.set_will.inheritors.clone();
-                        self.info.guardians = self.set_will.guardians.clone();
-                        self.info.locktime = self.set_will.pages.get_locktime_blocks();
-                        write_wallet(self.info.clone(), &self.launch.password);
-                        self.set_will.create_will(&mut self.info);
-                        self.set_will.update(x)
+                        match self.will_info(){
+                            Ok(info) => self.info = info,
+                            Err(e) => {
+                                self.set_will.set_error(e.to_string());
+                                return Command::none()
+                            }
+                        }
+                        self.progress = String::from("Building the will...");
+                        command = build_will(self.info.clone(), self.set_will.password.clone(), Some(self.set_will.pages.vaults()));
                     }
                     _ => {
                         self.set_will.update(x)
@@ -172,40 +269,312 @@ impl Sandbox for App{
             }
             Message::EnterPassword => {
                 match self.launch.enter_password(){
-                    Ok(x) => self.info = x,
+                    Ok(x) => {
+                        self.info = x;
+                        self.send_transaction.watch_only = self.info.is_watch_only();
+                        self.open_cache();
+                        self.will_state = off_chain_will_state(&self.info);
+                        self.watcher = Some(spawn_watcher(self.info.clone(), Duration::from_secs(60)));
+                    }
                     _ => ()
                 };
             }
             Message::Update => {
-                let address = blockchain_info::testnet_address_request(&self.info.address);
-                self.info.value = address.balance.parse::<u64>().unwrap();
-                write_wallet(self.info.clone(), &self.launch.password);
-                write_transaction_history(blockchain_info::testnet_address_history(&self.info.address, 6), &self.launch.password);
-                self.transaction_history.set(read_transaction_history(&self.launch.password));
+                self.error.clear();
+                self.progress = String::from("Loading balance...");
+                command = Command::perform(load_coins(self.cache.clone(), self.info.addresses()), Message::CoinsLoaded);
+            }
+            Message::CoinsLoaded(Ok((value, utxos))) => {
+                self.info.value = value;
+                self.utxos = utxos;
+                self.check_coins();
+                self.save();
+                let addresses = self.info.addresses();
+                command = match self.cache.clone(){
+                    //the sync has already downloaded the new transactions
+                    Some(cache) => self.update(Message::HistoryLoaded(cache.history_page(&addresses, 1, HISTORY_PAGE_SIZE))),
+                    None => {
+                        self.progress = String::from("Loading transaction history...");
+                        Command::perform(async move { testnet_wallet_history_page(&addresses, 1, HISTORY_PAGE_SIZE).await }, Message::HistoryLoaded)
+                    }
+                };
+            }
+            Message::HistoryLoaded(Ok(mut history)) => {
+                mark_will_transactions(&mut history.transactions, &known_wills(&self.info));
+                if let Err(e) = write_transaction_history(history.transactions.clone(), &self.launch.password){
+                    self.error = e.to_string();
+                }
+                self.transaction_history.set(Ok(history));
+                self.progress = String::from("Checking the will on chain...");
+                let info = self.info.clone();
+                command = Command::perform(unblock(move || will_state(&info, &TestnetBackend)), Message::WillStateLoaded);
+            }
+            Message::WillStateLoaded(Ok(state)) => {
+                self.will_state = state;
+                self.progress.clear();
+            }
+            Message::CoinsLoaded(Err(e)) | Message::HistoryLoaded(Err(e)) | Message::WillStateLoaded(Err(e)) => {
+                self.error = e.to_string();
+                self.progress.clear();
             }
             Message::OpenHistory => {
                 self.transaction_history.on();
                 if self.transaction_history.is_empty(){
-                    self.transaction_history.set(read_transaction_history(&self.launch.password));
+                    self.transaction_history.set(read_transaction_history(&self.launch.password).map(|history| HistoryPage::new(history, 1, 1)));
+                };
+            }
+            Message::ShowHistoryPage(page) => {
+                let addresses = self.info.addresses();
+                command = match self.cache.clone(){
+                    Some(cache) => self.update(Message::HistoryPageLoaded(cache.history_page(&addresses, page, HISTORY_PAGE_SIZE))),
+                    None => {
+                        self.progress = String::from("Loading transaction history...");
+                        Command::perform(async move { testnet_wallet_history_page(&addresses, page, HISTORY_PAGE_SIZE).await }, Message::HistoryPageLoaded)
+                    }
                 };
             }
+            Message::HistoryPageLoaded(result) => {
+                self.progress.clear();
+                match result{
+                    Ok(mut history) => {
+                        mark_will_transactions(&mut history.transactions, &known_wills(&self.info));
+                        self.transaction_history.set(Ok(history));
+                    }
+                    Err(e) => self.error = e.to_string()
+                }
+            }
             Message::CloseHistory => {
                 self.transaction_history.off();
+                self.save();
+            }
+            Message::SetTransactionLabel(txid, label) => {
+                self.info.labels.set(LabelType::Tx, &txid, &label);
+            }
+            Message::SaveLabels => {
+                self.save();
+            }
+            Message::TypeLabelsPath(x) => {
+                self.transaction_history.labels_path = x;
+            }
+            Message::ImportLabels => {
+                self.transaction_history.labels_status = match read_labels(&self.transaction_history.labels_path, &mut self.info.labels){
+                    Ok(imported) => format!("{} labels imported", imported),
+                    Err(e) => e.to_string()
+                };
+                self.save();
+            }
+            Message::TypeExportPath(x) => {
+                self.transaction_history.export_path = x;
+            }
+            Message::TypePricesPath(x) => {
+                self.transaction_history.prices_path = x;
+            }
+            Message::ExportHistory => {
+                self.progress = String::from("Exporting the transaction history...");
+                let (addresses, labels, wills) = (self.info.addresses(), self.info.labels.clone(), known_wills(&self.info));
+                let (path, prices_path) = (self.transaction_history.export_path.clone(), self.transaction_history.prices_path.clone());
+                command = Command::perform(export_history(self.cache.clone(), addresses, labels, wills, path, prices_path), Message::HistoryExported);
+            }
+            Message::HistoryExported(result) => {
+                self.progress.clear();
+                self.transaction_history.labels_status = match result{
+                    Ok(count) => format!("{} transactions written to {}", count, self.transaction_history.export_path),
+                    Err(e) => e.to_string()
+                };
+            }
+            Message::ExportLabels => {
+                let labels = self.info.export_labels();
+                self.transaction_history.labels_status = match write_labels(&self.transaction_history.labels_path, &labels){
+                    Ok(()) => format!("{} labels written to {}", labels.len(), self.transaction_history.labels_path),
+                    Err(e) => e.to_string()
+                };
             }
             Message::TransactionMessage(x) => {
                 match x{
                     TransactionMessage::Create => {
-                        self.send_transaction.create_transaction(&mut self.info);
-                        self.send_transaction.update(x)
+                        self.progress = String::from("Signing the transaction...");
+                        command = self.send_transaction.create_transaction(self.info.clone());
+                        self.send_transaction.update(x);
+                    }
+                    TransactionMessage::CreatePsbt => {
+                        command = self.send_transaction.create_psbt(self.info.clone());
+                    }
+                    TransactionMessage::ToggleFreeze(outpoint) => {
+                        let frozen = self.info.labels.is_frozen(&outpoint);
+                        self.info.labels.freeze(&outpoint, !frozen);
+                        self.check_coins();
+                        self.save();
                     }
                     _ => {
-                        self.send_transaction.update(x)
+                        command = self.send_transaction.update(x).map(Message::TransactionMessage)
+                    }
+                }
+            }
+            Message::TransactionSigned(result) => {
+                self.progress.clear();
+                match result{
+                    Ok(signed) => {
+                        if let Some(e) = signed.will_error{
+                            self.send_transaction.update_debug(format!("Will parts were not updated: {}", e));
+                        }
+                        self.send_transaction.set_transaction(signed.transaction);
+                        self.info = signed.will_info;
+                        self.record_will();
+                        self.will_state = off_chain_will_state(&self.info);
+                        self.check_coins();
+                        self.save();
                     }
+                    Err(e) => self.send_transaction.update_debug(e.to_string())
                 }
             }
+            Message::PsbtCreated(result) => {
+                self.send_transaction.update_debug(match result{
+                    Ok(psbt) => format!("Unsigned PSBT to sign with the wallet key:\n{}", psbt),
+                    Err(e) => e.to_string()
+                });
+            }
             Message::OpenSendTransaction => {
                 self.send_transaction.on()
             }
+            Message::TypeRegeneratePassword(x) => {
+                self.regenerate_password = x;
+            }
+            Message::RegenerateWill => {
+                self.progress = String::from("Building the will...");
+                command = build_will(self.info.clone(), std::mem::take(&mut self.regenerate_password), None);
+            }
+            Message::WillBuilt(result) => {
+                self.progress.clear();
+                match result{
+                    Ok(info) => {
+                        self.info = info;
+                        self.error.clear();
+                        self.record_will();
+                        self.will_stale = false;
+                        self.will_state = off_chain_will_state(&self.info);
+                        self.save();
+                        if self.set_will.is_on(){
+                            self.set_will.update(SetWillMessage::Finish);
+                        }
+                    }
+                    Err(e) if self.set_will.is_on() => self.set_will.set_error(e.to_string()),
+                    Err(e) => self.error = e.to_string()
+                }
+            }
+            Message::MarkWillDistributed => {
+                self.info.mark_will_distributed();
+                self.will_state = off_chain_will_state(&self.info);
+                self.save();
+            }
+            Message::OpenClaim => {
+                self.claim_inheritance.on();
+            }
+            Message::CloseClaim => {
+                self.claim_inheritance.off();
+            }
+            Message::TypeClaimPath(x) => {
+                self.claim_inheritance.path = x;
+            }
+            Message::ImportClaim => {
+                self.claim_inheritance.import();
+            }
+            Message::RefreshClaim => (),
+            Message::OpenGuardianMode => {
+                self.guardian_mode.on();
+            }
+            Message::GuardianMessage(x) => {
+                command = self.guardian_mode.update(x).map(Message::GuardianMessage);
+            }
+            Message::CheckIn => {
+                if let Some((commands, _)) = &self.watcher{
+                    let _ = commands.send(WatcherCommand::CheckIn);
+                }
+                self.watch_event = WatchEvent::Quiet;
+            }
+            Message::SetRevocationAction(x) => {
+                self.info.revocation_settings.action = x;
+                self.save();
+            }
+            Message::SetRevocationWindow(x) => {
+                self.info.revocation_settings.window_blocks = x;
+                self.save();
+            }
+        }
+        //keep the watcher on the latest will and settings
+        if let Some((commands, _)) = &self.watcher{
+            let _ = commands.send(WatcherCommand::Update(Box::new(self.info.clone())));
+        }
+        command
+    }
+}
+
+impl App{
+    //opens the local cache and shows what it knew at the last sync until the next update
+    fn open_cache(&mut self){
+        match unlock_cache(&self.launch.password){
+            Ok(cache) => {
+                self.cache = Some(cache.clone());
+                //shows the cached balance until the next update, once every address has been synced
+                let records: Result<Vec<AddressRecord>, WalletError> = self.info.addresses().iter().map(|address| cache.address(address)).collect();
+                if let Ok(records) = records{
+                    if records.iter().all(|record| record.synced_height > 0){
+                        self.info.value = records.iter().map(|record| record.balance).sum();
+                        self.utxos = records.into_iter().flat_map(|record| record.utxos).collect();
+                        self.check_coins();
+                    }
+                }
+                self.record_will();
+                let mut history = cache.history_page(&self.info.addresses(), 1, HISTORY_PAGE_SIZE);
+                if let Ok(history) = &mut history{
+                    mark_will_transactions(&mut history.transactions, &known_wills(&self.info));
+                }
+                self.transaction_history.set(history);
+            }
+            Err(e) => self.error = format!("Working without the local cache: {}", e)
+        }
+    }
+
+    //keeps the new will generation in the cache
+    fn record_will(&mut self){
+        if let (Some(cache), Some(will)) = (&self.cache, &self.info.will){
+            if let Err(e) = cache.record_will(&self.info.address, will){
+                self.error = e.to_string();
+            }
+        }
+    }
+
+    //lists the coins in the send panel and checks whether the will still spends every coin that is not frozen
+    fn check_coins(&mut self){
+        let spendable = self.info.coin_control().spendable(self.utxos.clone());
+        self.will_stale = match &self.info.will{
+            Some(will) => will.is_stale(&spendable),
+            None => false
+        };
+        match list_coins(&self.utxos, &self.info){
+            Ok(coins) => self.send_transaction.set_coins(coins),
+            Err(e) => self.error = e.to_string()
+        }
+    }
+
+    //the wallet with the will chosen in the set will pages, once the inheritor shares add up
+    fn will_info(&self) -> Result<WalletInfo, WalletError>{
+        let inheritors = self.set_will.inheritors_with_locktimes();
+        inheritors.validate_shares()?;
+        let mut info = self.info.clone();
+        info.inheritors = inheritors;
+        info.guardians = self.set_will.guardians.clone();
+        info.guardian_threshold = self.set_will.pages.guardian_threshold();
+        info.locktime = self.set_will.pages.get_locktime_blocks();
+        info.initiation_data = self.set_will.initiation_data.trim().to_string();
+        //every redemption delay must fit a vault's relative timelock
+        info.inheritors.tranches(info.locktime)?;
+        Ok(info)
+    }
+
+    //writes the wallet file, showing why if it could not be saved
+    fn save(&mut self){
+        if let Err(e) = write_wallet(self.info.clone(), &self.launch.password){
+            self.error = e.to_string();
         }
     }
 }
@@ -218,22 +587,133 @@ enum Message{
     TypePassword(String),
     EnterPassword,
     Update,
+    CoinsLoaded(Result<(u64, Vec<UTXO>), WalletError>),
+    HistoryLoaded(Result<HistoryPage, WalletError>),
+    WillStateLoaded(Result<WillState, WalletError>),
     OpenHistory,
     CloseHistory,
+    ShowHistoryPage(u64),
+    SetTransactionLabel(String, String),
+    SaveLabels,
+    TypeLabelsPath(String),
+    ImportLabels,
+    ExportLabels,
+    TypeExportPath(String),
+    TypePricesPath(String),
+    ExportHistory,
+    HistoryExported(Result<usize, WalletError>),
+    HistoryPageLoaded(Result<HistoryPage, WalletError>),
     TransactionMessage(TransactionMessage),
+    TransactionSigned(Result<SignedSend, WalletError>),
+    PsbtCreated(Result<String, WalletError>),
     OpenSendTransaction,
+    TypeRegeneratePassword(String),
+    RegenerateWill,
+    WillBuilt(Result<WalletInfo, WalletError>),
+    MarkWillDistributed,
+    OpenClaim,
+    CloseClaim,
+    TypeClaimPath(String),
+    ImportClaim,
+    RefreshClaim,
+    OpenGuardianMode,
+    GuardianMessage(GuardianMessage),
+    CheckIn,
+    SetRevocationAction(RevocationAction),
+    SetRevocationWindow(u32),
+}
+
+//creates will parts from the wallet's current coins with the key unlocked by the password, in the vaults the user
+//reviewed or, when the will is rebuilt without the set will pages, in new ones
+fn regenerate_will(will_info: &mut WalletInfo, password: &str, vaults: Option<Vec<TimelockComponents>>) -> Result<(), WalletError>{
+    let secretkey = SecretKey::from_str(&read_keys(password)?).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")))?;
+    match vaults{
+        Some(vaults) => create_will_with_vaults(will_info, secretkey, vaults),
+        None => create_will(will_info, secretkey)
+    }
+}
+
+//creates the will parts away from the window, since it asks the block explorer for the wallet's coins
+fn build_will(mut will_info: WalletInfo, password: String, vaults: Option<Vec<TimelockComponents>>) -> Command<Message>{
+    Command::perform(unblock(move || regenerate_will(&mut will_info, &password, vaults).map(|()| will_info)), Message::WillBuilt)
+}
+
+//the balance and coins of every address of the wallet, synced through the cache when it is open
+async fn load_coins(cache: Option<Arc<Cache>>, addresses: Vec<String>) -> Result<(u64, Vec<UTXO>), WalletError>{
+    let (mut total, mut coins) = (0, Vec::new());
+    for address in addresses.iter(){
+        let (balance, mut utxos) = match &cache{
+            Some(cache) => cache.sync(address).await.map(|record| (record.balance, record.utxos))?,
+            None => {
+                let (balance, utxos) = futures::future::join(testnet_address(address), testnet_utxos(address)).await;
+                (parse_satoshis(&balance?.balance)?, utxos?)
+            }
+        };
+        total += balance;
+        coins.append(&mut utxos);
+    }
+    Ok((total, coins))
+}
+
+//writes the whole history for accounting, valued with the price file when one is given
+async fn export_history(cache: Option<Arc<Cache>>, addresses: Vec<String>, labels: Labels, wills: Vec<WillRecord>, path: String, prices_path: String) -> Result<usize, WalletError>{
+    let mut history = wallet_full_history(cache.as_deref(), &addresses).await?;
+    mark_will_transactions(&mut history, &wills);
+    let prices = match prices_path.is_empty(){
+        true => None,
+        false => Some(CsvPriceFile::open(&prices_path)?)
+    };
+    let rows = export_rows(&history, &labels, prices.as_ref().map(|prices| prices as &dyn PriceSource))?;
+    write_history_export(&path, &rows)?;
+    Ok(rows.len())
+}
+
+//runs blocking wallet work, like signing or building a will, on its own thread so the window keeps drawing
+async fn unblock<T: Send + 'static>(work: impl FnOnce() -> Result<T, WalletError> + Send + 'static) -> Result<T, WalletError>{
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+    receiver.await.unwrap_or_else(|_| Err(WalletError::Policy(String::from("The wallet stopped before it finished"))))
+}
+
+//a signed transaction and the wallet with its will moved onto the change
+#[derive(Debug, Clone)]
+struct SignedSend{
+    transaction: SignedTransaction,
+    will_info: WalletInfo,
+    //the transaction is still good when the will could not be moved
+    will_error: Option<WalletError>,
+}
+
+fn sign_send(payment: Payment, password: &str, mut will_info: WalletInfo, coins: CoinControl) -> Result<SignedSend, WalletError>{
+    let secretkey = SecretKey::from_str(&read_keys(password)?).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")))?;
+    let transaction = create_transaction(&payment, &will_info.address, &will_info.pubkey, secretkey, &coins)?;
+    let will_error = predict_will(&mut will_info, transaction.clone(), secretkey).err();
+    Ok(SignedSend{transaction, will_info, will_error})
 }
 
 struct SendTransaction{
     signed_transaction: Option<SignedTransaction>,
     signed_transaction_string: Option<String>,
-    address: String,
-    value: u64,
+    //address and satoshis of each recipient
+    recipients: Vec<(String, u64)>,
+    //the recipient sent everything left after the others and the fee
+    send_max: Option<usize>,
+    //the recipient whose amount the fee comes out of
+    fee_from: Option<usize>,
+    //hex data for an OP_RETURN output
+    data: String,
     fee: u64,
+    //builds unsigned PSBTs instead of signing
+    watch_only: bool,
     on: bool,
     password: String,
     enter_password: bool,
     debug: String,
+    coins: Vec<Coin>,
+    //coins picked by hand; none means the wallet picks
+    selected: Vec<Outpoint>,
 }
 
 impl SendTransaction{
@@ -242,13 +722,18 @@ impl SendTransaction{
         SendTransaction{
             signed_transaction: None,
             signed_transaction_string: None,
-            address: String::new(),
-            value: 0,
+            recipients: vec![(String::new(), 0)],
+            send_max: None,
+            fee_from: None,
+            data: String::new(),
             fee: 0,
+            watch_only: false,
             on: false,
             password: String::new(),
             enter_password: false,
             debug: String::new(),
+            coins: Vec::new(),
+            selected: Vec::new(),
         }
     }
 
@@ -257,9 +742,32 @@ impl SendTransaction{
             row![horizontal_space(Length::Fill), button("x").on_press(TransactionMessage::Close)],
         ].width(Length::Units(640)).align_items(Alignment::Start).height(Length::Units(750)).spacing(25);
 
-        contents = contents.push(text_input("Address:", &self.address, TransactionMessage::SetAddress));
-        contents = contents.push(number_input(self.value, TransactionMessage::SetValue));
+        for (i, (address, value)) in self.recipients.iter().enumerate(){
+            let mut recipient = row![text_input("Address:", address, move |x| TransactionMessage::SetAddress(i, x))].spacing(15).align_items(Alignment::Center);
+            recipient = match self.send_max == Some(i){
+                true => recipient.push(text(match self.payment().and_then(|payment| payment.amounts(self.spendable_total())){
+                    Ok(amounts) => format!("{} sats", amounts[i]),
+                    Err(e) => e.to_string()
+                })),
+                false => recipient.push(number_input(*value, move |x| TransactionMessage::SetValue(i, x)))
+            };
+            recipient = recipient.push(checkbox("Max", self.send_max == Some(i), move |_| TransactionMessage::ToggleMax(i)));
+            recipient = recipient.push(checkbox("Pays Fee", self.fee_from == Some(i), move |_| TransactionMessage::ToggleFeeFrom(i)));
+            if self.recipients.len() > 1{
+                recipient = recipient.push(button("Remove").on_press(TransactionMessage::RemoveRecipient(i)));
+            }
+            contents = contents.push(recipient);
+        }
+        contents = contents.push(button("Add Recipient").on_press(TransactionMessage::AddRecipient));
         contents = contents.push(number_input(self.fee, TransactionMessage::SetFee));
+        contents = contents.push(text_input("OP_RETURN data (hex, optional):", &self.data, TransactionMessage::SetData));
+        contents = contents.push(self.coins_view());
+        if self.watch_only{
+            contents = contents.push(button("Create Unsigned PSBT").on_press(TransactionMessage::CreatePsbt));
+            contents = contents.push(text(&self.debug));
+            contents = contents.push(vertical_space(Length::Fill));
+            return column![container(contents).height(Length::Fill).center_x().center_y()].into()
+        }
         contents = contents.push(button("Create Transaction (Unsigned)").on_press(TransactionMessage::EnterInfo));
 
         if self.enter_password{
@@ -273,16 +781,66 @@ impl SendTransaction{
         column![container(contents).height(Length::Fill).center_x().center_y()].into()
     }
 
-    fn update(&mut self, message: TransactionMessage){
+    //each coin with a box to spend it and a button to freeze it; frozen coins cannot be picked
+    fn coins_view(&self) -> Element<TransactionMessage>{
+        let mut coins = column![
+            text(if self.selected.is_empty() { "Coins (the wallet picks them)" } else { "Coins (spending the ones checked)" })
+        ].spacing(10);
+        for coin in self.coins.iter(){
+            let outpoint = coin.outpoint.clone();
+            let name = format!("{}:{}", coin.outpoint.txid, coin.outpoint.vout);
+            let mut entry = row![].spacing(15).align_items(Alignment::Center);
+            entry = match coin.frozen{
+                true => entry.push(text(format!("{} (frozen)", name))),
+                false => entry.push(checkbox(name, self.selected.contains(&coin.outpoint), move |_| TransactionMessage::ToggleCoin(outpoint.clone())))
+            };
+            entry = entry.push(text(format!("{} sats", coin.value)));
+            entry = entry.push(text(format!("{} conf", coin.confirmations)));
+            if !coin.label.is_empty(){
+                entry = entry.push(text(&coin.label));
+            }
+            if coin.in_will{
+                entry = entry.push(text("In will"));
+            }
+            entry = entry.push(button(if coin.frozen { "Unfreeze" } else { "Freeze" }).on_press(TransactionMessage::ToggleFreeze(coin.outpoint.clone())));
+            coins = coins.push(entry);
+        }
+        scrollable(coins).height(Length::Units(160)).into()
+    }
+
+    fn update(&mut self, message: TransactionMessage) -> Command<TransactionMessage>{
         match message{
+            TransactionMessage::ToggleCoin(outpoint) => {
+                match self.selected.iter().position(|selected| *selected == outpoint){
+                    Some(i) => {self.selected.remove(i);}
+                    None => self.selected.push(outpoint)
+                }
+            }
+            //freezing changes the wallet's labels, and the PSBT is built from the wallet's addresses, so the app handles them
+            TransactionMessage::ToggleFreeze(_) | TransactionMessage::CreatePsbt => (),
             TransactionMessage::Save => {
                 self.update_debug(
                     self.signed_transaction_string.clone()
                     .unwrap_or(String::from("No Transaction Created"))
                 )
             }
-            TransactionMessage::SetAddress(x) => {self.address = x}
-            TransactionMessage::SetValue(x) => {self.value = x}
+            TransactionMessage::SetAddress(i, x) => {self.recipients[i].0 = x}
+            TransactionMessage::SetValue(i, x) => {self.recipients[i].1 = x}
+            TransactionMessage::ToggleMax(i) => {self.send_max = if self.send_max == Some(i) { None } else { Some(i) }}
+            TransactionMessage::ToggleFeeFrom(i) => {self.fee_from = if self.fee_from == Some(i) { None } else { Some(i) }}
+            TransactionMessage::SetData(x) => {self.data = x}
+            TransactionMessage::AddRecipient => {self.recipients.push((String::new(), 0))}
+            TransactionMessage::RemoveRecipient(i) => {
+                self.recipients.remove(i);
+                //the later recipients move up one
+                let shift = |chosen: Option<usize>| match chosen{
+                    Some(j) if j == i => None,
+                    Some(j) if j > i => Some(j - 1),
+                    chosen => chosen
+                };
+                self.send_max = shift(self.send_max);
+                self.fee_from = shift(self.fee_from);
+            }
             TransactionMessage::SetFee(x) => {self.fee = x}
             TransactionMessage::EnterInfo => {self.enter_password = true}
             TransactionMessage::EnterPassword(x) => {self.password = x}
@@ -292,13 +850,22 @@ impl SendTransaction{
             }
             TransactionMessage::Close => {self.on = false}
             TransactionMessage::Broadcast => {
-                let transaction_text = match self.signed_transaction_string.clone(){
-                    Some(n) => testnet_broadcast_transaction(&n),
-                    None => String::from("No Transaction Created")
-                };
-                self.update_debug(transaction_text)
+                match self.signed_transaction_string.clone(){
+                    Some(n) => {
+                        self.update_debug(String::from("Broadcasting..."));
+                        return Command::perform(async move { testnet_send(&n).await }, TransactionMessage::Broadcasted)
+                    }
+                    None => self.update_debug(String::from("No Transaction Created"))
+                }
+            }
+            TransactionMessage::Broadcasted(result) => {
+                self.update_debug(match result{
+                    Ok(txid) => format!("Transaction broadcast: {}", txid),
+                    Err(e) => e.to_string()
+                })
             }
         }
+        Command::none()
     }
 
     fn on(&mut self){
@@ -307,21 +874,47 @@ impl SendTransaction{
     fn is_on(&self) -> bool{
         self.on
     }
-    fn create_transaction(&mut self, will_info: &mut WalletInfo){
-        let shrink_factor: f64 = 1.0 - ((self.value + self.fee) as f64 / will_info.value as f64);
-        let secretkey = SecretKey::from_str(&read_keys(&self.password)).unwrap();
-        self.signed_transaction = create_transaction(&self.address, self.value, self.fee, &will_info.address, &will_info.pubkey, secretkey).ok();
-        self.signed_transaction_string = match self.signed_transaction.clone(){
-            Some(n) => Some(n.concat().to_string()),
-            None => None
-        };
-        for i in 0..will_info.inheritors.len(){
-            will_info.inheritors[i].value = (will_info.inheritors[i].value as f64 * shrink_factor) as u64;
+    //signs on another thread; the answer comes back as Message::TransactionSigned
+    fn create_transaction(&self, will_info: WalletInfo) -> Command<Message>{
+        let (payment, password) = (self.payment(), self.password.clone());
+        let coins = CoinControl::new(will_info.labels.frozen(), self.selected.clone());
+        Command::perform(unblock(move || sign_send(payment?, &password, will_info, coins)), Message::TransactionSigned)
+    }
+    //builds the unsigned PSBT on another thread; it comes back as Message::PsbtCreated
+    fn create_psbt(&self, will_info: WalletInfo) -> Command<Message>{
+        let payment = self.payment();
+        let coins = CoinControl::new(will_info.labels.frozen(), self.selected.clone());
+        Command::perform(unblock(move || Ok(create_payment_psbt(&payment?, &will_info.addresses(), &coins)?.to_base64())), Message::PsbtCreated)
+    }
+    fn payment(&self) -> Result<Payment, WalletError>{
+        let recipients = self.recipients.iter().enumerate().map(|(i, (address, value))| Recipient::new(address, match self.send_max == Some(i){
+            true => SendAmount::Max,
+            false => SendAmount::Sats(*value)
+        })).collect();
+        let mut payment = Payment::new(recipients, self.fee);
+        if let Some(i) = self.fee_from{
+            payment = payment.with_fee_from(i);
         }
-        let will_parts = predict_will_parts(will_info.inheritors.addresses(), will_info.inheritors.amounts(), will_info.locktime as u16, self.signed_transaction.clone().unwrap(), &(*will_info.address), &(*will_info.pubkey), secretkey);
-        write_will_parts(will_parts);
-        
-        write_wallet(will_info.clone(), &self.password);
+        if !self.data.is_empty(){
+            payment = payment.with_data(self.data.to_bytes().map_err(|_| WalletError::Parse(format!("OP_RETURN data is not hex: {}", self.data)))?);
+        }
+        Ok(payment)
+    }
+    //satoshis in the coins the transaction may spend: the checked ones, or all that are not frozen
+    fn spendable_total(&self) -> u64{
+        self.coins.iter()
+            .filter(|coin| if self.selected.is_empty() { !coin.frozen } else { self.selected.contains(&coin.outpoint) })
+            .map(|coin| coin.value)
+            .sum()
+    }
+    //keeps the picked coins that are still there and not frozen
+    fn set_coins(&mut self, coins: Vec<Coin>){
+        self.selected.retain(|outpoint| coins.iter().any(|coin| coin.outpoint == *outpoint && !coin.frozen));
+        self.coins = coins;
+    }
+    fn set_transaction(&mut self, transaction: SignedTransaction){
+        self.signed_transaction_string = Some(transaction.concat().to_string());
+        self.signed_transaction = Some(transaction);
     }
     fn update_debug(&mut self, text: String){
         self.debug = text + "\n\n" + &self.debug
@@ -331,20 +924,29 @@ impl SendTransaction{
 #[derive(Debug, Clone)]
 enum TransactionMessage{
     Save,
-    SetAddress(String),
-    SetValue(u64),
+    SetAddress(usize, String),
+    SetValue(usize, u64),
+    ToggleMax(usize),
+    ToggleFeeFrom(usize),
+    AddRecipient,
+    RemoveRecipient(usize),
+    SetData(String),
     SetFee(u64),
     EnterInfo,
     Create,
     Close,
     EnterPassword(String),
     Broadcast,
+    Broadcasted(Result<String, WalletError>),
+    ToggleCoin(Outpoint),
+    ToggleFreeze(Outpoint),
+    CreatePsbt,
 }
 
 struct Launch{
     password: String,
     unlocked: bool,
-    wrong_password: bool,
+    error: Option<WalletError>,
 }
 
 impl Launch{
@@ -352,7 +954,7 @@ impl Launch{
         Launch {
             password: String::new(),
             unlocked: false,
-            wrong_password: false,
+            error: None,
         }
     }
 
@@ -362,9 +964,15 @@ impl Launch{
             text_input("-Enter Password-", &self.password, Message::TypePassword).on_submit(Message::EnterPassword)
         ].width(Length::Units(450)).align_items(Alignment::Center);
 
-        if self.wrong_password{
-            contents = contents.push(text("Wrong Password"))
+        match &self.error{
+            Some(WalletError::Crypto(_)) => contents = contents.push(text("Wrong Password")),
+            Some(e) => contents = contents.push(text(e.to_string())),
+            None => ()
         }
+        contents = contents.push(vertical_space(Length::Units(40)));
+        contents = contents.push(button("Claim an Inheritance").on_press(Message::OpenClaim));
+        contents = contents.push(vertical_space(Length::Units(20)));
+        contents = contents.push(button("Guardian Mode").on_press(Message::OpenGuardianMode));
 
         column![container(contents).height(Length::Fill).center_x().center_y()].into()
     }
@@ -373,12 +981,12 @@ impl Launch{
         self.password = password;
     }
 
-    fn enter_password(&mut self) -> Result<WalletInfo, String>{
-        self.wrong_password = false;
+    fn enter_password(&mut self) -> Result<WalletInfo, WalletError>{
+        self.error = None;
         let wallet = match read_wallet(&self.password){
             Ok(x) => x,
             Err(e) => {
-                self.wrong_password = true;
+                self.error = Some(e.clone());
                 return Err(e);
             }
         };
@@ -392,45 +1000,348 @@ impl Launch{
 }
 
 
+//inheritor mode: imports a will package and claims it without a wallet
+struct ClaimInheritance{
+    on: bool,
+    path: String,
+    statuses: Option<Receiver<Result<ClaimStatus, WalletError>>>,
+    status: String,
+}
+
+impl ClaimInheritance{
+    fn new() -> Self{
+        ClaimInheritance {
+            on: false,
+            path: String::from("will_parts(Child).json"),
+            statuses: None,
+            status: String::new(),
+        }
+    }
+
+    fn view(&self) -> Element<Message>{
+        let mut contents = column![
+            row![horizontal_space(Length::Fill), button("x").on_press(Message::CloseClaim)],
+            text("Claim an Inheritance").size(40),
+            text("Will parts file:"),
+            text_input("-File Path-", &self.path, Message::TypeClaimPath).on_submit(Message::ImportClaim),
+        ].width(Length::Units(640)).align_items(Alignment::Center).spacing(20);
+
+        if self.statuses.is_none(){
+            contents = contents.push(button("Import and Claim").on_press(Message::ImportClaim));
+        }else{
+            contents = contents.push(text("The will is checked every minute and each transaction is broadcast as soon as it is valid. Keep this window open."));
+            contents = contents.push(button("Refresh").on_press(Message::RefreshClaim));
+        }
+        contents = contents.push(text(&self.status));
+
+        column![container(contents).height(Length::Fill).center_x().center_y()].into()
+    }
+
+    fn import(&mut self){
+        let claim = read_will_package(&self.path)
+            .and_then(|contents| WillPackage::parse(&contents))
+            .and_then(Claim::new);
+        match claim{
+            Ok(claim) => {
+                self.status = format!("Imported will generation {} with {} redemptions", claim.package.generation, claim.redemptions.len());
+                self.statuses = Some(spawn_claim(claim, Duration::from_secs(60)));
+            }
+            Err(e) => self.status = e.to_string()
+        }
+    }
+
+    //shows the latest status from the claim thread
+    fn receive(&mut self){
+        if let Some(statuses) = &self.statuses{
+            for status in statuses.try_iter(){
+                self.status = match status{
+                    Ok(status) => status.description(),
+                    Err(e) => format!("Error: {}", e)
+                };
+            }
+        }
+    }
+
+    fn on(&mut self){
+        self.on = true;
+    }
+
+    fn off(&mut self){
+        self.on = false;
+        self.statuses = None;
+    }
+
+    fn is_on(&self) -> bool{
+        self.on
+    }
+}
+
+
+//guardian mode: imports a revocation package, watches the will vault and broadcasts the revocation
+struct GuardianMode{
+    on: bool,
+    path: String,
+    package: Option<GuardianPackage>,
+    watch: Option<(Sender<GuardianPackage>, Receiver<Result<GuardianStatus, WalletError>>)>,
+    status: Option<GuardianStatus>,
+    message: String,
+    secret_key: String,
+    cosigned_psbt: String,
+    fee: u32,
+}
+
+impl GuardianMode{
+    fn new() -> Self{
+        GuardianMode {
+            on: false,
+            path: String::from("will_parts(Guardian).json"),
+            package: None,
+            watch: None,
+            status: None,
+            message: String::new(),
+            secret_key: String::new(),
+            cosigned_psbt: String::new(),
+            fee: 1000,
+        }
+    }
+
+    fn view(&self) -> Element<GuardianMessage>{
+        let mut contents = column![
+            row![horizontal_space(Length::Fill), button("x").on_press(GuardianMessage::Close)],
+            text("Guardian Mode").size(40),
+            text("Will parts file:"),
+            text_input("-File Path-", &self.path, GuardianMessage::TypePath).on_submit(GuardianMessage::Import),
+            button("Import").on_press(GuardianMessage::Import),
+        ].width(Length::Units(640)).align_items(Alignment::Center).spacing(20);
+
+        if let Some(package) = &self.package{
+            contents = contents.push(text(format!("Will generation {}, watching {} vaults", package.generation, package.vaults.len())));
+            if let Some(status) = &self.status{
+                let mut status_text = text(status.description()).size(25);
+                if status.needs_action(){
+                    status_text = status_text.style(Color::from([1.0, 0.0, 0.0]));
+                }
+                contents = contents.push(status_text);
+            }
+            contents = contents.push(button("Refresh").on_press(GuardianMessage::Refresh));
+
+            if let Some(psbt) = package.psbt_base64(){
+                contents = contents.push(text_input("-Your Secret Key-", &self.secret_key, GuardianMessage::TypeSecretKey).password());
+                contents = contents.push(button("Sign Revocation").on_press(GuardianMessage::Sign));
+                contents = contents.push(text_input("-Another Guardian's Signed PSBT-", &self.cosigned_psbt, GuardianMessage::TypeCosignedPsbt));
+                contents = contents.push(button("Add Signatures").on_press(GuardianMessage::Combine));
+                contents = contents.push(row![
+                    text("Fee:"),
+                    number_input_2(self.fee, GuardianMessage::SetFee),
+                    text("sats"),
+                    button("Bump Fee").on_press(GuardianMessage::BumpFee),
+                ].spacing(10).align_items(Alignment::Center));
+                contents = contents.push(text("Share this PSBT with the other guardians:"));
+                contents = contents.push(text(psbt).size(12));
+            }
+            contents = contents.push(button("Broadcast Revocation").on_press(GuardianMessage::Broadcast));
+        }
+        contents = contents.push(text(&self.message));
+
+        column![container(scrollable(contents)).height(Length::Fill).center_x().center_y()].into()
+    }
+
+    fn update(&mut self, message: GuardianMessage) -> Command<GuardianMessage>{
+        self.message.clear();
+        let mut command = Command::none();
+        match message{
+            GuardianMessage::Close => {
+                *self = GuardianMode::new();
+            }
+            GuardianMessage::TypePath(x) => self.path = x,
+            GuardianMessage::Import => {
+                match read_will_package(&self.path).and_then(|contents| GuardianPackage::parse(&contents)){
+                    Ok(package) => {
+                        self.watch = Some(spawn_guardian_watch(package.clone(), Duration::from_secs(60)));
+                        self.package = Some(package);
+                    }
+                    Err(e) => self.message = e.to_string()
+                }
+            }
+            GuardianMessage::Refresh => (),
+            GuardianMessage::TypeSecretKey(x) => self.secret_key = x,
+            GuardianMessage::Sign => {
+                let secret_key = SecretKey::from_str(self.secret_key.trim()).map_err(|_| WalletError::Crypto(String::from("Invalid secret key")));
+                self.message = match (&mut self.package, secret_key){
+                    (Some(package), Ok(secret_key)) => match package.sign(secret_key){
+                        Ok(0) => String::from("That key is not one of the guardian keys of this will"),
+                        Ok(n) => format!("Signed {} vaults", n),
+                        Err(e) => e.to_string()
+                    },
+                    (_, Err(e)) => e.to_string(),
+                    _ => String::new()
+                };
+                self.secret_key.clear();
+            }
+            GuardianMessage::TypeCosignedPsbt(x) => self.cosigned_psbt = x,
+            GuardianMessage::Combine => {
+                if let Some(package) = &mut self.package{
+                    self.message = match package.combine(&self.cosigned_psbt){
+                        Ok(()) => String::from("Signatures added"),
+                        Err(e) => e.to_string()
+                    };
+                }
+                self.cosigned_psbt.clear();
+            }
+            GuardianMessage::SetFee(x) => self.fee = x,
+            GuardianMessage::BumpFee => {
+                if let Some(package) = &mut self.package{
+                    self.message = match package.bump_fee(self.fee as u64){
+                        Ok(()) => String::from("Fee changed. Every guardian has to sign the new PSBT."),
+                        Err(e) => e.to_string()
+                    };
+                }
+            }
+            GuardianMessage::Broadcast => {
+                if let Some(package) = self.package.clone(){
+                    self.message = String::from("Broadcasting...");
+                    command = Command::perform(unblock(move || broadcast_revocation(&package, &TestnetBackend)), GuardianMessage::Broadcasted);
+                }
+            }
+            GuardianMessage::Broadcasted(result) => {
+                self.message = match result{
+                    Ok(txid) => format!("Will revocation broadcast: {}", txid),
+                    Err(e) => e.to_string()
+                };
+            }
+        }
+        //keep the watcher on the latest revocation
+        if let (Some(package), Some((packages, _))) = (&self.package, &self.watch){
+            let _ = packages.send(package.clone());
+        }
+        command
+    }
+
+    //shows the latest vault status from the watch thread
+    fn receive(&mut self){
+        if let Some((_, statuses)) = &self.watch{
+            for status in statuses.try_iter(){
+                match status{
+                    Ok(status) => self.status = Some(status),
+                    Err(e) => self.message = format!("Error: {}", e)
+                }
+            }
+        }
+    }
+
+    fn on(&mut self){
+        self.on = true;
+    }
+
+    fn is_on(&self) -> bool{
+        self.on
+    }
+}
+
+#[derive(Debug, Clone)]
+enum GuardianMessage{
+    Close,
+    TypePath(String),
+    Import,
+    Refresh,
+    TypeSecretKey(String),
+    Sign,
+    TypeCosignedPsbt(String),
+    Combine,
+    SetFee(u32),
+    BumpFee,
+    Broadcast,
+    Broadcasted(Result<String, WalletError>),
+}
+
+
 struct ViewTransactionHistory{
-    history: Vec<TransactionHistory>,
+    history: HistoryPage,
     on: bool,
+    labels_path: String,
+    labels_status: String,
+    export_path: String,
+    prices_path: String,
 }
 
 impl ViewTransactionHistory{
     fn new() -> Self{
         ViewTransactionHistory {
-            history: vec![],
+            history: HistoryPage::new(vec![], 1, 1),
             on: false,
+            labels_path: String::from("labels.jsonl"),
+            labels_status: String::new(),
+            export_path: String::from("history.csv"),
+            prices_path: String::new(),
         }
     }
 
-    fn view(&self) -> Element<Message>{
+    fn view(&self, labels: &Labels) -> Element<Message>{
         let mut contents = column![
             row![horizontal_space(Length::Fill), button("x").on_press(Message::CloseHistory)],
         ].width(Length::Units(640)).align_items(Alignment::Start).height(Length::Units(750)).spacing(25);
 
-        for transaction in self.history.clone(){
+        let mut transactions = column![].spacing(25);
+        for transaction in self.history.transactions.iter(){
             let mut to_address = row![].align_items(Alignment::End);
-            if transaction.to{
+            if transaction.is_sent(){
                 to_address = to_address.push(text("Sent To -> "));
-                to_address = to_address.push(text(transaction.address).size(12));
             }else{
                 to_address = to_address.push(text("Received From: "));
-                to_address = to_address.push(text(transaction.address).size(12));
             }
-            contents = contents.push(column![
+            let counterparties: Vec<String> = transaction.counterparties.iter().map(|address| match labels.address(address){
+                Some(label) => format!("{} ({})", label, address),
+                None => address.clone()
+            }).collect();
+            to_address = to_address.push(text(counterparties.join(", ")).size(12));
+            let status = match transaction.block_height{
+                Some(height) => format!("{}     Block {}     {} CONFIRMATIONS", transaction.date(), height, transaction.confirmations),
+                None => format!("{}     PENDING", transaction.date()),
+            };
+            let mut entry = column![
                 to_address,
-                text(&format!("Value: {} sats     {} CONFIRMATIONS", transaction.value, transaction.confirmations))
-            ]);
+                text(&format!("Value: {:+} sats     Fee: {} sats", transaction.net, transaction.fee)),
+                text(status).size(14),
+            ];
+            if let Some(will) = transaction.will{
+                entry = entry.push(text(will.description()).style(Color::from([0.0, 0.4, 0.8])));
+            }
+            let txid = transaction.txid.clone();
+            entry = entry.push(
+                text_input("-Label-", labels.transaction(&transaction.txid).unwrap_or(""), move |label| Message::SetTransactionLabel(txid.clone(), label))
+                .on_submit(Message::SaveLabels)
+            );
+            transactions = transactions.push(entry);
         }
+        contents = contents.push(scrollable(transactions).height(Length::Fill));
 
-        contents = contents.push(vertical_space(Length::Fill));
+        let mut pages = row![].spacing(20).align_items(Alignment::Center);
+        if self.history.has_newer(){
+            pages = pages.push(button("Newer").on_press(Message::ShowHistoryPage(self.history.page - 1)));
+        }
+        pages = pages.push(text(format!("Page {} of {}", self.history.page, self.history.total_pages)));
+        if self.history.has_older(){
+            pages = pages.push(button("Older").on_press(Message::ShowHistoryPage(self.history.page + 1)));
+        }
+        contents = contents.push(pages);
+        contents = contents.push(row![
+            text_input("-BIP329 File-", &self.labels_path, Message::TypeLabelsPath),
+            button("Import Labels").on_press(Message::ImportLabels),
+            button("Export Labels").on_press(Message::ExportLabels),
+        ].spacing(10).align_items(Alignment::Center));
+        contents = contents.push(row![
+            text_input("-Export File (.csv or .json)-", &self.export_path, Message::TypeExportPath),
+            text_input("-Price File (optional)-", &self.prices_path, Message::TypePricesPath),
+            button("Export History").on_press(Message::ExportHistory),
+        ].spacing(10).align_items(Alignment::Center));
+        if !self.labels_status.is_empty(){
+            contents = contents.push(text(&self.labels_status));
+        }
 
         column![container(contents).height(Length::Fill).center_x().center_y()].into()
     }
 
-    fn set(&mut self, history: Result<Vec<TransactionHistory>, String>){
+    fn set(&mut self, history: Result<HistoryPage, WalletError>){
         match history{
             Ok(x) => self.history = x,
             _ => ()
@@ -438,7 +1349,7 @@ impl ViewTransactionHistory{
     }
 
     fn is_empty(&self) -> bool{
-        self.history.len() == 0
+        self.history.transactions.is_empty()
     }
     
     fn is_on(&self) -> bool {
@@ -460,6 +1371,11 @@ struct SetWill{
     locktime: u64,
     on: bool,
     password: String,
+    error: String,
+    //hex data for an OP_RETURN output of the will initiation
+    initiation_data: String,
+    //a document whose hash becomes the initiation data
+    commit_path: String,
 }
 impl Sandbox for SetWill{
     type Message = SetWillMessage;
@@ -472,6 +1388,9 @@ impl Sandbox for SetWill{
             locktime: 0,
             on: false,
             password: String::new(),
+            error: String::new(),
+            initiation_data: String::new(),
+            commit_path: String::new(),
         }
     }
 
@@ -518,6 +1437,11 @@ impl Sandbox for SetWill{
                     button("Next")
                     .on_press(SetWillMessage::NextGuardian)
                 )
+            }else if pages.next_is_review(){
+                controls_bottom = controls_bottom.push(
+                    button("Review Vault")
+                    .on_press(SetWillMessage::Review)
+                )
             }else{
                 controls_bottom = controls_bottom.push(
                     button("Next")
@@ -526,8 +1450,13 @@ impl Sandbox for SetWill{
             }
         }else{
             controls_bottom = controls_bottom.push(column![
+                text_input("Will initiation OP_RETURN data (hex, optional)", &self.initiation_data, SetWillMessage::SetInitiationData).width(Length::Units(300)),
+                row![
+                    text_input("or a document to commit to", &self.commit_path, SetWillMessage::SetCommitPath).width(Length::Units(200)),
+                    button("Hash").on_press(SetWillMessage::CommitFile),
+                ].spacing(10),
                 text_input("-Enter Password-", &self.password, SetWillMessage::EnterPassword).width(Length::Units(200))
-            ].width(Length::Fill).align_items(Alignment::Center));
+            ].spacing(10).width(Length::Fill).align_items(Alignment::Center));
             controls_bottom = controls_bottom.push(
                 button("Finish")
                 .on_press(SetWillMessage::Finish)
@@ -542,7 +1471,7 @@ impl Sandbox for SetWill{
                 inheritors_list.push_str(&format!("Name: {}\n", inheritor.name));
                 inheritors_list.push_str(&format!("Address: {}\n", inheritor.address));
                 inheritors_list.push_str(&format!("User ID: {}\n", inheritor.id));
-                inheritors_list.push_str(&format!("Amount (Satoshis): {}\n", inheritor.value));
+                inheritors_list.push_str(&format!("Share: {}\n", inheritor.share_percent()));
                 inheritors_list.push_str(&format!("\n\n"));
             }
         }else if pages.exit_guardians(){
@@ -550,6 +1479,7 @@ impl Sandbox for SetWill{
                 guardians_list.push_str(&format!("Guardian {}\n", i+1));
                 guardians_list.push_str(&format!("Name: {}\n", guardian.name));
                 guardians_list.push_str(&format!("User ID: {}\n", guardian.id));
+                guardians_list.push_str(&format!("Public Key: {}\n", guardian.pubkey));
                 guardians_list.push_str(&format!("\n\n"));
             }
         }
@@ -558,6 +1488,7 @@ impl Sandbox for SetWill{
             controls_top,
             self.pages.view(inheritors_list, guardians_list).map(SetWillMessage::PagesMessages),
             vertical_space(Length::Fill),
+            text(&self.error).style(theme::Text::Color(iced::Color::from_rgb8(255, 0, 0))),
             controls_bottom,
         ]
         .height(Length::Units(650))
@@ -578,22 +1509,36 @@ impl Sandbox for SetWill{
 
     fn update(&mut self, message: SetWillMessage){
         match message{
-            SetWillMessage::Next => self.pages.next(),
+            SetWillMessage::Next => {
+                if self.pages.next_is_inheritor_locktimes(){
+                    self.pages.set_inheritor_locktimes(&self.inheritors);
+                }
+                self.pages.next()
+            }
             SetWillMessage::Back => self.pages.back(),
             SetWillMessage::PagesMessages(val) => self.pages.update(val),
             SetWillMessage::AddInheritors(n) => {self.pages.add_inheritors(); self.pages.next()}
             SetWillMessage::NextInheritor => {
-                let inheritor = self.pages.current_inheritor_info();
                 if self.pages.inheritor_is_guardian(){
-                    self.guardians.push(Guardian { name: (inheritor.name.clone()), id: (inheritor.id.clone()) });
+                    self.guardians.push(self.pages.current_inheritor_guardian_info());
                 }
+                let inheritor = self.pages.current_inheritor_info();
                 self.inheritors.push(inheritor);
                 self.pages.next()}
             SetWillMessage::AddGuardians(n) => {self.pages.add_guardians(); self.pages.next()}
             SetWillMessage::NextGuardian => {self.guardians.push(self.pages.current_guardian_info()); self.pages.next()}
             SetWillMessage::EnterPassword(x) => {self.password = x}
-            SetWillMessage::Finish => {self.password = String::new(); self.on = false}
-            SetWillMessage::Close => {self.password = String::new(); self.on = false}
+            SetWillMessage::SetInitiationData(x) => {self.initiation_data = x}
+            SetWillMessage::SetCommitPath(x) => {self.commit_path = x}
+            SetWillMessage::CommitFile => {
+                match file_commitment(&self.commit_path){
+                    Ok(hash) => self.initiation_data = hash,
+                    Err(e) => self.error = e.to_string()
+                }
+            }
+            SetWillMessage::Review => {}
+            SetWillMessage::Finish => {self.password = String::new(); self.error = String::new(); self.on = false}
+            SetWillMessage::Close => {self.password = String::new(); self.error = String::new(); self.on = false}
         }
     }
 }
@@ -604,10 +1549,20 @@ impl SetWill{
     fn on(&mut self){
         self.on = true;
     }
-    fn create_will(&mut self, will_info: &mut WalletInfo){
-        let secretkey = SecretKey::from_str(&read_keys(&self.password)).unwrap();
-        let will_parts = create_will_parts(will_info.inheritors.addresses(), will_info.inheritors.amounts(), will_info.locktime as u16, &(*will_info.address), &(*will_info.pubkey), secretkey);
-        write_will_parts(will_parts);
+    fn set_error(&mut self, error: String){
+        self.error = error;
+    }
+    fn review(&mut self, parent_pubkey: &str, vaults: Vec<TimelockComponents>){
+        self.error = String::new();
+        self.pages.set_vaults(parent_pubkey, vaults);
+        self.pages.next()
+    }
+    fn inheritors_with_locktimes(&self) -> Vec<Inheritor>{
+        let mut inheritors = self.inheritors.clone();
+        for (inheritor, locktime) in inheritors.iter_mut().zip(self.pages.inheritor_locktimes()){
+            inheritor.locktime = locktime;
+        }
+        inheritors
     }
 }
 
@@ -623,6 +1578,10 @@ enum SetWillMessage{
     AddGuardians(u8),
     NextGuardian,
     EnterPassword(String),
+    SetInitiationData(String),
+    SetCommitPath(String),
+    CommitFile,
+    Review,
     Finish,
     Close,
 }
@@ -632,21 +1591,26 @@ enum PageMessage{
     InheritorMessages(InheritorMessage),
     ChangeInputGuardians(Option<u8>),
     GuardianMessages(GuardianMessage),
+    ChangeGuardianThreshold(Option<u8>),
     SetLocktime(u32),
     SetTimeUnit(TimeUnit),
+    SetInheritorLocktime(usize, u32),
+    SetInheritorTimeUnit(usize, TimeUnit),
 }
 #[derive(Debug, Clone)]
 enum InheritorMessage{
     SetName(String),
     SetAddress(String),
     SetID(String),
-    SetValue(u64),
+    SetShare(u64),
     ToggleGuardian(bool),
+    SetPubkey(String),
 }
 #[derive(Debug, Clone)]
 enum GuardianMessage{
     SetName(String),
     SetID(String),
+    SetPubkey(String),
 }
 
 
@@ -655,6 +1619,7 @@ struct Pages{
     current: usize,
     n_inheritors: Option<u8>,
     n_guardians: Option<u8>,
+    guardian_threshold: Option<u8>,
 }
 impl Pages{
     fn new() -> Self{
@@ -665,19 +1630,22 @@ impl Pages{
                 Page::SetNumberOfGuardians,
                 Page::ShowGuardians,
                 Page::SetLocktime(SetLocktime::new()),
+                Page::SetInheritorLocktimes(InheritorLocktimes::new()),
+                Page::ReviewVault(VaultReview::new()),
             ],
         current: 0,
         n_inheritors: Some(1),
         n_guardians: None,
+        guardian_threshold: Some(1),
         }
     }
 
     fn update(&mut self, message: PageMessage){
-        self.pages[self.current].update(message, &mut self.n_inheritors, &mut self.n_guardians)
+        self.pages[self.current].update(message, &mut self.n_inheritors, &mut self.n_guardians, &mut self.guardian_threshold)
     }
 
     fn view(&self, inheritors: String, guardians: String) -> Element<PageMessage>{
-        self.pages[self.current].view(self.n_inheritors, self.n_guardians, self.current, inheritors, guardians)
+        self.pages[self.current].view(self.n_inheritors, self.n_guardians, self.guardian_threshold, self.current, inheritors, guardians)
     }
 
     fn back(&mut self){
@@ -722,6 +1690,10 @@ impl Pages{
         self.pages[self.current].inheritor_is_guardian()
     }
 
+    fn current_inheritor_guardian_info(&self) -> Guardian{
+        self.pages[self.current].inheritor_guardian_info()
+    }
+
     fn exit_inheritors(&self) -> bool{
         self.current == 1 + self.n_inheritors()
     }
@@ -760,8 +1732,62 @@ impl Pages{
         self.n_guardians.unwrap_or(0).into()
     }
 
+    fn guardian_threshold(&self) -> u8{
+        self.guardian_threshold.unwrap_or(1)
+    }
+
     fn get_locktime_blocks(&self) -> u32{
-        self.pages[self.current].get_locktime_blocks()
+        for page in self.pages.iter(){
+            if let Page::SetLocktime(_) = page{
+                return page.get_locktime_blocks()
+            }
+        }
+        4294967295
+    }
+
+    fn next_is_inheritor_locktimes(&self) -> bool{
+        self.current == self.pages.len()-3
+    }
+
+    fn next_is_review(&self) -> bool{
+        self.current == self.pages.len()-2
+    }
+
+    //fills the inheritor locktimes page, starting every inheritor at the will's redemption period
+    fn set_inheritor_locktimes(&mut self, inheritors: &Vec<Inheritor>){
+        let mut default = SetLocktime::new();
+        for page in self.pages.iter(){
+            if let Page::SetLocktime(x) = page{
+                default = x.clone();
+            }
+        }
+        for page in self.pages.iter_mut(){
+            if let Page::SetInheritorLocktimes(x) = page{
+                x.set_inheritors(inheritors, default.clone());
+            }
+        }
+    }
+
+    fn inheritor_locktimes(&self) -> Vec<u32>{
+        for page in self.pages.iter(){
+            if let Page::SetInheritorLocktimes(x) = page{
+                return x.get_locktimes_blocks()
+            }
+        }
+        vec![]
+    }
+
+    fn set_vaults(&mut self, parent_pubkey: &str, vaults: Vec<TimelockComponents>){
+        if let Some(Page::ReviewVault(x)) = self.pages.last_mut(){
+            x.set_vaults(parent_pubkey, vaults);
+        }
+    }
+
+    fn vaults(&self) -> Vec<TimelockComponents>{
+        match self.pages.last(){
+            Some(Page::ReviewVault(x)) => x.vaults.clone(),
+            _ => vec![]
+        }
     }
 
 }
@@ -776,10 +1802,12 @@ enum Page{
     NewGuardian(NewGuardian),
     ShowGuardians,
     SetLocktime(SetLocktime),
+    SetInheritorLocktimes(InheritorLocktimes),
+    ReviewVault(VaultReview),
 }
 impl Page{
 
-    fn view(&self, n_inheritors: Option<u8>, n_guardians: Option<u8>, current: usize, inheritors: String,
+    fn view(&self, n_inheritors: Option<u8>, n_guardians: Option<u8>, guardian_threshold: Option<u8>, current: usize, inheritors: String,
         guardians: String) -> Element<PageMessage>{
         match self{
             Page::SetNumberOfInheritors => {
@@ -872,7 +1900,10 @@ impl Page{
 
                 container(column![
                     text("Please verify your Guardians:").size(20),
-                    scrollable
+                    scrollable,
+                    text("Guardian Signatures Required To Revoke:").size(20),
+                    text("(Only guardians with a public key can sign)").size(15),
+                    numeric_input(guardian_threshold, PageMessage::ChangeGuardianThreshold),
                 ]
                 .spacing(20))
                 .height(Length::Fill)
@@ -891,10 +1922,32 @@ impl Page{
                 .into();
                 container(content).center_x().into()
             }
+            Page::SetInheritorLocktimes(x) => {
+                let scrollable = scrollable(
+                    container(x.view())
+                    .width(Length::Fill)
+                    .center_x()
+                );
+                container(scrollable)
+                .height(Length::Units(500))
+                .center_x()
+                .into()
+            }
+            Page::ReviewVault(x) => {
+                let scrollable = scrollable(
+                    container(x.view())
+                    .width(Length::Fill)
+                    .center_x()
+                );
+                container(scrollable)
+                .height(Length::Units(500))
+                .center_x()
+                .into()
+            }
         }
     }
 
-    fn update(&mut self, message: PageMessage, n_inheritors: &mut Option<u8>, n_guardians: &mut Option<u8>){
+    fn update(&mut self, message: PageMessage, n_inheritors: &mut Option<u8>, n_guardians: &mut Option<u8>, guardian_threshold: &mut Option<u8>){
         match message{
             PageMessage::ChangeInputInheritors(val) => {
                 *n_inheritors = val
@@ -912,6 +1965,9 @@ impl Page{
                     x.update(val)
                 }
             }
+            PageMessage::ChangeGuardianThreshold(val) => {
+                *guardian_threshold = val
+            }
             PageMessage::SetLocktime(val) => {
                 if let Page::SetLocktime(x) = self {
                     x.set_value(val)
@@ -922,6 +1978,16 @@ impl Page{
                     x.set_unit(val)
                 }
             }
+            PageMessage::SetInheritorLocktime(i, val) => {
+                if let Page::SetInheritorLocktimes(x) = self {
+                    x.locktimes[i].set_value(val)
+                }
+            }
+            PageMessage::SetInheritorTimeUnit(i, val) => {
+                if let Page::SetInheritorLocktimes(x) = self {
+                    x.locktimes[i].set_unit(val)
+                }
+            }
         }
     }
 
@@ -946,6 +2012,14 @@ impl Page{
             x.is_guardian
         }else{false}
     }
+    fn inheritor_guardian_info(&self) -> Guardian{
+        match self{
+            Page::NewInheritor(x) => {
+                x.guardian_info()
+            }
+            _ => panic!["Cannot Get Guardian Info"]
+        }
+    }
     fn get_locktime_blocks(&self) -> u32{
         if let Page::SetLocktime(x) = self{
             x.get_locktime_blocks()
@@ -960,8 +2034,9 @@ struct NewInheritor{
     name: String,
     address: String,
     id: String,
-    value: u64,
+    share: u64,
     is_guardian: bool,
+    pubkey: String,
 }
 impl NewInheritor{
     fn new(nth: u8) -> Self{
@@ -970,25 +2045,31 @@ impl NewInheritor{
             name: String::from(""),
             address: String::from(""),
             id: String::from(""),
-            value: 0,
+            share: 0,
             is_guardian: false,
+            pubkey: String::from(""),
         }
     }
 
     fn view(&self, i: usize) -> Element<InheritorMessage>{
-        return column![
+        let mut contents = column![
             text(format!("Inheritor: {}", i)).size(50),
             text_input("Name:", &self.name, InheritorMessage::SetName),
             text_input("Address:", &self.address, InheritorMessage::SetAddress),
             text_input("UserID:", &self.id, InheritorMessage::SetID),
-            number_input(self.value, InheritorMessage::SetValue),
+            text(format!("Share: {}.{:02}% (Basis Points, 10000 = 100%)", self.share / 100, self.share % 100)),
+            number_input_1(self.share, InheritorMessage::SetShare),
             checkbox("Include as Guardian", self.is_guardian, InheritorMessage::ToggleGuardian),
         ]
         .max_width(800)
         .spacing(10)
         .padding(10)
-        .align_items(Alignment::Center)
-        .into();
+        .align_items(Alignment::Center);
+
+        if self.is_guardian{
+            contents = contents.push(text_input("Guardian Public Key (optional):", &self.pubkey, InheritorMessage::SetPubkey));
+        }
+        contents.into()
     }
 
     fn update(&mut self, message: InheritorMessage){
@@ -996,8 +2077,17 @@ impl NewInheritor{
             InheritorMessage::SetName(x) => self.name = x,
             InheritorMessage::SetAddress(x) => self.address = x,
             InheritorMessage::SetID(x) => self.id = x,
-            InheritorMessage::SetValue(x) => self.value = x,
+            InheritorMessage::SetShare(x) => self.share = x.min(TOTAL_SHARES),
             InheritorMessage::ToggleGuardian(x) => self.is_guardian = x,
+            InheritorMessage::SetPubkey(x) => self.pubkey = x,
+        }
+    }
+
+    fn guardian_info(&self) -> Guardian{
+        Guardian{
+            name: self.name.clone(),
+            id: self.id.clone(),
+            pubkey: self.pubkey.trim().to_string(),
         }
     }
 
@@ -1006,11 +2096,13 @@ impl NewInheritor{
             name: self.name.clone(),
             address: self.address.clone(),
             id: self.id.clone(),
-            value: self.value,
+            share: self.share as u16,
+            locktime: 0,
         };
         self.name.clear();
         self.address.clear();
         self.id.clear();
+        self.pubkey.clear();
         inheritor
     }
 }
@@ -1021,6 +2113,7 @@ struct NewGuardian{
     guardian_number: u8,
     name: String,
     id: String,
+    pubkey: String,
 }
 impl NewGuardian{
     fn new(guardian_number: u8) -> Self{
@@ -1028,6 +2121,7 @@ impl NewGuardian{
             guardian_number,
             name: String::from(""),
             id: String::from(""),
+            pubkey: String::from(""),
         }
     }
 
@@ -1036,6 +2130,7 @@ impl NewGuardian{
             text(format!("Guardian: {}", i)).size(50),
             text_input("Name:", &self.name, GuardianMessage::SetName),
             text_input("UserID:", &self.id, GuardianMessage::SetID),
+            text_input("Public Key (optional):", &self.pubkey, GuardianMessage::SetPubkey),
         ]
         .max_width(800)
         .spacing(10)
@@ -1048,6 +2143,7 @@ impl NewGuardian{
         match message{
             GuardianMessage::SetName(x) => self.name = x,
             GuardianMessage::SetID(x) => self.id = x,
+            GuardianMessage::SetPubkey(x) => self.pubkey = x,
         }
     }
 
@@ -1055,9 +2151,11 @@ impl NewGuardian{
         let guardian = Guardian{
             name: self.name.clone(),
             id: self.id.clone(),
+            pubkey: self.pubkey.trim().to_string(),
         };
         self.name.clear();
         self.id.clear();
+        self.pubkey.clear();
         guardian
     }
 }
@@ -1093,27 +2191,126 @@ impl SetLocktime{
         .into();
     }
 
+    //capped at the longest delay a vault can enforce in the unit
     fn set_value(&mut self, timelock_value: u32){
-        self.value = timelock_value
+        self.value = timelock_value.min(MAX_LOCKTIME_BLOCKS / self.unit.blocks_per_unit())
     }
 
     fn set_unit(&mut self, timelock_unit: TimeUnit){
-        self.unit = timelock_unit
+        self.unit = timelock_unit;
+        self.set_value(self.value)
     }
 
     fn get_locktime_blocks(&self) -> u32{
-        match self.unit{
-            TimeUnit::blocks => self.value,
-            TimeUnit::days => self.value * 144,
-            TimeUnit::weeks => self.value * 1008,
-            TimeUnit::months => self.value * 4320,
-            TimeUnit::years => self.value * 52560,
+        self.value * self.unit.blocks_per_unit()
+    }
+
+}
+
+
+#[derive(Debug, Clone)]
+struct InheritorLocktimes{
+    names: Vec<String>,
+    locktimes: Vec<SetLocktime>,
+}
+impl InheritorLocktimes{
+    fn new() -> Self{
+        InheritorLocktimes {
+            names: vec![],
+            locktimes: vec![],
         }
     }
 
+    fn view(&self) -> Element<PageMessage>{
+        let mut contents = column![
+            text(format!("Stagger Inheritance")).size(50),
+            text(format!("Each inheritor can wait a different amount of time after the will is initiated. Inheritors with the same redemption period share one vault.")).size(20),
+            vertical_space(Length::Units(30)),
+        ]
+        .max_width(800)
+        .spacing(10)
+        .padding(10)
+        .align_items(Alignment::Center);
+
+        for (i, (name, locktime)) in self.names.iter().zip(self.locktimes.iter()).enumerate(){
+            contents = contents.push(row![
+                text(name).size(25).width(Length::Units(200)),
+                number_input_2(locktime.value, move |x| PageMessage::SetInheritorLocktime(i, x)),
+                pick_list(&TimeUnit::ALL[..], Some(locktime.unit), move |x| PageMessage::SetInheritorTimeUnit(i, x)),
+            ].spacing(10).align_items(Alignment::Center));
+        }
+        contents.into()
+    }
+
+    fn set_inheritors(&mut self, inheritors: &Vec<Inheritor>, default: SetLocktime){
+        self.names = inheritors.iter().map(|inheritor| inheritor.name.clone()).collect();
+        self.locktimes = vec![default; inheritors.len()];
+    }
+
+    fn get_locktimes_blocks(&self) -> Vec<u32>{
+        self.locktimes.iter().map(|locktime| locktime.get_locktime_blocks()).collect()
+    }
 }
 
 
+//what each vault of the will enforces, shown before the will is built with them
+#[derive(Debug, Clone)]
+struct VaultReview{
+    parent_pubkey: String,
+    vaults: Vec<TimelockComponents>,
+}
+impl VaultReview{
+    fn new() -> Self{
+        VaultReview {
+            parent_pubkey: String::new(),
+            vaults: vec![],
+        }
+    }
+
+    fn view(&self) -> Element<PageMessage>{
+        let mut contents = column![
+            text(format!("Review Vault")).size(50),
+            text(format!("The will locks your coins in this script. You can take them back at any time; your inheritors can claim them with the single-use key once the delay has passed.")).size(20),
+            vertical_space(Length::Units(30)),
+        ]
+        .max_width(800)
+        .spacing(10)
+        .padding(10)
+        .align_items(Alignment::Center);
+
+        for (i, vault) in self.vaults.iter().enumerate(){
+            if self.vaults.len() > 1{
+                contents = contents.push(text(format!("Vault {} of {}", i+1, self.vaults.len())).size(30));
+            }
+            contents = contents.push(text(format!("P2WSH Address: {}", vault.address())).size(20));
+            contents = contents.push(text(format!("Parent Key: {}", self.parent_pubkey)).size(20));
+            contents = contents.push(text(format!("Single-Use Key Fingerprint: {}", vault.single_use_fingerprint())).size(20));
+            if vault.has_guardians(){
+                contents = contents.push(text(format!("Guardians: any {} of {} can revoke", vault.guardian_threshold, vault.guardian_pubkeys.len())).size(20));
+            }
+            contents = contents.push(text(format!("Delay: {} Blocks ({})", vault.locktime_blocks(), blocks_duration(vault.locktime_blocks() as u32))).size(20));
+            contents = contents.push(text(vault.asm()).size(15));
+            contents = contents.push(vertical_space(Length::Units(20)));
+        }
+        contents.into()
+    }
+
+    fn set_vaults(&mut self, parent_pubkey: &str, vaults: Vec<TimelockComponents>){
+        self.parent_pubkey = parent_pubkey.to_string();
+        self.vaults = vaults;
+    }
+}
+
+//roughly how long the blocks take to be mined, at 10 minutes a block
+fn blocks_duration(blocks: u32) -> String{
+    match blocks{
+        0..=5 => format!("≈{} Minutes", blocks * 10),
+        6..=143 => format!("≈{} Hours", blocks / 6),
+        144..=52559 => format!("≈{} Days", blocks / 144),
+        _ => format!("≈{:.1} Years", blocks as f64 / 52560.0),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TimeUnit{
     blocks,
@@ -1130,6 +2327,17 @@ impl TimeUnit{
         TimeUnit::months,
         TimeUnit::years,
     ];
+
+    //about 144 blocks are mined a day
+    fn blocks_per_unit(&self) -> u32{
+        match self{
+            TimeUnit::blocks => 1,
+            TimeUnit::days => 144,
+            TimeUnit::weeks => 1008,
+            TimeUnit::months => 4320,
+            TimeUnit::years => 52560,
+        }
+    }
 }
 impl Default for TimeUnit {
     fn default() -> TimeUnit {
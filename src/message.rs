@@ -0,0 +1,240 @@
+use secp256k1::{Secp256k1, SecretKey, PublicKey, Message};
+use secp256k1::ecdsa::{self, RecoverableSignature, RecoveryId};
+
+use crate::create_transaction::{decode_address, hash160};
+use crate::descriptor::tagged_hash;
+use crate::script::{Script, Opcode, p2pkh_script, p2sh_script, witness_program_script};
+use crate::transaction_parts::{Vin, Vout, RawTransaction, UnsignedSegwitTransaction};
+use crate::traits::*;
+use crate::error::WalletError;
+
+//prefixed to a message before it is hashed, so a signed message can never be a signed transaction
+const MESSAGE_MAGIC: &str = "Bitcoin Signed Message:\n";
+const BIP322_TAG: &str = "BIP0322-signed-message";
+//BIP137 headers are 27 plus the recovery id, plus 4 for a compressed P2PKH key, 8 for P2SH-P2WPKH and 12 for P2WPKH
+const BIP137_HEADER: u8 = 27;
+
+//the ways a single key can receive coins
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyScript{
+    P2pkh,
+    P2shP2wpkh,
+    P2wpkh,
+}
+
+impl KeyScript{
+    const ALL: [KeyScript;3] = [KeyScript::P2pkh, KeyScript::P2shP2wpkh, KeyScript::P2wpkh];
+
+    fn script_pubkey(&self, pubkey: &PublicKey) -> Vec<u8>{
+        let pubkey_hash = hash160(&pubkey.to_string());
+        match self{
+            KeyScript::P2pkh => p2pkh_script(&pubkey_hash).into_bytes(),
+            KeyScript::P2shP2wpkh => p2sh_script(&hash160(&witness_program_script(0, &pubkey_hash).into_bytes().to_string())).into_bytes(),
+            KeyScript::P2wpkh => witness_program_script(0, &pubkey_hash).into_bytes(),
+        }
+    }
+}
+
+//the hash a legacy signed message signs: the magic and the message, each with its length, hashed twice
+pub fn message_hash(message: &[u8]) -> Vec<u8>{
+    [var(MESSAGE_MAGIC.as_bytes().to_vec()), var(message.to_vec())].concat().sha256d()
+}
+
+//the hash BIP322 commits to in its virtual transaction
+pub fn bip322_message_hash(message: &[u8]) -> Vec<u8>{
+    tagged_hash(BIP322_TAG, message)
+}
+
+//signs a message with the key of the address: a BIP137 signature for P2PKH and P2SH-P2WPKH addresses, and a BIP322
+//simple signature for P2WPKH addresses, both as base64
+pub fn sign_message(secret_key: &SecretKey, address: &str, message: &[u8]) -> Result<String, WalletError>{
+    let secp = Secp256k1::new();
+    let pubkey = PublicKey::from_secret_key(&secp, secret_key);
+    let script_pubkey = address_script(address)?;
+    let key_script = KeyScript::ALL.into_iter().find(|key_script| key_script.script_pubkey(&pubkey) == script_pubkey)
+        .ok_or(WalletError::Policy(format!("{} is not an address of this wallet's key", address)))?;
+    match key_script{
+        KeyScript::P2pkh | KeyScript::P2shP2wpkh => {
+            let header = BIP137_HEADER + if key_script == KeyScript::P2pkh { 4 } else { 8 };
+            let (recovery_id, signature) = secp.sign_ecdsa_recoverable(&Message::from_slice(&message_hash(message))?, secret_key).serialize_compact();
+            Ok(base64_encode(&[vec![header + recovery_id.to_i32() as u8], signature.to_vec()].concat()))
+        }
+        KeyScript::P2wpkh => {
+            let sighash = bip322_sighash(&script_pubkey, message, &pubkey)?;
+            let signature = secp.sign_ecdsa_low_r(&Message::from_slice(&sighash)?, secret_key);
+            //SIGHASH_ALL
+            let witness = vec![[signature.serialize_der().to_vec(), vec![1u8]].concat(), pubkey.serialize().to_vec()];
+            Ok(base64_encode(&serialize_witness(&witness)))
+        }
+    }
+}
+
+//checks a BIP137 or BIP322 simple signature of the message by the address
+pub fn verify_message(address: &str, message: &[u8], signature: &str) -> Result<bool, WalletError>{
+    verify_message_script(&address_script(address)?, message, signature)
+}
+
+//checks a signature of the message made for any P2PKH, P2SH-P2WPKH or P2WPKH address of the public key
+pub fn verify_message_key(pubkey: &PublicKey, message: &[u8], signature: &str) -> Result<bool, WalletError>{
+    for key_script in KeyScript::ALL{
+        match verify_message_script(&key_script.script_pubkey(pubkey), message, signature){
+            Ok(true) => return Ok(true),
+            //a BIP322 signature cannot be for the key's other addresses
+            Ok(false) | Err(WalletError::Policy(_)) => {}
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(false)
+}
+
+fn verify_message_script(script_pubkey: &[u8], message: &[u8], signature: &str) -> Result<bool, WalletError>{
+    let signature = base64_decode(signature).map_err(|e| WalletError::Parse(format!("Invalid signature: {}", e)))?;
+    match signature.as_slice(){
+        [header @ 27..=42, compact @ ..] if compact.len() == 64 => verify_bip137(script_pubkey, message, *header, compact),
+        _ => verify_bip322(script_pubkey, message, &signature),
+    }
+}
+
+//recovers the key from the signature and checks that the address belongs to it; wallets differ in which header they
+//use for segwit addresses, so any header of a compressed key is accepted for any of its addresses
+fn verify_bip137(script_pubkey: &[u8], message: &[u8], header: u8, compact: &[u8]) -> Result<bool, WalletError>{
+    let secp = Secp256k1::new();
+    let recovery_id = RecoveryId::from_i32(((header - BIP137_HEADER) % 4) as i32)?;
+    let signature = RecoverableSignature::from_compact(compact, recovery_id)?;
+    let pubkey = match secp.recover_ecdsa(&Message::from_slice(&message_hash(message))?, &signature){
+        Ok(pubkey) => pubkey,
+        Err(_) => return Ok(false)
+    };
+    Ok(match header < BIP137_HEADER + 4{
+        //uncompressed keys only ever had P2PKH addresses
+        true => p2pkh_script(&hash160(&pubkey.serialize_uncompressed().to_vec().to_string())).into_bytes() == script_pubkey,
+        false => KeyScript::ALL.iter().any(|key_script| key_script.script_pubkey(&pubkey) == script_pubkey)
+    })
+}
+
+//a BIP322 simple signature is the witness spending the address in the virtual to_sign transaction
+fn verify_bip322(script_pubkey: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, WalletError>{
+    let witness = read_witness(signature)?;
+    let pubkey_hash = match script_pubkey{
+        [0x00, 20, pubkey_hash @ ..] if pubkey_hash.len() == 20 => pubkey_hash,
+        _ => return Err(WalletError::Policy(String::from("BIP322 signatures can only be checked for P2WPKH addresses")))
+    };
+    let (signature, pubkey) = match witness.as_slice(){
+        [signature, pubkey] => (signature, pubkey),
+        _ => return Ok(false)
+    };
+    let pubkey = match PublicKey::from_slice(pubkey){
+        Ok(pubkey) if hash160(&pubkey.to_string()) == pubkey_hash => pubkey,
+        _ => return Ok(false)
+    };
+    let der = match signature.split_last(){
+        Some((1, der)) => der,
+        _ => return Ok(false)
+    };
+    let mut signature = match ecdsa::Signature::from_der(der){
+        Ok(signature) => signature,
+        Err(_) => return Ok(false)
+    };
+    signature.normalize_s();
+    let sighash = bip322_sighash(script_pubkey, message, &pubkey)?;
+    Ok(Secp256k1::new().verify_ecdsa(&Message::from_slice(&sighash)?, &signature, &pubkey).is_ok())
+}
+
+//the BIP143 hash a P2WPKH key signs to spend the to_spend transaction of the message in to_sign
+fn bip322_sighash(script_pubkey: &[u8], message: &[u8], pubkey: &PublicKey) -> Result<Vec<u8>, WalletError>{
+    let to_spend = to_spend_txid(&bip322_message_hash(message), script_pubkey);
+    //P2WPKH inputs sign the P2PKH script of their key hash
+    let script_code = p2pkh_script(&hash160(&pubkey.to_string())).into_bytes().to_string();
+    let vin = Vin::new(&to_spend, 0, &script_code, vec![], 0, 0)?;
+    let vout = Vout::new(0, &Script::new().push_opcode(Opcode::OP_RETURN).into_bytes().to_string())?;
+    let to_sign = RawTransaction::new(0, vec![vin], vec![vout], 0);
+    Ok(UnsignedSegwitTransaction::new(to_sign, 0, 1).concat().sha256d())
+}
+
+//the txid of the virtual transaction paying the address, whose only input commits to the message
+fn to_spend_txid(message_hash: &[u8], script_pubkey: &[u8]) -> String{
+    let script_sig = Script::new().push_int(0).push_slice(message_hash).into_bytes();
+    let to_spend: Vec<u8> = [
+        0u32.to_le_bytes().to_vec(),
+        varint(1),
+        vec![0u8;32],
+        u32::MAX.to_le_bytes().to_vec(),
+        var(script_sig),
+        0u32.to_le_bytes().to_vec(),
+        varint(1),
+        0u64.to_le_bytes().to_vec(),
+        var(script_pubkey.to_vec()),
+        0u32.to_le_bytes().to_vec(),
+    ].concat();
+    to_spend.sha256d().reverse().to_string().to_lowercase()
+}
+
+fn address_script(address: &str) -> Result<Vec<u8>, WalletError>{
+    decode_address(address.trim())?.0.to_bytes().map_err(WalletError::InvalidAddress)
+}
+
+fn serialize_witness(witness: &[Vec<u8>]) -> Vec<u8>{
+    let mut result = varint(witness.len());
+    for item in witness.iter(){
+        result.append(&mut var(item.clone()));
+    }
+    result
+}
+
+fn read_witness(bytes: &[u8]) -> Result<Vec<Vec<u8>>, WalletError>{
+    let mut pos = 0;
+    let count = read_varint(bytes, &mut pos).map_err(WalletError::Parse)?;
+    let mut witness = Vec::new();
+    for _ in 0..count{
+        let len = read_varint(bytes, &mut pos).map_err(WalletError::Parse)?;
+        witness.push(read_bytes(bytes, &mut pos, len).map_err(WalletError::Parse)?);
+    }
+    if pos != bytes.len(){
+        return Err(WalletError::Parse(String::from("Invalid signature: data after the witness")))
+    }
+    Ok(witness)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::str::FromStr;
+
+    //the BIP322 test vector key, L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k
+    const BIP322_KEY: &str = "bb051cd0dda0246f33c5a9e133ebd8e7bc02a92af6c41adc131ccd7826c5b004";
+    const BIP322_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const BIP322_EMPTY: &str = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+    const BIP322_HELLO: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+    //Bitcoin Core's signmessage test: key cUeKHd5orzT3mz8P9pxyREHfsWtVfgsfDjiZZBcjUBAaGk1BTj7N
+    const CORE_KEY: &str = "d2b8a0116d641fe7d3036f8464628fb595b480414c13a301b3d4038c811c28b0";
+    const CORE_ADDRESS: &str = "mpLQjfK79b7CCV4VMJWEWAj5Mpx8Up5zxB";
+    const CORE_SIGNATURE: &str = "INbVnW4e6PeRmsv2Qgu8NuopvrVjkcxob+sX8OcZG0SALhWybUjzMLPdAsXI46YZGb0KQTRii+wWIQzRpG/U+S0=";
+
+    #[test]
+    fn bip322_message_hashes(){
+        assert_eq!(bip322_message_hash(b"").to_string().to_lowercase(), "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1");
+        assert_eq!(bip322_message_hash(b"Hello World").to_string().to_lowercase(), "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a");
+    }
+
+    #[test]
+    fn bip322_simple_signatures(){
+        let secret_key = SecretKey::from_str(BIP322_KEY).unwrap();
+        assert_eq!(sign_message(&secret_key, BIP322_ADDRESS, b"").unwrap(), BIP322_EMPTY);
+        assert_eq!(sign_message(&secret_key, BIP322_ADDRESS, b"Hello World").unwrap(), BIP322_HELLO);
+        assert!(verify_message(BIP322_ADDRESS, b"", BIP322_EMPTY).unwrap());
+        assert!(verify_message(BIP322_ADDRESS, b"Hello World", BIP322_HELLO).unwrap());
+        assert!(!verify_message(BIP322_ADDRESS, b"Hello World", BIP322_EMPTY).unwrap());
+    }
+
+    #[test]
+    fn bip137_signature_matches_bitcoin_core(){
+        let secret_key = SecretKey::from_str(CORE_KEY).unwrap();
+        assert_eq!(sign_message(&secret_key, CORE_ADDRESS, b"This is just a test message").unwrap(), CORE_SIGNATURE);
+        assert!(verify_message(CORE_ADDRESS, b"This is just a test message", CORE_SIGNATURE).unwrap());
+        assert!(!verify_message(CORE_ADDRESS, b"This is another message", CORE_SIGNATURE).unwrap());
+        let pubkey = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        assert!(verify_message_key(&pubkey, b"This is just a test message", CORE_SIGNATURE).unwrap());
+        //a key only signs for its own addresses
+        assert!(sign_message(&secret_key, BIP322_ADDRESS, b"x").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::str::FromStr;
+
+use crate::create_transaction::decode_address;
+use crate::transaction_parts::MAX_OP_RETURN_DATA;
+use crate::will_components::DUST_LIMIT;
+use crate::error::WalletError;
+
+//how much a recipient of a payment gets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SendAmount{
+    Sats(u64),
+    //everything the spent coins hold after the other recipients and the fee
+    Max,
+}
+
+impl FromStr for SendAmount{
+    type Err = WalletError;
+
+    //a number of satoshis, or max
+    fn from_str(amount: &str) -> Result<Self, WalletError>{
+        match amount.trim(){
+            max if max.eq_ignore_ascii_case("max") => Ok(SendAmount::Max),
+            sats => sats.parse::<u64>().map(SendAmount::Sats).map_err(|_| WalletError::Parse(format!("Invalid amount: {}", amount)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipient{
+    pub address: String,
+    pub amount: SendAmount,
+}
+
+impl Recipient{
+    pub fn new(address: &str, amount: SendAmount) -> Self{
+        Recipient{
+            address: address.trim().to_string(),
+            amount,
+        }
+    }
+}
+
+//the outputs of a transaction and who pays its fee
+#[derive(Debug, Clone)]
+pub struct Payment{
+    pub recipients: Vec<Recipient>,
+    pub fee: u64,
+    //index of the recipient whose amount the fee comes out of; none means the wallet pays it on top
+    pub subtract_fee_from: Option<usize>,
+    //put on chain in an OP_RETURN output
+    pub data: Option<Vec<u8>>,
+}
+
+impl Payment{
+    pub fn new(recipients: Vec<Recipient>, fee: u64) -> Self{
+        Payment{
+            recipients,
+            fee,
+            subtract_fee_from: None,
+            data: None,
+        }
+    }
+
+    pub fn with_fee_from(mut self, recipient: usize) -> Self{
+        self.subtract_fee_from = Some(recipient);
+        self
+    }
+
+    pub fn with_data(mut self, data: Vec<u8>) -> Self{
+        self.data = Some(data);
+        self
+    }
+
+    //a payment of everything the spent coins hold spends all of them
+    pub fn is_max(&self) -> bool{
+        self.recipients.iter().any(|recipient| recipient.amount == SendAmount::Max)
+    }
+
+    //checks the payment and returns each recipient's locking script and whether it is segwit
+    pub fn validate(&self) -> Result<Vec<(String, bool)>, WalletError>{
+        if self.recipients.is_empty(){
+            return Err(WalletError::Policy(String::from("A transaction needs at least one recipient")))
+        }
+        if self.recipients.iter().filter(|recipient| recipient.amount == SendAmount::Max).count() > 1{
+            return Err(WalletError::Policy(String::from("Only one recipient can be sent the maximum")))
+        }
+        if let Some(i) = self.subtract_fee_from{
+            if i >= self.recipients.len(){
+                return Err(WalletError::Policy(format!("There is no recipient {} to pay the fee", i + 1)))
+            }
+        }
+        if let Some(data) = &self.data{
+            if data.len() > MAX_OP_RETURN_DATA{
+                return Err(WalletError::Policy(format!("OP_RETURN data is {} bytes, more than the standard {}", data.len(), MAX_OP_RETURN_DATA)))
+            }
+        }
+        let mut scripts: Vec<(String, bool)> = Vec::new();
+        for (i, recipient) in self.recipients.iter().enumerate(){
+            scripts.push(decode_address(&recipient.address).map_err(|e| WalletError::InvalidAddress(format!("recipient {} ({}): {}", i + 1, recipient.address, e)))?);
+        }
+        Ok(scripts)
+    }
+
+    //satoshis the spent coins must hold: the fixed amounts, and the fee unless a recipient pays it
+    pub fn needed(&self) -> Result<u64, WalletError>{
+        let mut needed = if self.subtract_fee_from.is_none() { self.fee } else { 0 };
+        for recipient in self.recipients.iter(){
+            if let SendAmount::Sats(sats) = recipient.amount{
+                needed = needed.checked_add(sats).ok_or(WalletError::Policy(String::from("The amounts and fee add up to more than can exist")))?;
+            }
+        }
+        Ok(needed)
+    }
+
+    //what each recipient receives when the transaction spends coins holding input_total
+    pub fn amounts(&self, input_total: u64) -> Result<Vec<u64>, WalletError>{
+        let needed = self.needed()?;
+        let left = input_total.checked_sub(needed).ok_or(WalletError::InsufficientFunds{needed, available: input_total})?;
+        let mut amounts: Vec<u64> = self.recipients.iter().map(|recipient| match recipient.amount{
+            SendAmount::Sats(sats) => sats,
+            SendAmount::Max => left,
+        }).collect();
+        if let Some(i) = self.subtract_fee_from{
+            let amount = amounts.get_mut(i).ok_or(WalletError::Policy(format!("There is no recipient {} to pay the fee", i + 1)))?;
+            *amount = amount.checked_sub(self.fee).ok_or(WalletError::Policy(format!("Recipient {} is sent less than the fee of {} sats", i + 1, self.fee)))?;
+        }
+        for (recipient, amount) in self.recipients.iter().zip(amounts.iter()){
+            if *amount < DUST_LIMIT{
+                return Err(WalletError::Policy(format!("{} sats to {} is below the dust limit of {} sats", amount, recipient.address, DUST_LIMIT)))
+            }
+        }
+        Ok(amounts)
+    }
+}
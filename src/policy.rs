@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+
+use secp256k1::PublicKey;
+
+use crate::descriptor::{Descriptor, DescriptorKey, Miniscript, MAX_MULTI_KEYS, call, split_arguments, parse_older};
+use crate::error::WalletError;
+
+//who can spend a will vault and when, like or(pk(parent),and(thresh(2,pk(A),pk(B),pk(C)),older(52560))),
+//compiled to miniscript for the vault's witness script
+//only pk(), older() of 1 to 65535 blocks, and(), or() and thresh() are understood; or() branches cannot carry
+//probabilities, as every or() becomes an or_i, and a thresh() that is not all keys must need 1 or all of its policies
+#[derive(Debug, Clone, PartialEq)]
+pub enum Policy{
+    Key(DescriptorKey),
+    //the coins must be n blocks old
+    Older(u32),
+    And(Box<Policy>, Box<Policy>),
+    Or(Box<Policy>, Box<Policy>),
+    //k of the policies
+    Thresh(usize, Vec<Policy>),
+}
+
+impl FromStr for Policy{
+    type Err = WalletError;
+
+    fn from_str(policy: &str) -> Result<Self, WalletError>{
+        parse(policy, &[])
+    }
+}
+
+//parses a policy in which each of the names stands for its policy, like parent for pk(<parent key>)
+fn parse(policy: &str, names: &[(&str, Policy)]) -> Result<Policy, WalletError>{
+    let policy = policy.trim();
+    if let Some((_, named)) = names.iter().find(|(name, _)| *name == policy){
+        return Ok(named.clone())
+    }
+    //probabilities like or(9@pk(A),1@pk(B)) would pick a cheaper branch, which this compiler cannot do
+    if let Some((weight, _)) = policy.split_once('@').filter(|(weight, _)| weight.chars().all(|c| c.is_ascii_digit())){
+        return Err(WalletError::Policy(format!("Branch probabilities like {}@ are not supported; every or() compiles to or_i", weight)))
+    }
+    let (function, arguments) = call(policy)?;
+    let arguments = split_arguments(arguments);
+    let two = |arguments: &[&str]| -> Result<(Box<Policy>, Box<Policy>), WalletError>{
+        match arguments{
+            [first, second] => Ok((Box::new(parse(first, names)?), Box::new(parse(second, names)?))),
+            _ => Err(WalletError::Parse(format!("{}() takes two policies", function)))
+        }
+    };
+    match function{
+        "pk" => match arguments[..]{
+            [key] => Ok(Policy::Key(DescriptorKey::from_str(key)?)),
+            _ => Err(WalletError::Parse(String::from("pk() takes one key")))
+        },
+        "older" => Ok(Policy::Older(parse_older(arguments[0])?)),
+        "and" => two(&arguments).map(|(first, second)| Policy::And(first, second)),
+        "or" => two(&arguments).map(|(first, second)| Policy::Or(first, second)),
+        "thresh" => {
+            let threshold = arguments[0].parse::<usize>().map_err(|_| WalletError::Parse(format!("Invalid thresh() threshold: {}", arguments[0])))?;
+            let policies = arguments[1..].iter().map(|policy| parse(policy, names)).collect::<Result<Vec<Policy>, WalletError>>()?;
+            if threshold < 1 || threshold > policies.len(){
+                return Err(WalletError::Policy(format!("Cannot require {} of {} policies", threshold, policies.len())))
+            }
+            Ok(Policy::Thresh(threshold, policies))
+        }
+        _ => Err(WalletError::Policy(format!("Unsupported policy {}()", function)))
+    }
+}
+
+impl Policy{
+    //the miniscript enforcing the policy: or() lets the spender pick a branch with OP_IF, and() checks its first
+    //policy then its second, and a thresh() of keys is a multisig
+    pub fn compile(&self) -> Result<Miniscript, WalletError>{
+        Ok(match self{
+            Policy::Key(key) => Miniscript::Pk(key.clone()),
+            Policy::Older(blocks) => Miniscript::Older(*blocks),
+            Policy::And(first, second) => Miniscript::AndV(Box::new(Miniscript::Verify(Box::new(first.compile()?))), Box::new(second.compile()?)),
+            Policy::Or(first, second) => Miniscript::OrI(Box::new(first.compile()?), Box::new(second.compile()?)),
+            Policy::Thresh(threshold, policies) => {
+                let keys: Vec<DescriptorKey> = policies.iter().filter_map(|policy| match policy{
+                    Policy::Key(key) => Some(key.clone()),
+                    _ => None
+                }).collect();
+                if keys.len() == policies.len(){
+                    if keys.len() > MAX_MULTI_KEYS{
+                        return Err(WalletError::Policy(format!("A multisig can have at most {} keys, not {}", MAX_MULTI_KEYS, keys.len())))
+                    }
+                    Miniscript::Multi(*threshold, keys)
+                }else if *threshold == policies.len(){
+                    fold(policies, Policy::And)?.compile()?
+                }else if *threshold == 1{
+                    fold(policies, Policy::Or)?.compile()?
+                }else{
+                    return Err(WalletError::Policy(format!("thresh({}) of {} policies that are not all keys cannot be compiled; only 1 or all of them can be", threshold, policies.len())))
+                }
+            }
+        })
+    }
+
+    //the P2WSH descriptor of the compiled policy
+    pub fn descriptor(&self) -> Result<Descriptor, WalletError>{
+        Ok(Descriptor::Wsh(self.compile()?))
+    }
+}
+
+//joins the policies pairwise, like and(A,and(B,C))
+fn fold(policies: &[Policy], join: fn(Box<Policy>, Box<Policy>) -> Policy) -> Result<Policy, WalletError>{
+    match policies{
+        [] => Err(WalletError::Policy(String::from("An empty policy cannot be compiled"))),
+        [policy] => Ok(policy.clone()),
+        [first, rest @ ..] => Ok(join(Box::new(first.clone()), Box::new(fold(rest, join)?))),
+    }
+}
+
+impl fmt::Display for Policy{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Policy::Key(key) => write!(f, "pk({})", key),
+            Policy::Older(blocks) => write!(f, "older({})", blocks),
+            Policy::And(first, second) => write!(f, "and({},{})", first, second),
+            Policy::Or(first, second) => write!(f, "or({},{})", first, second),
+            Policy::Thresh(threshold, policies) => write!(f, "thresh({},{})", threshold, policies.iter().map(|policy| policy.to_string()).collect::<Vec<String>>().join(",")),
+        }
+    }
+}
+
+//the will vault: the parent, or m of the guardians, can revoke at any time, and the single-use key can redeem once
+//the vault is locktime_blocks old
+pub fn vault_policy(parent_pubkey: &str, guardian_pubkeys: &[String], guardian_threshold: u8, single_use_pubkey: &str, locktime_blocks: u16) -> Result<Policy, WalletError>{
+    if locktime_blocks == 0{
+        return Err(WalletError::Policy(String::from("A will vault needs a delay of at least one block")))
+    }
+    let parent = Policy::Key(DescriptorKey::from_str(parent_pubkey)?);
+    let revocation = match guardian_pubkeys.is_empty(){
+        true => parent,
+        false => Policy::Or(Box::new(parent), Box::new(guardian_policy(guardian_pubkeys, guardian_threshold)?))
+    };
+    let redemption = Policy::And(Box::new(Policy::Older(locktime_blocks as u32)), Box::new(Policy::Key(DescriptorKey::from_str(single_use_pubkey)?)));
+    Ok(Policy::Or(Box::new(revocation), Box::new(redemption)))
+}
+
+//a will vault of the wallet's own policy, written with parent, guardians, single_use and delay in place of the keys
+//and the tranche's older(), like or(or(parent,guardians),and(delay,single_use)) for the standard vault
+//the policy is refused unless the parent and the guardians can still revoke at any time and the single-use key can
+//redeem after the delay but not before, since the will parts are signed that way
+pub fn custom_vault_policy(policy: &str, parent_pubkey: &str, guardian_pubkeys: &[String], guardian_threshold: u8, single_use_pubkey: &str, locktime_blocks: u16) -> Result<Policy, WalletError>{
+    if locktime_blocks == 0{
+        return Err(WalletError::Policy(String::from("A will vault needs a delay of at least one block")))
+    }
+    let mut names = vec![
+        ("parent", Policy::Key(DescriptorKey::from_str(parent_pubkey)?)),
+        ("single_use", Policy::Key(DescriptorKey::from_str(single_use_pubkey)?)),
+        ("delay", Policy::Older(locktime_blocks as u32)),
+    ];
+    if !guardian_pubkeys.is_empty(){
+        names.push(("guardians", guardian_policy(guardian_pubkeys, guardian_threshold)?));
+    }
+    let vault = parse(policy, &names)?;
+
+    let miniscript = vault.compile()?;
+    let key = |pubkey: &str| PublicKey::from_str(pubkey).map_err(|_| WalletError::Crypto(format!("Invalid public key: {}", pubkey)));
+    let (parent, single_use) = (key(parent_pubkey)?, key(single_use_pubkey)?);
+    let guardians = guardian_pubkeys.iter().take(guardian_threshold as usize).map(|pubkey| key(pubkey)).collect::<Result<Vec<PublicKey>, WalletError>>()?;
+    //only whether a witness can be made matters, so any signature will do
+    let spends = |keys: &[PublicKey], age: u32| miniscript.satisfy(0, &|pubkey: &PublicKey| keys.contains(pubkey).then(|| vec![0u8; 72]), age).is_ok();
+    if !spends(&[parent], 0){
+        return Err(WalletError::Policy(format!("{} does not let the parent revoke the will at any time", policy)))
+    }
+    if !guardian_pubkeys.is_empty() && !spends(&guardians, 0){
+        return Err(WalletError::Policy(format!("{} does not let {} guardians revoke the will at any time", policy, guardian_threshold)))
+    }
+    if !spends(&[single_use], locktime_blocks as u32){
+        return Err(WalletError::Policy(format!("{} does not let the inheritors redeem after {} blocks", policy, locktime_blocks)))
+    }
+    if spends(&[single_use], locktime_blocks as u32 - 1){
+        return Err(WalletError::Policy(format!("{} lets the inheritors redeem before {} blocks", policy, locktime_blocks)))
+    }
+    Ok(vault)
+}
+
+//m of the guardians' keys, a multisig pushed with OP_1 to OP_16
+fn guardian_policy(guardian_pubkeys: &[String], guardian_threshold: u8) -> Result<Policy, WalletError>{
+    if guardian_threshold < 1 || guardian_threshold as usize > guardian_pubkeys.len() || guardian_pubkeys.len() > 16{
+        return Err(WalletError::Policy(format!("Cannot require {} of {} guardian signatures", guardian_threshold, guardian_pubkeys.len())))
+    }
+    let guardians = guardian_pubkeys.iter().map(|pubkey| Ok(Policy::Key(DescriptorKey::from_str(pubkey).map_err(|_| WalletError::Crypto(format!("Invalid guardian public key: {}", pubkey)))?)))
+        .collect::<Result<Vec<Policy>, WalletError>>()?;
+    Ok(Policy::Thresh(guardian_threshold as usize, guardians))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const A: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const B: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const C: &str = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    fn compiled(policy: &str) -> Result<String, WalletError>{
+        Ok(Policy::from_str(policy)?.compile()?.to_string())
+    }
+
+    #[test]
+    fn compiles_the_supported_fragments(){
+        assert_eq!(compiled(&format!("or(pk({}),and(older(10),pk({})))", A, B)).unwrap(), format!("or_i(pk({}),and_v(v:older(10),pk({})))", A, B));
+        assert_eq!(compiled(&format!("thresh(2,pk({}),pk({}),pk({}))", A, B, C)).unwrap(), format!("multi(2,{},{},{})", A, B, C));
+        //a thresh() of other policies is an and() of all of them or an or() of any one
+        assert_eq!(compiled(&format!("thresh(2,pk({}),older(5))", A)).unwrap(), format!("and_v(v:pk({}),older(5))", A));
+        assert_eq!(compiled(&format!("thresh(1,pk({}),older(5))", A)).unwrap(), format!("or_i(pk({}),older(5))", A));
+        assert!(matches!(compiled(&format!("thresh(2,pk({}),pk({}),older(5))", A, B)), Err(WalletError::Policy(_))));
+    }
+
+    #[test]
+    fn branch_probabilities_are_refused(){
+        assert!(matches!(Policy::from_str(&format!("or(9@pk({}),1@pk({}))", A, B)), Err(WalletError::Policy(_))));
+        assert!(Policy::from_str(&format!("or(pk({}),pk({}))", A, B)).is_ok());
+    }
+
+    #[test]
+    fn older_takes_1_to_65535_blocks(){
+        assert_eq!(Policy::from_str("older(65535)").unwrap(), Policy::Older(65535));
+        assert!(Policy::from_str("older(0)").is_err());
+        assert!(Policy::from_str("older(65536)").is_err());
+        assert!(Policy::from_str("older(4194305)").is_err());
+    }
+
+    #[test]
+    fn displays_what_it_parsed(){
+        let policy = format!("or(pk({}),and(thresh(2,pk({}),pk({}),pk({})),older(52560)))", A, A, B, C);
+        assert_eq!(Policy::from_str(&policy).unwrap().to_string(), policy);
+    }
+
+    #[test]
+    fn wallet_policy_names_the_vault_keys(){
+        let guardians = [C.to_string()];
+        assert_eq!(custom_vault_policy("or(parent,and(delay,single_use))", A, &[], 0, B, 144).unwrap(), vault_policy(A, &[], 0, B, 144).unwrap());
+        assert_eq!(custom_vault_policy("or(or(parent,guardians),and(delay,single_use))", A, &guardians, 1, B, 144).unwrap(), vault_policy(A, &guardians, 1, B, 144).unwrap());
+        //a recovery key that can also spend long after the inheritors could
+        let recovery = format!("or(parent,or(and(delay,single_use),and(older(65535),pk({}))))", C);
+        assert!(custom_vault_policy(&recovery, A, &[], 0, B, 144).is_ok());
+    }
+
+    #[test]
+    fn wallet_policy_must_keep_the_will_working(){
+        let guardians = [C.to_string()];
+        //the parent cannot revoke
+        assert!(matches!(custom_vault_policy("and(delay,single_use)", A, &[], 0, B, 144), Err(WalletError::Policy(_))));
+        //the guardians cannot revoke
+        assert!(matches!(custom_vault_policy("or(parent,and(delay,single_use))", A, &guardians, 1, B, 144), Err(WalletError::Policy(_))));
+        //the inheritors cannot redeem, or can redeem too early
+        assert!(matches!(custom_vault_policy("or(parent,and(delay,and(single_use,parent)))", A, &[], 0, B, 144), Err(WalletError::Policy(_))));
+        assert!(matches!(custom_vault_policy("or(parent,single_use)", A, &[], 0, B, 144), Err(WalletError::Policy(_))));
+        assert!(matches!(custom_vault_policy("or(parent,and(older(10),single_use))", A, &[], 0, B, 144), Err(WalletError::Policy(_))));
+        //guardians is only a name when the will has guardians
+        assert!(custom_vault_policy("or(or(parent,guardians),and(delay,single_use))", A, &[], 0, B, 144).is_err());
+    }
+}
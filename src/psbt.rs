@@ -0,0 +1,406 @@
+use secp256k1::{Secp256k1, SecretKey, PublicKey, Message};
+
+use crate::traits::*;
+use crate::transaction_parts::*;
+use crate::will_components::DUST_LIMIT;
+use crate::script::{Script, Opcode, Instruction};
+use crate::error::WalletError;
+
+const PSBT_MAGIC: [u8;5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+
+//highest input sequence that signals the transaction can be replaced by one paying a higher fee (BIP125)
+const RBF_SEQUENCE: u32 = 0xfffffffd;
+
+//a psbt map entry: the key, starting with its type, and the value
+type KeyValue = (Vec<u8>, Vec<u8>);
+
+#[derive(Debug, Clone)]
+pub struct PsbtInput {
+    pub witness_utxo: Option<Vout>,
+    pub witness_script: Option<Vec<u8>>,
+    pub partial_sigs: Vec<(Vec<u8>, Vec<u8>)>,
+    pub sighash_type: Option<u32>,
+}
+
+    impl PsbtInput{
+        pub fn new(witness_utxo: Option<Vout>, witness_script: Option<Vec<u8>>) -> Self{
+            PsbtInput{
+                witness_utxo,
+                witness_script,
+                partial_sigs: vec![],
+                sighash_type: Some(1),
+            }
+        }
+
+        //adds a signature for a public key, replacing any earlier signature by the same key
+        pub fn add_partial_sig(&mut self, pubkey: Vec<u8>, signature: Vec<u8>){
+            self.partial_sigs.retain(|(key, _)| *key != pubkey);
+            self.partial_sigs.push((pubkey, signature));
+        }
+
+        pub fn concat(self) -> Vec<u8> {
+            let mut result: Vec<u8> = Vec::new();
+            if let Some(utxo) = self.witness_utxo{
+                result.append(&mut key_value(vec![PSBT_IN_WITNESS_UTXO], utxo.concat()));
+            }
+            for (pubkey, signature) in self.partial_sigs{
+                let mut key = vec![PSBT_IN_PARTIAL_SIG];
+                key.extend(pubkey);
+                result.append(&mut key_value(key, signature));
+            }
+            if let Some(sighash_type) = self.sighash_type{
+                result.append(&mut key_value(vec![PSBT_IN_SIGHASH_TYPE], sighash_type.to_le_bytes().to_vec()));
+            }
+            if let Some(script) = self.witness_script{
+                result.append(&mut key_value(vec![PSBT_IN_WITNESS_SCRIPT], script));
+            }
+            result.push(0);
+            result
+        }
+    }
+
+//partially signed bitcoin transaction (BIP174) used for spends that need signatures from more than one party
+#[derive(Debug, Clone)]
+pub struct Psbt {
+    pub unsigned_transaction: RawTransaction,
+    pub inputs: Vec<PsbtInput>,
+}
+
+    impl Psbt{
+        //creates a psbt from an unsigned transaction whose inputs carry their previous locking script, value and witness script
+        pub fn new(unsigned_transaction: RawTransaction) -> Self{
+            let mut inputs: Vec<PsbtInput> = Vec::new();
+            for vin in unsigned_transaction.vins.iter(){
+                let witness_utxo = Vout{
+                    value: vin.value,
+                    locking_script_length: vin.locking_script_length.clone(),
+                    locking_script: vin.locking_script.clone(),
+                };
+                let witness_script = match vin.redeem_script.concat_legacy(){
+                    script if script.is_empty() => None,
+                    script => Some(script)
+                };
+                inputs.push(PsbtInput::new(Some(witness_utxo), witness_script));
+            }
+            Psbt{
+                unsigned_transaction,
+                inputs,
+            }
+        }
+
+        pub fn from_base64(psbt: &str) -> Result<Self, WalletError>{
+            Psbt::from_bytes(&base64_decode(psbt).map_err(WalletError::Parse)?)
+        }
+
+        pub fn to_base64(self) -> String{
+            base64_encode(&self.concat())
+        }
+
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, WalletError>{
+            let mut pos = 0;
+            if read_bytes(bytes, &mut pos, 5).map_err(WalletError::Parse)? != PSBT_MAGIC.to_vec(){
+                return Err(WalletError::Parse(String::from("Not a PSBT: missing magic bytes")))
+            }
+
+            let mut unsigned_transaction: Option<RawTransaction> = None;
+            for (key, value) in read_map(bytes, &mut pos).map_err(WalletError::Parse)?{
+                if key == vec![PSBT_GLOBAL_UNSIGNED_TX]{
+                    unsigned_transaction = Some(RawTransaction::from_unsigned(&value)?);
+                }
+            }
+            let mut unsigned_transaction = unsigned_transaction.ok_or(WalletError::Parse(String::from("PSBT has no unsigned transaction")))?;
+
+            let mut inputs: Vec<PsbtInput> = Vec::new();
+            for i in 0..unsigned_transaction.vins.len(){
+                let mut input = PsbtInput::new(None, None);
+                input.sighash_type = None;
+                for (key, value) in read_map(bytes, &mut pos).map_err(WalletError::Parse)?{
+                    match key[0]{
+                        PSBT_IN_WITNESS_UTXO => {
+                            let mut utxo_pos = 0;
+                            let amount: [u8;8] = read_bytes(&value, &mut utxo_pos, 8).map_err(WalletError::Parse)?.try_into().unwrap();
+                            let script_length = read_varint(&value, &mut utxo_pos).map_err(WalletError::Parse)?;
+                            let script = read_bytes(&value, &mut utxo_pos, script_length).map_err(WalletError::Parse)?;
+                            let vin = &mut unsigned_transaction.vins[i];
+                            vin.value = amount;
+                            vin.locking_script_length = varint(script.len());
+                            vin.locking_script = script.clone();
+                            input.witness_utxo = Some(Vout{
+                                value: amount,
+                                locking_script_length: varint(script.len()),
+                                locking_script: script,
+                            });
+                        }
+                        PSBT_IN_PARTIAL_SIG => input.add_partial_sig(key[1..].to_vec(), value),
+                        PSBT_IN_SIGHASH_TYPE => input.sighash_type = Some(value.to_int_le() as u32),
+                        PSBT_IN_WITNESS_SCRIPT => {
+                            unsigned_transaction.vins[i].redeem_script = vec![StackItem::Data(value.clone())];
+                            input.witness_script = Some(value);
+                        }
+                        _ => ()
+                    }
+                }
+                inputs.push(input);
+            }
+            for _ in 0..unsigned_transaction.vouts.len(){
+                read_map(bytes, &mut pos).map_err(WalletError::Parse)?;
+            }
+
+            Ok(Psbt{
+                unsigned_transaction,
+                inputs,
+            })
+        }
+
+        pub fn concat(self) -> Vec<u8> {
+            let mut result: Vec<u8> = PSBT_MAGIC.to_vec();
+            result.append(&mut key_value(vec![PSBT_GLOBAL_UNSIGNED_TX], self.unsigned_transaction.clone().concat_unsigned()));
+            result.push(0);
+            for input in self.inputs{
+                result.append(&mut input.concat());
+            }
+            //an empty map for every output
+            result.resize(result.len() + self.unsigned_transaction.vouts.len(), 0);
+            result
+        }
+
+        //txid of the final transaction, which segwit signatures do not change
+        pub fn txid(&self) -> String{
+            self.unsigned_transaction.txid()
+        }
+
+        //signs every input whose witness script contains the public key of the secret key, returning the number of inputs signed
+        pub fn sign(&mut self, secret_key: SecretKey) -> Result<usize, WalletError>{
+            let secp = Secp256k1::new();
+            let pubkey = PublicKey::from_secret_key(&secp, &secret_key).serialize().to_vec();
+            let mut signed = 0;
+            for i in 0..self.inputs.len(){
+                let witness_script = match self.inputs[i].witness_script.clone(){
+                    Some(script) => script,
+                    None => continue
+                };
+                if !witness_script.windows(pubkey.len()).any(|window| window == pubkey.as_slice()){
+                    continue
+                }
+                let mut vin = self.unsigned_transaction.vins[i].clone();
+                vin.redeem_script = vec![StackItem::Data(witness_script)];
+                if let Some(utxo) = &self.inputs[i].witness_utxo{
+                    vin.value = utxo.value;
+                }
+                let mut unsigned = UnsignedSegwitTransaction::new(self.unsigned_transaction.clone(), i, 1);
+                unsigned.change_vin_p2wsh(vin, 1, 0);
+
+                let message = Message::from_slice(&unsigned.concat().sha256d())?;
+                let mut signature = secp.sign_ecdsa(&message, &secret_key).serialize_der().to_vec();
+                signature.push(1);
+                self.inputs[i].add_partial_sig(pubkey.clone(), signature);
+                signed += 1;
+            }
+            Ok(signed)
+        }
+
+        //pays a new fee out of the single output and signals replaceability, dropping signatures made for the old fee
+        pub fn bump_fee(&mut self, fee: u64) -> Result<(), WalletError>{
+            if self.unsigned_transaction.vouts.len() != 1{
+                return Err(WalletError::Policy(String::from("Only a PSBT with a single output can have its fee bumped")))
+            }
+            let mut input_satoshis: u64 = 0;
+            for input in self.inputs.iter(){
+                input_satoshis += input.witness_utxo.as_ref().ok_or(WalletError::Parse(String::from("PSBT input has no previous output value")))?.value.to_int_le();
+            }
+            let output_satoshis = input_satoshis.checked_sub(fee).ok_or(WalletError::InsufficientFunds{needed: fee, available: input_satoshis})?;
+            if output_satoshis < DUST_LIMIT{
+                return Err(WalletError::Policy(String::from("Fee leaves a dust output")))
+            }
+            self.unsigned_transaction.vouts[0].value = output_satoshis.to_le_bytes();
+            for (vin, input) in self.unsigned_transaction.vins.iter_mut().zip(self.inputs.iter_mut()){
+                vin.sequence = RBF_SEQUENCE.to_le_bytes();
+                input.partial_sigs.clear();
+            }
+            Ok(())
+        }
+
+        //merges the signatures of another copy of the same psbt into this one
+        pub fn combine(&mut self, other: Psbt) -> Result<(), WalletError>{
+            if self.txid() != other.txid(){
+                return Err(WalletError::Policy(String::from("Cannot combine PSBTs for different transactions")))
+            }
+            for (input, other_input) in self.inputs.iter_mut().zip(other.inputs){
+                for (pubkey, signature) in other_input.partial_sigs{
+                    input.add_partial_sig(pubkey, signature);
+                }
+            }
+            Ok(())
+        }
+
+        //finalizes a will revocation co-signed by guardians, spending every vault input through its m-of-n guardian branch
+        pub fn finalize_guardian_revocation(self) -> Result<SignedTransaction, WalletError>{
+            let mut witnesses: Vec<Option<Witness>> = Vec::new();
+            for (i, input) in self.inputs.iter().enumerate(){
+                let witness_script = input.witness_script.clone().ok_or(WalletError::Parse(format!("Input {} has no witness script", i)))?;
+                let (threshold, pubkeys) = multisig_keys(&witness_script).ok_or(WalletError::Parse(format!("Input {} is not a guardian multisig vault", i)))?;
+
+                //signatures must be in the same order as their public keys in the script
+                let mut signatures: Vec<String> = vec![String::from("00")];
+                for pubkey in pubkeys{
+                    if signatures.len() > threshold{
+                        break
+                    }
+                    if let Some((_, signature)) = input.partial_sigs.iter().find(|(key, _)| *key == pubkey){
+                        signatures.push(signature[..signature.len() - 1].to_vec().to_string());
+                    }
+                }
+                if signatures.len() <= threshold{
+                    return Err(WalletError::Policy(format!("Input {} has {} of the {} guardian signatures required", i, signatures.len() - 1, threshold)))
+                }
+
+                //OP_0 selects the guardian branch of the revocation, OP_1 selects revocation over redemption
+                witnesses.push(Some(Witness::new(signatures.iter().map(|sig| sig.as_str()).collect(),
+                vec![StackItem::OP([0u8]), StackItem::OP([1u8]), StackItem::Data(witness_script)], 1)?));
+            }
+            Ok(SignedTransaction::new(self.unsigned_transaction, witnesses, true))
+        }
+    }
+
+//finds the `<m> <pubkey>... <n> OP_CHECKMULTISIG` section of a script and returns m and the public keys
+pub fn multisig_keys(script: &[u8]) -> Option<(usize, Vec<Vec<u8>>)>{
+    let instructions = Script::from_bytes(script.to_vec()).instructions().ok()?;
+    //m and n are pushed with OP_1 to OP_16
+    let small_number = |instruction: &Instruction| match instruction{
+        Instruction::Op(opcode) if (Opcode::OP_1 as u8..=Opcode::OP_16 as u8).contains(&(*opcode as u8)) => Some((*opcode as u8 - Opcode::OP_RESERVED as u8) as usize),
+        _ => None
+    };
+    for end in 0..instructions.len(){
+        if instructions[end] != Instruction::Op(Opcode::OP_CHECKMULTISIG){
+            continue
+        }
+        let key_count = match end.checked_sub(1).and_then(|i| small_number(&instructions[i])){
+            Some(key_count) if key_count + 2 <= end => key_count,
+            _ => continue
+        };
+        let start = end - key_count - 2;
+        let pubkeys: Vec<Vec<u8>> = instructions[start + 1..end - 1].iter().filter_map(|instruction| match instruction{
+            Instruction::Push(pubkey) if pubkey.len() == 33 => Some(pubkey.clone()),
+            _ => None
+        }).collect();
+        match small_number(&instructions[start]){
+            Some(threshold) if pubkeys.len() == key_count && threshold <= key_count => return Some((threshold, pubkeys)),
+            _ => continue
+        }
+    }
+    None
+}
+
+//serializes a single psbt key-value pair
+fn key_value(key: Vec<u8>, value: Vec<u8>) -> Vec<u8>{
+    let mut result = var(key);
+    result.append(&mut var(value));
+    result
+}
+
+//reads psbt key-value pairs up to and including the map separator
+fn read_map(bytes: &[u8], pos: &mut usize) -> Result<Vec<KeyValue>, String>{
+    let mut result = Vec::new();
+    loop{
+        let key_length = read_varint(bytes, pos)?;
+        if key_length == 0{
+            return Ok(result)
+        }
+        let key = read_bytes(bytes, pos, key_length)?;
+        let value_length = read_varint(bytes, pos)?;
+        result.push((key, read_bytes(bytes, pos, value_length)?));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::create_transaction::{generate_timelock_components, create_will_revocation_psbt};
+    use crate::will_components::TimelockComponents;
+
+    fn secret_key(byte: u8) -> SecretKey{
+        SecretKey::from_slice(&[byte;32]).unwrap()
+    }
+
+    fn pubkey(byte: u8) -> PublicKey{
+        PublicKey::from_secret_key(&Secp256k1::new(), &secret_key(byte))
+    }
+
+    //an unsigned revocation of two vaults that any 2 of 3 guardians can co-sign
+    fn revocation() -> (Psbt, Vec<TimelockComponents>){
+        let guardians: Vec<String> = (21..24).map(|byte| pubkey(byte).to_string()).collect();
+        let vaults = vec![
+            generate_timelock_components(&pubkey(7).to_string(), guardians.clone(), 2, 300, "").unwrap(),
+            generate_timelock_components(&pubkey(7).to_string(), guardians, 2, 12, "").unwrap(),
+        ];
+        let vin = Vin::new(&"11".repeat(32), 0, &format!("0014{}", "00".repeat(20)), vec![], 0xffffffff, 100000).unwrap();
+        let vouts = vaults.iter().map(|vault| Vout::new(49000, &vault.locking_script.to_string()).unwrap()).collect();
+        let initiation = SignedTransaction::new(RawTransaction::new(2, vec![vin], vouts, 0), vec![], false);
+        (create_will_revocation_psbt(initiation, vaults.clone(), "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", 1000).unwrap(), vaults)
+    }
+
+    #[test]
+    fn base64_round_trip(){
+        let (mut psbt, _) = revocation();
+        assert_eq!(psbt.sign(secret_key(21)).unwrap(), 2);
+        let base64 = psbt.clone().to_base64();
+        let decoded = Psbt::from_base64(&base64).unwrap();
+        assert_eq!(decoded.txid(), psbt.txid());
+        for (decoded, input) in decoded.inputs.iter().zip(psbt.inputs.iter()){
+            assert_eq!(decoded.witness_script, input.witness_script);
+            assert_eq!(decoded.partial_sigs, input.partial_sigs);
+            assert_eq!(decoded.sighash_type, Some(1));
+            assert_eq!(decoded.witness_utxo.clone().unwrap().concat(), input.witness_utxo.clone().unwrap().concat());
+        }
+        assert_eq!(decoded.to_base64(), base64);
+        assert!(Psbt::from_base64("cHNidP8=").is_err());
+    }
+
+    #[test]
+    fn combine_merges_signatures(){
+        let (mut psbt, _) = revocation();
+        let mut other = psbt.clone();
+        psbt.sign(secret_key(21)).unwrap();
+        other.sign(secret_key(23)).unwrap();
+        other.sign(secret_key(21)).unwrap();
+        psbt.combine(other).unwrap();
+        for input in psbt.inputs.iter(){
+            let mut signers: Vec<Vec<u8>> = input.partial_sigs.iter().map(|(pubkey, _)| pubkey.clone()).collect();
+            signers.sort();
+            let mut expected = vec![pubkey(21).serialize().to_vec(), pubkey(23).serialize().to_vec()];
+            expected.sort();
+            assert_eq!(signers, expected);
+        }
+
+        let mut bumped = psbt.clone();
+        bumped.bump_fee(2000).unwrap();
+        assert!(psbt.combine(bumped).is_err());
+    }
+
+    #[test]
+    fn guardian_revocation_witness_follows_key_order(){
+        let (mut psbt, vaults) = revocation();
+        assert_eq!(multisig_keys(&vaults[0].witness_script), Some((2, (21..24).map(|byte| pubkey(byte).serialize().to_vec()).collect())));
+
+        //signed out of key order, and one guardian short
+        psbt.sign(secret_key(23)).unwrap();
+        assert!(matches!(psbt.clone().finalize_guardian_revocation(), Err(WalletError::Policy(_))));
+        psbt.sign(secret_key(21)).unwrap();
+        let revocation = psbt.clone().finalize_guardian_revocation().unwrap();
+
+        //the same witness as satisfying the vault's miniscript: the dummy, the signatures in key order, then the branch selectors
+        for (i, vault) in vaults.iter().enumerate(){
+            let signatures: Vec<(PublicKey, Vec<u8>)> = psbt.inputs[i].partial_sigs.iter()
+                .map(|(key, signature)| (PublicKey::from_slice(key).unwrap(), signature[..signature.len() - 1].to_vec())).collect();
+            let expected = Witness::new(vec![], vault.witness(&signatures, 0).unwrap(), 1).unwrap().concat();
+            assert_eq!(revocation.witnesses[i].clone().unwrap().concat(), expected);
+        }
+        assert_eq!(revocation.txid(), psbt.txid());
+    }
+}
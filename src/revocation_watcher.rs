@@ -0,0 +1,309 @@
+use std::sync::mpsc::{self, Sender, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::blockchain_info::{ChainBackend, TestnetBackend};
+use crate::wallet_info::*;
+use crate::will_status::*;
+use crate::error::WalletError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent{
+    //the will has not been initiated, or the parent has already checked in
+    Quiet,
+    //an initiation was found and the parent has until the action height to check in
+    InitiationDetected{action_height: u64, blocks_left: u64},
+    Notify(String),
+    Broadcast(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum WatcherCommand{
+    CheckIn,
+    Update(Box<WalletInfo>),
+}
+
+//watches the chain for the parent's will being initiated and takes the parent's chosen action if they do not check in
+#[derive(Debug, Clone)]
+pub struct RevocationWatcher{
+    detected_height: Option<u64>,
+    checked_in: bool,
+    acted: bool,
+}
+
+impl Default for RevocationWatcher{
+    fn default() -> Self{
+        RevocationWatcher::new()
+    }
+}
+
+impl RevocationWatcher{
+    pub fn new() -> Self{
+        RevocationWatcher {
+            detected_height: None,
+            checked_in: false,
+            acted: false,
+        }
+    }
+
+    //the parent has seen the initiation and will decide what to do themselves
+    pub fn check_in(&mut self){
+        if self.detected_height.is_some(){
+            self.checked_in = true;
+        }
+    }
+
+    //checks the will once; call it again with the same watcher every time the chain may have moved
+    pub fn poll(&mut self, will_info: &WalletInfo, backend: &impl ChainBackend) -> Result<WatchEvent, WalletError>{
+        let (redeemable_height, tip) = match will_state(will_info, backend)?{
+            WillState::Initiated{redeemable_height, blocks_left, ..} => (redeemable_height, redeemable_height - 1 - blocks_left),
+            WillState::Redeemable{redeemable_height, ..} => (redeemable_height, backend.tip_height()?),
+            WillState::Revoked | WillState::Redeemed => {
+                *self = RevocationWatcher::new();
+                return Ok(WatchEvent::Quiet)
+            }
+            //an initiation that is not seen may only be missing from the block explorer for a while, so the parent's
+            //check in is kept until the will is settled
+            _ => return Ok(WatchEvent::Quiet)
+        };
+        if self.checked_in || self.acted{
+            return Ok(WatchEvent::Quiet)
+        }
+        let detected_height = *self.detected_height.get_or_insert(tip);
+
+        //act at the end of the window, or earlier if the revocation would otherwise miss the redeemable height
+        let settings = &will_info.revocation_settings;
+        let action_height = (detected_height + settings.window_blocks as u64).min(redeemable_height.saturating_sub(2));
+        if tip < action_height{
+            return Ok(WatchEvent::InitiationDetected{action_height, blocks_left: action_height - tip})
+        }
+
+        self.acted = true;
+        match settings.action{
+            RevocationAction::Notify => Ok(WatchEvent::Notify(format!("Your will was initiated and you did not check in within {} blocks. Revoke it before block {}.", settings.window_blocks, redeemable_height))),
+            RevocationAction::Broadcast => {
+                let revocation_hex = match &will_info.will{
+                    Some(will) if !will.revocation_hex.is_empty() => will.revocation_hex.clone(),
+                    _ => return Ok(WatchEvent::Failed(String::from("No signed will revocation is stored in the wallet")))
+                };
+                match backend.broadcast(&revocation_hex){
+                    Ok(txid) => Ok(WatchEvent::Broadcast(txid)),
+                    Err(e) => {
+                        //try again on the next poll while there is still time
+                        self.acted = false;
+                        Ok(WatchEvent::Failed(e.to_string()))
+                    }
+                }
+            }
+        }
+    }
+}
+
+//runs a revocation watcher on its own thread, polling the testnet backend every interval
+pub fn spawn_watcher(will_info: WalletInfo, interval: Duration) -> (Sender<WatcherCommand>, Receiver<WatchEvent>){
+    let (command_sender, commands) = mpsc::channel();
+    let (event_sender, events) = mpsc::channel();
+    thread::spawn(move || {
+        let mut will_info = will_info;
+        let mut watcher = RevocationWatcher::new();
+        let mut last_event = WatchEvent::Quiet;
+        loop{
+            let event = match watcher.poll(&will_info, &TestnetBackend){
+                Ok(event) => event,
+                Err(e) => WatchEvent::Failed(e.to_string())
+            };
+            if event != last_event{
+                if event_sender.send(event.clone()).is_err(){
+                    return
+                }
+                last_event = event;
+            }
+            //a command cuts the wait short so a check in is seen at once; the wallet closing drops the command sender
+            //and stops the watcher
+            match commands.recv_timeout(interval){
+                Ok(WatcherCommand::CheckIn) => watcher.check_in(),
+                Ok(WatcherCommand::Update(info)) => {
+                    //a new will generation has a new initiation, which the old check in does not cover
+                    if info.will_generation() != will_info.will_generation(){
+                        watcher = RevocationWatcher::new();
+                    }
+                    will_info = *info
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    });
+    (command_sender, events)
+}
+
+#[cfg(test)]
+mod tests{
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+    use crate::blockchain_transaction::BlockchainTransaction;
+    use crate::will_components::{WillParts, Redemption};
+
+    //a chain kept in memory: the tip, the confirmed transactions with their heights, and what was broadcast
+    struct MockChain{
+        tip: Cell<u64>,
+        confirmed: RefCell<Vec<(String, u64)>>,
+        broadcasts: RefCell<Vec<String>>,
+        broadcast_fails: Cell<bool>,
+    }
+
+    impl MockChain{
+        fn new(tip: u64) -> Self{
+            MockChain{
+                tip: Cell::new(tip),
+                confirmed: RefCell::new(vec![]),
+                broadcasts: RefCell::new(vec![]),
+                broadcast_fails: Cell::new(false),
+            }
+        }
+
+        fn confirm(&self, txid: &str, height: u64){
+            self.confirmed.borrow_mut().push((txid.to_string(), height));
+        }
+    }
+
+    impl ChainBackend for MockChain{
+        fn tip_height(&self) -> Result<u64, WalletError>{
+            Ok(self.tip.get())
+        }
+
+        fn transaction(&self, txid: &str) -> Result<Option<BlockchainTransaction>, WalletError>{
+            Ok(self.confirmed.borrow().iter().find(|(confirmed, _)| confirmed == txid).map(|(txid, height)| BlockchainTransaction{
+                txid: txid.clone(),
+                version: 2,
+                vin: vec![],
+                vout: vec![],
+                block_hash: None,
+                block_height: *height as i64,
+                confirmations: self.tip.get() + 1 - height,
+                block_time: 0,
+                size: 0,
+                vsize: 0,
+                value: String::from("0"),
+                value_in: String::from("0"),
+                fees: String::from("0"),
+                hex: String::new(),
+            }))
+        }
+
+        fn broadcast(&self, transaction_hex: &str) -> Result<String, WalletError>{
+            if self.broadcast_fails.get(){
+                return Err(WalletError::Network(String::from("connection refused")))
+            }
+            self.broadcasts.borrow_mut().push(transaction_hex.to_string());
+            Ok(String::from("dd"))
+        }
+    }
+
+    //a will whose vault opens locktime blocks after the initiation aa, revoked by dd, with a 6 block check in window
+    fn will_info(action: RevocationAction, locktime: u16) -> WalletInfo{
+        let mut info = WalletInfo::new_empty();
+        let parts = WillParts::new(String::new(), String::new(), vec![], String::from("aa"), vec![Redemption::new(locktime, String::from("bb"))], String::from("dd"), String::from("0200"));
+        info.will = Some(WillRecord::new(1, &parts));
+        info.revocation_settings = RevocationSettings::new(action, 6);
+        info
+    }
+
+    #[test]
+    fn broadcasts_once_the_window_has_passed(){
+        let info = will_info(RevocationAction::Broadcast, 20);
+        let chain = MockChain::new(99);
+        let mut watcher = RevocationWatcher::new();
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+
+        chain.confirm("aa", 100);
+        chain.tip.set(100);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::InitiationDetected{action_height: 106, blocks_left: 6});
+        chain.tip.set(105);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::InitiationDetected{action_height: 106, blocks_left: 1});
+        chain.tip.set(106);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Broadcast(String::from("dd")));
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+        assert_eq!(*chain.broadcasts.borrow(), vec![String::from("0200")]);
+
+        //the revocation confirming ends the watch
+        chain.confirm("dd", 107);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+    }
+
+    #[test]
+    fn notifies_instead_of_broadcasting(){
+        let info = will_info(RevocationAction::Notify, 20);
+        let chain = MockChain::new(100);
+        chain.confirm("aa", 100);
+        let mut watcher = RevocationWatcher::new();
+        assert!(matches!(watcher.poll(&info, &chain).unwrap(), WatchEvent::InitiationDetected{..}));
+        chain.tip.set(106);
+        assert!(matches!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Notify(_)));
+        assert!(chain.broadcasts.borrow().is_empty());
+    }
+
+    #[test]
+    fn check_in_stops_the_action(){
+        let info = will_info(RevocationAction::Broadcast, 20);
+        let chain = MockChain::new(100);
+        chain.confirm("aa", 100);
+        let mut watcher = RevocationWatcher::new();
+        watcher.poll(&info, &chain).unwrap();
+        watcher.check_in();
+        chain.tip.set(110);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+        assert!(chain.broadcasts.borrow().is_empty());
+
+        //a check in before anything was detected does not silence a later initiation
+        let mut watcher = RevocationWatcher::new();
+        watcher.check_in();
+        chain.tip.set(100);
+        assert!(matches!(watcher.poll(&info, &chain).unwrap(), WatchEvent::InitiationDetected{..}));
+    }
+
+    #[test]
+    fn check_in_survives_the_initiation_going_missing(){
+        let info = will_info(RevocationAction::Broadcast, 20);
+        let chain = MockChain::new(100);
+        chain.confirm("aa", 100);
+        let mut watcher = RevocationWatcher::new();
+        watcher.poll(&info, &chain).unwrap();
+        watcher.check_in();
+        chain.confirmed.borrow_mut().clear();
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+        chain.confirm("aa", 100);
+        chain.tip.set(110);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Quiet);
+        assert!(chain.broadcasts.borrow().is_empty());
+    }
+
+    #[test]
+    fn failed_broadcast_is_retried(){
+        let info = will_info(RevocationAction::Broadcast, 20);
+        let chain = MockChain::new(100);
+        chain.confirm("aa", 100);
+        let mut watcher = RevocationWatcher::new();
+        watcher.poll(&info, &chain).unwrap();
+        chain.tip.set(106);
+        chain.broadcast_fails.set(true);
+        assert!(matches!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Failed(_)));
+        chain.broadcast_fails.set(false);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Broadcast(String::from("dd")));
+        assert_eq!(chain.broadcasts.borrow().len(), 1);
+    }
+
+    #[test]
+    fn acts_two_blocks_before_the_vault_opens(){
+        //the vault opens at block 105, before the window would end at block 106
+        let info = will_info(RevocationAction::Broadcast, 5);
+        let chain = MockChain::new(100);
+        chain.confirm("aa", 100);
+        let mut watcher = RevocationWatcher::new();
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::InitiationDetected{action_height: 103, blocks_left: 3});
+        chain.tip.set(103);
+        assert_eq!(watcher.poll(&info, &chain).unwrap(), WatchEvent::Broadcast(String::from("dd")));
+    }
+}
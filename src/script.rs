@@ -0,0 +1,426 @@
+use std::fmt;
+use std::str::FromStr;
+
+use secp256k1::PublicKey;
+
+use crate::create_transaction::bytes_le_to_int;
+use crate::error::WalletError;
+
+//declares the opcodes and OPCODES, their table of names, from one list, so the byte, the variant and the name
+//cannot disagree
+macro_rules! opcodes{
+    ($($name:ident = $byte:literal,)*) => {
+        //every opcode other than the direct pushes of 1 to 75 bytes
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum Opcode{
+            $($name = $byte,)*
+        }
+
+        //every opcode with its name, in byte order
+        pub const OPCODES: &[(Opcode, &str)] = &[$((Opcode::$name, stringify!($name)),)*];
+    }
+}
+
+opcodes!{
+    OP_0 = 0x00,
+    OP_PUSHDATA1 = 0x4c,
+    OP_PUSHDATA2 = 0x4d,
+    OP_PUSHDATA4 = 0x4e,
+    OP_1NEGATE = 0x4f,
+    OP_RESERVED = 0x50,
+    OP_1 = 0x51,
+    OP_2 = 0x52,
+    OP_3 = 0x53,
+    OP_4 = 0x54,
+    OP_5 = 0x55,
+    OP_6 = 0x56,
+    OP_7 = 0x57,
+    OP_8 = 0x58,
+    OP_9 = 0x59,
+    OP_10 = 0x5a,
+    OP_11 = 0x5b,
+    OP_12 = 0x5c,
+    OP_13 = 0x5d,
+    OP_14 = 0x5e,
+    OP_15 = 0x5f,
+    OP_16 = 0x60,
+    OP_NOP = 0x61,
+    OP_VER = 0x62,
+    OP_IF = 0x63,
+    OP_NOTIF = 0x64,
+    OP_VERIF = 0x65,
+    OP_VERNOTIF = 0x66,
+    OP_ELSE = 0x67,
+    OP_ENDIF = 0x68,
+    OP_VERIFY = 0x69,
+    OP_RETURN = 0x6a,
+    OP_TOALTSTACK = 0x6b,
+    OP_FROMALTSTACK = 0x6c,
+    OP_2DROP = 0x6d,
+    OP_2DUP = 0x6e,
+    OP_3DUP = 0x6f,
+    OP_2OVER = 0x70,
+    OP_2ROT = 0x71,
+    OP_2SWAP = 0x72,
+    OP_IFDUP = 0x73,
+    OP_DEPTH = 0x74,
+    OP_DROP = 0x75,
+    OP_DUP = 0x76,
+    OP_NIP = 0x77,
+    OP_OVER = 0x78,
+    OP_PICK = 0x79,
+    OP_ROLL = 0x7a,
+    OP_ROT = 0x7b,
+    OP_SWAP = 0x7c,
+    OP_TUCK = 0x7d,
+    OP_CAT = 0x7e,
+    OP_SUBSTR = 0x7f,
+    OP_LEFT = 0x80,
+    OP_RIGHT = 0x81,
+    OP_SIZE = 0x82,
+    OP_INVERT = 0x83,
+    OP_AND = 0x84,
+    OP_OR = 0x85,
+    OP_XOR = 0x86,
+    OP_EQUAL = 0x87,
+    OP_EQUALVERIFY = 0x88,
+    OP_RESERVED1 = 0x89,
+    OP_RESERVED2 = 0x8a,
+    OP_1ADD = 0x8b,
+    OP_1SUB = 0x8c,
+    OP_2MUL = 0x8d,
+    OP_2DIV = 0x8e,
+    OP_NEGATE = 0x8f,
+    OP_ABS = 0x90,
+    OP_NOT = 0x91,
+    OP_0NOTEQUAL = 0x92,
+    OP_ADD = 0x93,
+    OP_SUB = 0x94,
+    OP_MUL = 0x95,
+    OP_DIV = 0x96,
+    OP_MOD = 0x97,
+    OP_LSHIFT = 0x98,
+    OP_RSHIFT = 0x99,
+    OP_BOOLAND = 0x9a,
+    OP_BOOLOR = 0x9b,
+    OP_NUMEQUAL = 0x9c,
+    OP_NUMEQUALVERIFY = 0x9d,
+    OP_NUMNOTEQUAL = 0x9e,
+    OP_LESSTHAN = 0x9f,
+    OP_GREATERTHAN = 0xa0,
+    OP_LESSTHANOREQUAL = 0xa1,
+    OP_GREATERTHANOREQUAL = 0xa2,
+    OP_MIN = 0xa3,
+    OP_MAX = 0xa4,
+    OP_WITHIN = 0xa5,
+    OP_RIPEMD160 = 0xa6,
+    OP_SHA1 = 0xa7,
+    OP_SHA256 = 0xa8,
+    OP_HASH160 = 0xa9,
+    OP_HASH256 = 0xaa,
+    OP_CODESEPARATOR = 0xab,
+    OP_CHECKSIG = 0xac,
+    OP_CHECKSIGVERIFY = 0xad,
+    OP_CHECKMULTISIG = 0xae,
+    OP_CHECKMULTISIGVERIFY = 0xaf,
+    OP_NOP1 = 0xb0,
+    OP_CHECKLOCKTIMEVERIFY = 0xb1,
+    OP_CHECKSEQUENCEVERIFY = 0xb2,
+    OP_NOP4 = 0xb3,
+    OP_NOP5 = 0xb4,
+    OP_NOP6 = 0xb5,
+    OP_NOP7 = 0xb6,
+    OP_NOP8 = 0xb7,
+    OP_NOP9 = 0xb8,
+    OP_NOP10 = 0xb9,
+    OP_CHECKSIGADD = 0xba,
+}
+
+impl Opcode{
+    pub fn from_byte(byte: u8) -> Option<Opcode>{
+        OPCODES.binary_search_by_key(&byte, |(opcode, _)| *opcode as u8).ok().map(|i| OPCODES[i].0)
+    }
+
+    pub fn name(&self) -> &'static str{
+        OPCODES[OPCODES.binary_search_by_key(&(*self as u8), |(opcode, _)| *opcode as u8).unwrap()].1
+    }
+}
+
+impl FromStr for Opcode{
+    type Err = WalletError;
+
+    //an opcode by name, in any case, with OP_FALSE and OP_TRUE for OP_0 and OP_1
+    fn from_str(name: &str) -> Result<Self, WalletError>{
+        let name = name.to_ascii_uppercase();
+        match name.as_str(){
+            "OP_FALSE" => Ok(Opcode::OP_0),
+            "OP_TRUE" => Ok(Opcode::OP_1),
+            name => OPCODES.iter().find(|(_, known)| *known == name).map(|(opcode, _)| *opcode).ok_or(WalletError::Parse(format!("Unknown opcode: {}", name)))
+        }
+    }
+}
+
+impl fmt::Display for Opcode{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", self.name())
+    }
+}
+
+//one step of a script: a data push or any other opcode
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction{
+    Push(Vec<u8>),
+    Op(Opcode),
+    //a byte past OP_CHECKSIGADD, which fails the script when executed
+    Invalid(u8),
+}
+
+//a script built from opcodes, minimal pushes and numbers, rather than from concatenated hex
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script{
+    bytes: Vec<u8>,
+}
+
+impl Script{
+    pub fn new() -> Self{
+        Script{bytes: vec![]}
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self{
+        Script{bytes}
+    }
+
+    pub fn push_opcode(mut self, opcode: Opcode) -> Self{
+        self.bytes.push(opcode as u8);
+        self
+    }
+
+    //pushes data with the shortest prefix: the length itself up to 75 bytes, then OP_PUSHDATA1, 2 or 4
+    pub fn push_slice(mut self, data: &[u8]) -> Self{
+        self.bytes.append(&mut push_prefix(data.len()));
+        self.bytes.extend_from_slice(data);
+        self
+    }
+
+    //pushes a number the way the interpreter reads it back: OP_0, OP_1NEGATE and OP_1 to OP_16 where they fit, else
+    //a minimal script number
+    pub fn push_int(self, number: i64) -> Self{
+        match number{
+            0 => self.push_opcode(Opcode::OP_0),
+            -1 => self.push_opcode(Opcode::OP_1NEGATE),
+            1..=16 => {
+                let mut script = self;
+                script.bytes.push(Opcode::OP_1 as u8 + number as u8 - 1);
+                script
+            }
+            _ => self.push_slice(&script_number(number))
+        }
+    }
+
+    pub fn push_key(self, key: &PublicKey) -> Self{
+        self.push_slice(&key.serialize())
+    }
+
+    pub fn append(mut self, mut script: Script) -> Self{
+        self.bytes.append(&mut script.bytes);
+        self
+    }
+
+    pub fn as_bytes(&self) -> &[u8]{
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8>{
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize{
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.bytes.is_empty()
+    }
+
+    //splits the script into its pushes and opcodes, failing on a push that runs past the end
+    pub fn instructions(&self) -> Result<Vec<Instruction>, WalletError>{
+        let mut instructions = vec![];
+        let mut position = 0;
+        let truncated = || WalletError::Parse(String::from("Script ends in the middle of a push"));
+        while position < self.bytes.len(){
+            let byte = self.bytes[position];
+            position += 1;
+            let prefix_size = match byte{
+                0x01..=0x4b => 0,
+                0x4c => 1,
+                0x4d => 2,
+                0x4e => 4,
+                _ => {
+                    instructions.push(match Opcode::from_byte(byte){
+                        Some(opcode) => Instruction::Op(opcode),
+                        None => Instruction::Invalid(byte)
+                    });
+                    continue
+                }
+            };
+            let length = match prefix_size{
+                0 => byte as usize,
+                size => {
+                    let prefix = self.bytes.get(position..position + size).ok_or_else(truncated)?;
+                    position += size;
+                    bytes_le_to_int(prefix.to_vec()) as usize
+                }
+            };
+            let data = self.bytes.get(position..position + length).ok_or_else(truncated)?;
+            position += length;
+            instructions.push(Instruction::Push(data.to_vec()));
+        }
+        Ok(instructions)
+    }
+
+    //the script as text the way Bitcoin Core shows it: small numbers and pushes of up to 4 bytes as decimal, other
+    //pushes as hex and everything else by opcode name
+    pub fn asm(&self) -> String{
+        let instructions = match self.instructions(){
+            Ok(instructions) => instructions,
+            Err(_) => return String::from("[error]")
+        };
+        instructions.iter().map(|instruction| match instruction{
+            Instruction::Push(data) if data.len() <= 4 => read_script_number(data).to_string(),
+            Instruction::Push(data) => data.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            Instruction::Op(Opcode::OP_0) => String::from("0"),
+            Instruction::Op(Opcode::OP_1NEGATE) => String::from("-1"),
+            Instruction::Op(opcode) if (0x51..=0x60).contains(&(*opcode as u8)) => (*opcode as u8 - 0x50).to_string(),
+            Instruction::Op(opcode) => opcode.name().to_string(),
+            Instruction::Invalid(byte) => format!("OP_UNKNOWN<0x{:02x}>", byte),
+        }).collect::<Vec<String>>().join(" ")
+    }
+}
+
+impl fmt::Display for Script{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", self.asm())
+    }
+}
+
+//the opcode or opcodes announcing a push of len bytes
+pub fn push_prefix(len: usize) -> Vec<u8>{
+    match len{
+        0..=75 => vec![len as u8],
+        76..=0xff => vec![Opcode::OP_PUSHDATA1 as u8, len as u8],
+        0x100..=0xffff => [vec![Opcode::OP_PUSHDATA2 as u8], (len as u16).to_le_bytes().to_vec()].concat(),
+        _ => [vec![Opcode::OP_PUSHDATA4 as u8], (len as u32).to_le_bytes().to_vec()].concat()
+    }
+}
+
+//a number as the interpreter reads it: little endian magnitude with the sign in the top bit, adding a byte when the
+//top bit of the magnitude is already set, so 128 is 8000 and -128 is 8080
+pub fn script_number(number: i64) -> Vec<u8>{
+    let mut magnitude = number.unsigned_abs();
+    let mut bytes = vec![];
+    while magnitude > 0{
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    match bytes.last_mut(){
+        Some(last) if *last & 0x80 != 0 => bytes.push(if number < 0 { 0x80 } else { 0 }),
+        Some(last) if number < 0 => *last |= 0x80,
+        _ => {}
+    }
+    bytes
+}
+
+//the value of a script number of up to 8 bytes
+pub fn read_script_number(bytes: &[u8]) -> i64{
+    let Some(last) = bytes.last() else { return 0 };
+    let magnitude = bytes.iter().enumerate().fold(0i64, |number, (i, byte)| {
+        let byte = if i == bytes.len() - 1 { byte & 0x7f } else { *byte };
+        number | (byte as i64) << (8 * i)
+    });
+    if last & 0x80 != 0 { -magnitude } else { magnitude }
+}
+
+//OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG
+pub fn p2pkh_script(pubkey_hash: &[u8]) -> Script{
+    Script::new().push_opcode(Opcode::OP_DUP).push_opcode(Opcode::OP_HASH160).push_slice(pubkey_hash)
+        .push_opcode(Opcode::OP_EQUALVERIFY).push_opcode(Opcode::OP_CHECKSIG)
+}
+
+//<pubkey> OP_CHECKSIG
+pub fn p2pk_script(pubkey: &[u8]) -> Script{
+    Script::new().push_slice(pubkey).push_opcode(Opcode::OP_CHECKSIG)
+}
+
+//OP_HASH160 <script hash> OP_EQUAL
+pub fn p2sh_script(script_hash: &[u8]) -> Script{
+    Script::new().push_opcode(Opcode::OP_HASH160).push_slice(script_hash).push_opcode(Opcode::OP_EQUAL)
+}
+
+//the segwit version as OP_0 or OP_1 to OP_16, then the witness program
+pub fn witness_program_script(version: u8, program: &[u8]) -> Script{
+    Script::new().push_int(version as i64).push_slice(program)
+}
+
+//OP_RETURN <data>, an unspendable output carrying data
+pub fn op_return_script(data: &[u8]) -> Script{
+    Script::new().push_opcode(Opcode::OP_RETURN).push_slice(data)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn opcode_table_goes_both_ways(){
+        //from_byte searches the table, so it must stay in byte order
+        assert!(OPCODES.windows(2).all(|pair| (pair[0].0 as u8) < (pair[1].0 as u8)));
+        for (opcode, name) in OPCODES.iter(){
+            assert_eq!(Opcode::from_byte(*opcode as u8), Some(*opcode));
+            assert_eq!(opcode.name(), *name);
+            assert_eq!(Opcode::from_str(&name.to_lowercase()).unwrap(), *opcode);
+        }
+        assert_eq!(Opcode::from_byte(0x01), None);
+        assert_eq!(Opcode::from_byte(0xbb), None);
+        assert_eq!(Opcode::from_str("OP_TRUE").unwrap(), Opcode::OP_1);
+        assert!(Opcode::from_str("OP_NOTHING").is_err());
+    }
+
+    #[test]
+    fn pushes_use_the_shortest_prefix(){
+        assert_eq!(push_prefix(0), vec![0x00]);
+        assert_eq!(push_prefix(75), vec![75]);
+        assert_eq!(push_prefix(76), vec![0x4c, 76]);
+        assert_eq!(push_prefix(255), vec![0x4c, 0xff]);
+        assert_eq!(push_prefix(256), vec![0x4d, 0x00, 0x01]);
+        assert_eq!(push_prefix(0x10000), vec![0x4e, 0x00, 0x00, 0x01, 0x00]);
+        let data = vec![0xab; 80];
+        assert_eq!(Script::new().push_slice(&data).instructions().unwrap(), vec![Instruction::Push(data)]);
+    }
+
+    #[test]
+    fn numbers_are_minimally_encoded(){
+        for (number, bytes) in [(0, vec![]), (1, vec![0x01]), (-1, vec![0x81]), (127, vec![0x7f]), (128, vec![0x80, 0x00]), (-128, vec![0x80, 0x80]),
+            (255, vec![0xff, 0x00]), (256, vec![0x00, 0x01]), (52560, vec![0x50, 0xcd, 0x00]), (-52560, vec![0x50, 0xcd, 0x80])]{
+            assert_eq!(script_number(number), bytes, "{}", number);
+            assert_eq!(read_script_number(&bytes), number);
+        }
+        assert_eq!(Script::new().push_int(0).into_bytes(), vec![0x00]);
+        assert_eq!(Script::new().push_int(-1).into_bytes(), vec![0x4f]);
+        assert_eq!(Script::new().push_int(16).into_bytes(), vec![0x60]);
+        assert_eq!(Script::new().push_int(17).into_bytes(), vec![0x01, 0x11]);
+    }
+
+    #[test]
+    fn asm_reads_like_bitcoin_core(){
+        let hash = [0x11u8; 20];
+        assert_eq!(p2pkh_script(&hash).asm(), format!("OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG", "11".repeat(20)));
+        let script = Script::new().push_opcode(Opcode::OP_IF).push_int(2).push_opcode(Opcode::OP_ELSE).push_int(52560)
+            .push_opcode(Opcode::OP_CHECKSEQUENCEVERIFY).push_opcode(Opcode::OP_ENDIF).push_int(0).push_int(-1);
+        assert_eq!(script.asm(), "OP_IF 2 OP_ELSE 52560 OP_CHECKSEQUENCEVERIFY OP_ENDIF 0 -1");
+        assert_eq!(Script::from_bytes(vec![0xbb]).asm(), "OP_UNKNOWN<0xbb>");
+        //a push running past the end of the script
+        assert_eq!(Script::from_bytes(vec![0x05, 0x01]).asm(), "[error]");
+    }
+}
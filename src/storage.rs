@@ -0,0 +1,6 @@
+//! The encrypted wallet files, the will parts files and the local chain cache.
+
+pub use crate::file::{read_wallet, write_wallet, read_keys, write_keys, read_transaction_history, write_transaction_history,
+    write_will_parts, read_will_package, write_labels, read_labels, write_history_export, file_commitment,
+    write_watch_only, read_watch_only};
+pub use crate::cache::{Cache, AddressRecord, unlock_cache, wallet_cache, wallet_full_history, wallet_utxos, known_wills};
@@ -219,10 +219,70 @@ pub fn varint (num: usize) -> Vec<u8>{
     }
 }
 
+//reads a variable integer (VarInt / compact size integer) from a byte slice, advancing the position past it
+pub fn read_varint (bytes: &[u8], pos: &mut usize) -> Result<usize, String>{
+    let first = *read_bytes(bytes, pos, 1)?.first().unwrap();
+    let len = match first{
+        253 => 2,
+        254 => 4,
+        255 => 8,
+        n => return Ok(n as usize)
+    };
+    Ok(read_bytes(bytes, pos, len)?.to_int_le() as usize)
+}
+
+//reads a fixed number of bytes from a byte slice, advancing the position past them
+pub fn read_bytes (bytes: &[u8], pos: &mut usize, len: usize) -> Result<Vec<u8>, String>{
+    if *pos + len > bytes.len(){
+        return Err(format!("Unexpected end of data: needed {} bytes at position {} but only {} remain", len, pos, bytes.len() - *pos))
+    }
+    let result = bytes[*pos..*pos + len].to_vec();
+    *pos += len;
+    Ok(result)
+}
+
 //converts an amount of coins in satoshi to BTC and returns everything after and including the decimal point
 pub fn sat_decimal(n: u64) -> String{
     let n = (n % 100000000) as f64;
     let m = n / 1e8;
     let result = format!("{}",m);
     result.chars().skip(1).collect()
-}
\ No newline at end of file
+}
+
+const BASE64_DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+//encodes a byte vector as a padded base64 string
+pub fn base64_encode(bytes: &[u8]) -> String{
+    let mut result = String::new();
+    for chunk in bytes.chunks(3){
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4{
+            if i <= chunk.len(){
+                result.push(BASE64_DIGITS[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            }else{
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+//decodes a padded base64 string into a byte vector
+pub fn base64_decode(base64: &str) -> Result<Vec<u8>, String>{
+    let base64 = base64.trim().trim_end_matches('=');
+    let mut result = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in base64.chars(){
+        let digit = BASE64_DIGITS.iter().position(|&d| d as char == c).ok_or(format!("Invalid character '{}' found in base64 string", c))?;
+        buffer = (buffer << 6) | digit as u32;
+        bits += 6;
+        if bits >= 8{
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Ok(result)
+}
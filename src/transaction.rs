@@ -0,0 +1,9 @@
+//! Building, signing and reading transactions.
+
+pub use crate::create_transaction::{create_transaction, create_payment_psbt, max_sendable, decode_address, decode_segwit_address};
+pub use crate::payment::{Payment, Recipient, SendAmount};
+pub use crate::coin_control::{CoinControl, Coin, list_coins};
+pub use crate::transaction_parts::{Vin, Vout, Witness, StackItem, RawTransaction, SignedTransaction};
+pub use crate::psbt::{Psbt, PsbtInput};
+pub use crate::script::{Script, Opcode, Instruction, script_number};
+pub use crate::decode_transaction::{DecodedTransaction, DecodedInput, DecodedOutput, decode_transaction, script_csv_delay};
@@ -1,4 +1,12 @@
+use std::str::FromStr;
+
 use crate::traits::*;
+use crate::script::{Opcode, push_prefix, op_return_script};
+use crate::will_components::Outpoint;
+use crate::error::WalletError;
+
+//data an OP_RETURN output can carry and still be relayed by default
+pub const MAX_OP_RETURN_DATA: usize = 80;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -14,20 +22,21 @@ pub struct Vin {
 }
 
     impl Vin{
-        pub fn new(txid: &str, vout: u32, previous_locking_script: &str, redeem_script_vector: Vec<&str>, sequence: u32, value: u64) -> Result<Self, String>{
+        pub fn new(txid: &str, vout: u32, previous_locking_script: &str, redeem_script_vector: Vec<&str>, sequence: u32, value: u64) -> Result<Self, WalletError>{
             let mut redeem_script:  Vec<StackItem> = Vec::new();
             for word in redeem_script_vector{
-                redeem_script.push(match script(word){
-                    Some(n) => StackItem::OP(n.to_bytes()?.try_into().unwrap()),
-                    None => StackItem::Data(word.to_bytes()?)
+                //words naming an opcode must be one, so a misspelt opcode is an error rather than data
+                redeem_script.push(match word.to_ascii_uppercase().starts_with("OP_"){
+                    true => StackItem::OP([Opcode::from_str(word)? as u8]),
+                    false => StackItem::Data(word.to_bytes().map_err(WalletError::Parse)?)
                 });
             }
 
             Ok(Vin{
-                txid: txid.to_bytes()?.reverse(),
+                txid: txid.to_bytes().map_err(WalletError::Parse)?.reverse(),
                 vout: vout.to_le_bytes(),
                 locking_script_length: varint(previous_locking_script.len()/2),
-                locking_script: previous_locking_script.to_bytes()?,
+                locking_script: previous_locking_script.to_bytes().map_err(WalletError::Parse)?,
                 redeem_script,
                 sig_script: SigScript::Byte(0),
                 sequence: sequence.to_le_bytes(),
@@ -35,32 +44,33 @@ pub struct Vin {
             })
         }
 
-        pub fn sign(&mut self, signature: &str, sighash_type: u8){
-            self.sig_script = SigScript::Legacy(SigScriptLegacy::new(signature, self.redeem_script.concat_legacy(), sighash_type).unwrap());
+        pub fn sign(&mut self, signature: &str, sighash_type: u8) -> Result<(), WalletError>{
+            self.sig_script = SigScript::Legacy(SigScriptLegacy::new(signature, self.redeem_script.concat_legacy(), sighash_type)?);
+            Ok(())
         }
         
         pub fn concat_legacy(self) -> Vec<u8> {
             self.txid.into_iter()
-            .chain(self.vout.into_iter())
-            .chain(self.locking_script_length.into_iter())
-            .chain(self.locking_script.into_iter())
-            .chain(self.sequence.into_iter())
+            .chain(self.vout)
+            .chain(self.locking_script_length)
+            .chain(self.locking_script)
+            .chain(self.sequence)
             .collect()
         }
 
         pub fn concat_legacy_empty(self) -> Vec<u8> {
             self.txid.into_iter()
-            .chain(self.vout.into_iter())
-            .chain([0].into_iter())
-            .chain(self.sequence.into_iter())
+            .chain(self.vout)
+            .chain([0])
+            .chain(self.sequence)
             .collect()
         }
 
         pub fn concat(self) -> Vec<u8> {
             self.txid.into_iter()
-            .chain(self.vout.into_iter())
-            .chain(self.sig_script.concat().into_iter())
-            .chain(self.sequence.into_iter())
+            .chain(self.vout)
+            .chain(self.sig_script.concat())
+            .chain(self.sequence)
             .collect()
         }
     }
@@ -91,11 +101,11 @@ pub struct SigScriptLegacy{
 }
 
     impl SigScriptLegacy{
-        pub fn new(signature: &str, redeem_script: Vec<u8>, sighash_type: u8) -> Result<Self, String>{
-            let signature = Signature::new(signature, sighash_type).unwrap();
+        pub fn new(signature: &str, redeem_script: Vec<u8>, sighash_type: u8) -> Result<Self, WalletError>{
+            let signature = Signature::new(signature, sighash_type)?;
             let redeem_script_length = match redeem_script.len(){
                 0 => vec![],
-                len => varint(len)
+                len => push_prefix(len)
             };
             let script_sig_length = varint(signature.clone().concat().len() + redeem_script_length.len() + redeem_script.len());
             Ok(SigScriptLegacy{
@@ -107,9 +117,9 @@ pub struct SigScriptLegacy{
         }
         pub fn concatenate(self) -> Vec<u8> {
             self.script_sig_length.into_iter()
-            .chain(self.signature.concat().into_iter())
-            .chain(self.redeem_script_length.into_iter())
-            .chain(self.redeem_script.into_iter())
+            .chain(self.signature.concat())
+            .chain(self.redeem_script_length)
+            .chain(self.redeem_script)
             .collect()
         }
     }
@@ -123,18 +133,31 @@ pub struct Vout {
 }
 
     impl Vout{
-        pub fn new(amount_satoshis: u64, locking_script: &str) -> Result<Self, String>{
+        pub fn new(amount_satoshis: u64, locking_script: &str) -> Result<Self, WalletError>{
             Ok(Vout{
                 value: amount_satoshis.to_le_bytes(),
                 locking_script_length: varint(locking_script.len()/2),
-                locking_script: locking_script.to_bytes()?,
+                locking_script: locking_script.to_bytes().map_err(WalletError::Parse)?,
+            })
+        }
+
+        //an unspendable output of 0 satoshis that puts the data on chain
+        pub fn op_return(data: &[u8]) -> Result<Self, WalletError>{
+            if data.len() > MAX_OP_RETURN_DATA{
+                return Err(WalletError::Policy(format!("OP_RETURN data is {} bytes, more than the standard {}", data.len(), MAX_OP_RETURN_DATA)))
+            }
+            let locking_script = op_return_script(data).into_bytes();
+            Ok(Vout{
+                value: 0u64.to_le_bytes(),
+                locking_script_length: varint(locking_script.len()),
+                locking_script,
             })
         }
 
         pub fn concat(self) -> Vec<u8> {
             self.value.into_iter()
-            .chain(self.locking_script_length.into_iter())
-            .chain(self.locking_script.into_iter())
+            .chain(self.locking_script_length)
+            .chain(self.locking_script)
             .collect()
         }
     }
@@ -148,7 +171,7 @@ pub struct Signature {
 }
 
     impl Signature{
-        pub fn new(signature_hex: &str, sighash_type: u8) -> Result<Self, String>{
+        pub fn new(signature_hex: &str, sighash_type: u8) -> Result<Self, WalletError>{
             if signature_hex == "00"{
                 return Ok(Signature{
                     signature: vec![0],
@@ -158,15 +181,15 @@ pub struct Signature {
             }
             Ok(Signature{
                 
-                signature: signature_hex.to_bytes()?,
+                signature: signature_hex.to_bytes().map_err(WalletError::Parse)?,
                 sighash_type: vec![sighash_type],
                 size: varint(signature_hex.len()/2 + 1),
             })
         }
         pub fn concat(self) -> Vec<u8> {
             self.size.into_iter()
-            .chain(self.signature.into_iter())
-            .chain(self.sighash_type.into_iter())
+            .chain(self.signature)
+            .chain(self.sighash_type)
             .collect()
         }
     }
@@ -214,7 +237,7 @@ pub struct Witness {
 }
 
     impl Witness{
-        pub fn new(signatures_list: Vec<&str>, redeem_script: Vec<StackItem>, sighash_type: u8) -> Result<Self, String>{
+        pub fn new(signatures_list: Vec<&str>, redeem_script: Vec<StackItem>, sighash_type: u8) -> Result<Self, WalletError>{
             let mut signatures:  Vec<Signature> = Vec::new();
             for sig in signatures_list{
                 signatures.push(Signature::new(sig, sighash_type)?);
@@ -228,9 +251,9 @@ pub struct Witness {
         }
         pub fn concat(self) -> Vec<u8> {
             self.nitems.into_iter()
-            .chain(self.signatures.concat().into_iter())
-            .chain(self.code_separator.into_iter())
-            .chain(self.redeem_script.concat().into_iter())
+            .chain(self.signatures.concat())
+            .chain(self.code_separator)
+            .chain(self.redeem_script.concat())
             .collect()
         }
     }
@@ -261,27 +284,94 @@ pub struct RawTransaction {
             match sighash_type{
                 1 => {
                     self.version.into_iter()
-                    .chain(self.vin_count.into_iter())
-                    .chain(self.vins.concat_legacy(index).into_iter())
-                    .chain(self.vout_count.into_iter())
-                    .chain(self.vouts.concat().into_iter())
-                    .chain(self.locktime.into_iter())
-                    .chain(vec![sighash_type,0,0,0].into_iter())
+                    .chain(self.vin_count)
+                    .chain(self.vins.concat_legacy(index))
+                    .chain(self.vout_count)
+                    .chain(self.vouts.concat())
+                    .chain(self.locktime)
+                    .chain(vec![sighash_type,0,0,0])
                     .collect()
                 }
                 3 => {
                     self.version.into_iter()
-                    .chain(self.vin_count.into_iter())
-                    .chain(self.vins.concat_legacy(index).into_iter())
-                    .chain(self.vout_count.into_iter())
-                    .chain(self.vouts.concat_empty(index).into_iter())
-                    .chain(self.locktime.into_iter())
-                    .chain(vec![sighash_type,0,0,0].into_iter())
+                    .chain(self.vin_count)
+                    .chain(self.vins.concat_legacy(index))
+                    .chain(self.vout_count)
+                    .chain(self.vouts.concat_empty(index))
+                    .chain(self.locktime)
+                    .chain(vec![sighash_type,0,0,0])
                     .collect()
                 }
                 _ => vec![0]
             }
         }
+
+        //txid of the transaction once signed with segwit witnesses
+        pub fn txid(&self) -> String {
+            self.clone().concat_unsigned().sha256d().reverse().to_string().to_lowercase()
+        }
+
+        //serializes the transaction with every script sig left empty, as used for the txid of segwit transactions and in PSBTs
+        pub fn concat_unsigned(self) -> Vec<u8> {
+            let mut vins: Vec<u8> = Vec::new();
+            for vin in self.vins{
+                vins.append(&mut vin.concat_legacy_empty());
+            }
+            self.version.into_iter()
+            .chain(self.vin_count)
+            .chain(vins)
+            .chain(self.vout_count)
+            .chain(self.vouts.concat())
+            .chain(self.locktime)
+            .collect()
+        }
+
+        //parses a transaction serialized with empty script sigs; previous locking scripts and values are left empty
+        pub fn from_unsigned(bytes: &[u8]) -> Result<Self, WalletError>{
+            let mut pos = 0;
+            let version: [u8;4] = read_bytes(bytes, &mut pos, 4).map_err(WalletError::Parse)?.try_into().unwrap();
+            let mut vins: Vec<Vin> = Vec::new();
+            for _ in 0..read_varint(bytes, &mut pos).map_err(WalletError::Parse)?{
+                let txid = read_bytes(bytes, &mut pos, 32).map_err(WalletError::Parse)?;
+                let vout: [u8;4] = read_bytes(bytes, &mut pos, 4).map_err(WalletError::Parse)?.try_into().unwrap();
+                if read_varint(bytes, &mut pos).map_err(WalletError::Parse)? != 0{
+                    return Err(WalletError::Parse(String::from("Unsigned transactions must have empty script sigs")))
+                }
+                let sequence: [u8;4] = read_bytes(bytes, &mut pos, 4).map_err(WalletError::Parse)?.try_into().unwrap();
+                vins.push(Vin{
+                    txid,
+                    vout,
+                    locking_script_length: varint(0),
+                    locking_script: vec![],
+                    sig_script: SigScript::Byte(0),
+                    redeem_script: vec![],
+                    sequence,
+                    value: [0u8;8],
+                });
+            }
+            let mut vouts: Vec<Vout> = Vec::new();
+            for _ in 0..read_varint(bytes, &mut pos).map_err(WalletError::Parse)?{
+                let value: [u8;8] = read_bytes(bytes, &mut pos, 8).map_err(WalletError::Parse)?.try_into().unwrap();
+                let script_length = read_varint(bytes, &mut pos).map_err(WalletError::Parse)?;
+                vouts.push(Vout{
+                    value,
+                    locking_script_length: varint(script_length),
+                    locking_script: read_bytes(bytes, &mut pos, script_length).map_err(WalletError::Parse)?,
+                });
+            }
+            let locktime: [u8;4] = read_bytes(bytes, &mut pos, 4).map_err(WalletError::Parse)?.try_into().unwrap();
+            if pos != bytes.len(){
+                return Err(WalletError::Parse(format!("{} unexpected bytes after the end of the transaction", bytes.len() - pos)))
+            }
+            Ok(RawTransaction{
+                version,
+                vin_count: varint(vins.len()),
+                vins,
+                vout_count: varint(vouts.len()),
+                vouts,
+                locktime,
+            })
+        }
     }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -361,32 +451,32 @@ pub struct UnsignedSegwitTransaction {
             match self.sighash_type[0]{
                 1 => {
                     self.version.into_iter()
-                    .chain(self.hashPrevouts.into_iter())
-                    .chain(self.hashSequence.into_iter())
-                    .chain(self.txid.into_iter())
-                    .chain(self.vout.into_iter())
-                    .chain(self.script_code_length.into_iter())
-                    .chain(self.script_code.into_iter()) //FIX
-                    .chain(self.value.into_iter())
-                    .chain(self.sequence.into_iter())
-                    .chain(self.hashOutputs.into_iter())
-                    .chain(self.locktime.into_iter())
-                    .chain(self.sighash_type.into_iter())
+                    .chain(self.hashPrevouts)
+                    .chain(self.hashSequence)
+                    .chain(self.txid)
+                    .chain(self.vout)
+                    .chain(self.script_code_length)
+                    .chain(self.script_code) //FIX
+                    .chain(self.value)
+                    .chain(self.sequence)
+                    .chain(self.hashOutputs)
+                    .chain(self.locktime)
+                    .chain(self.sighash_type)
                     .collect()
                 }
                 3 => {
                     self.version.into_iter()
-                    .chain(self.hashPrevouts.into_iter())
-                    .chain(vec![0u8;32].into_iter())
-                    .chain(self.txid.into_iter())
-                    .chain(self.vout.into_iter())
-                    .chain(self.script_code_length.into_iter())
-                    .chain(self.script_code.into_iter()) //FIX
-                    .chain(self.value.into_iter())
-                    .chain(self.sequence.into_iter())
-                    .chain(vec![0u8;32].into_iter())
-                    .chain(self.locktime.into_iter())
-                    .chain(self.sighash_type.into_iter())
+                    .chain(self.hashPrevouts)
+                    .chain(vec![0u8;32])
+                    .chain(self.txid)
+                    .chain(self.vout)
+                    .chain(self.script_code_length)
+                    .chain(self.script_code) //FIX
+                    .chain(self.value)
+                    .chain(self.sequence)
+                    .chain(vec![0u8;32])
+                    .chain(self.locktime)
+                    .chain(self.sighash_type)
                     .collect()
                 }
                 _ => vec![0]
@@ -426,26 +516,36 @@ pub struct SignedTransaction {
         pub fn concat(self) -> Vec<u8> {
             if self.has_segwit_input{
                 self.version.into_iter()
-                .chain(self.marker.into_iter())
-                .chain(self.vin_count.into_iter())
-                .chain(self.vins.concat().into_iter())
-                .chain(self.vout_count.into_iter())
-                .chain(self.vouts.concat().into_iter())
-                .chain(self.witnesses.concat().into_iter())
-                .chain(self.locktime.into_iter())
+                .chain(self.marker)
+                .chain(self.vin_count)
+                .chain(self.vins.concat())
+                .chain(self.vout_count)
+                .chain(self.vouts.concat())
+                .chain(self.witnesses.concat())
+                .chain(self.locktime)
                 .collect()
             }else{
                 self.concat_legacy()
             }
         }
 
+        //txid of the transaction, which does not commit to the witnesses
+        pub fn txid(&self) -> String {
+            self.clone().concat_legacy().sha256d().reverse().to_string().to_lowercase()
+        }
+
+        //the previous outputs spent by this transaction
+        pub fn outpoints(&self) -> Vec<Outpoint> {
+            self.vins.iter().map(|vin| Outpoint::new(&vin.txid.reverse().to_string(), vin.vout.to_vec().to_int_le() as u32)).collect()
+        }
+
         pub fn concat_legacy(self) -> Vec<u8> {
             self.version.into_iter()
-            .chain(self.vin_count.into_iter())
-            .chain(self.vins.concat().into_iter())
-            .chain(self.vout_count.into_iter())
-            .chain(self.vouts.concat().into_iter())
-            .chain(self.locktime.into_iter())
+            .chain(self.vin_count)
+            .chain(self.vins.concat())
+            .chain(self.vout_count)
+            .chain(self.vouts.concat())
+            .chain(self.locktime)
             .collect()
         }
     }
@@ -513,7 +613,7 @@ impl ConcatEmpty for Vec<Vout>{
     }
     fn concat_empty(&self, index: usize) -> Vec<u8> {
         let mut concatenated_bytes: Vec<u8> = Vec::new();
-        for (j, vout )in self.into_iter().enumerate(){
+        for (j, vout )in self.iter().enumerate(){
             if index == j{
                 concatenated_bytes.append(&mut vout.clone().concat());
             }else{
@@ -603,12 +703,12 @@ pub struct UnsignedTransactionLegacy {
 
         pub fn concat(self) -> Vec<u8> {
             self.version.into_iter()
-            .chain(self.vin_count.into_iter())
-            .chain(self.vins.concat().into_iter())
-            .chain(self.vout_count.into_iter())
-            .chain(self.vouts.concat().into_iter())
-            .chain(self.locktime.into_iter())
-            .chain(self.sighash_type.into_iter())
+            .chain(self.vin_count)
+            .chain(self.vins.concat())
+            .chain(self.vout_count)
+            .chain(self.vouts.concat())
+            .chain(self.locktime)
+            .chain(self.sighash_type)
             .collect()
         }
     }
@@ -624,22 +724,22 @@ pub struct VinLegacy {
 }
 
     impl VinLegacy{
-        pub fn new(txid: &str, vout: u32, locking_script: &str, sequence: u32) -> Result<Self, String>{
+        pub fn new(txid: &str, vout: u32, locking_script: &str, sequence: u32) -> Result<Self, WalletError>{
             Ok(VinLegacy{
-                txid: txid.to_bytes()?.reverse(),
+                txid: txid.to_bytes().map_err(WalletError::Parse)?.reverse(),
                 vout: vout.to_le_bytes(),
                 locking_script_length: varint(locking_script.len()/2),
-                locking_script: locking_script.to_bytes()?,
+                locking_script: locking_script.to_bytes().map_err(WalletError::Parse)?,
                 sequence: sequence.to_le_bytes(),
             })
         }
         
         pub fn concat(self) -> Vec<u8> {
             self.txid.into_iter()
-            .chain(self.vout.into_iter())
-            .chain(self.locking_script_length.into_iter())
-            .chain(self.locking_script.into_iter())
-            .chain(self.sequence.into_iter())
+            .chain(self.vout)
+            .chain(self.locking_script_length)
+            .chain(self.locking_script)
+            .chain(self.sequence)
             .collect()
         }
     }
@@ -659,11 +759,11 @@ pub struct VinSigned {
 }
 
     impl VinSigned{
-        pub fn new(vin: Vin, signature: &str, redeem_script: &str) -> Result<Self, String>{
+        pub fn new(vin: Vin, signature: &str, redeem_script: &str) -> Result<Self, WalletError>{
             let signature_length = varint(signature.len()/2);
-            let signature = signature.to_bytes()?;
-            let redeem_script_length = varint(redeem_script.len()/2);
-            let redeem_script = redeem_script.to_bytes()?;
+            let signature = signature.to_bytes().map_err(WalletError::Parse)?;
+            let redeem_script_length = push_prefix(redeem_script.len()/2);
+            let redeem_script = redeem_script.to_bytes().map_err(WalletError::Parse)?;
             let script_sig_length = varint(signature_length.len() + signature.len() + 1 + redeem_script_length.len() + redeem_script.len());
 
             Ok(VinSigned{
@@ -681,105 +781,14 @@ pub struct VinSigned {
 
         pub fn concat(self) -> Vec<u8> {
             self.txid.into_iter()
-            .chain(self.vout.into_iter())
-            .chain(self.script_sig_length.into_iter())
-            .chain(self.signature_length.into_iter())
-            .chain(self.signature.into_iter())
-            .chain(self.sighash_type.into_iter())
-            .chain(self.redeem_script_length.into_iter())
-            .chain(self.redeem_script.into_iter())
-            .chain(self.sequence.into_iter())
+            .chain(self.vout)
+            .chain(self.script_sig_length)
+            .chain(self.signature_length)
+            .chain(self.signature)
+            .chain(self.sighash_type)
+            .chain(self.redeem_script_length)
+            .chain(self.redeem_script)
+            .chain(self.sequence)
             .collect()
         }
-    }
-
-
-fn script (script: &str) -> Option<&str>{
-    Some(match script.to_ascii_uppercase().as_ref() {
-        "OP_FALSE" => "00",
-        "OP_0" => "00",
-        "OP_PUSHDATA1" => "4c",
-        "OP_PUSHDATA2" => "4d",
-        "OP_PUSHDATA4" => "4e",
-        "OP_1NEGATE" => "4f",
-        "OP_TRUE" => "51",
-        "OP_1" => "51",
-        "OP_2" => "52",
-        "OP_3" => "53",
-        "OP_4" => "54",
-        "OP_5" => "55",
-        "OP_6" => "56",
-        "OP_7" => "57",
-        "OP_8" => "58",
-        "OP_9" => "59",
-        "OP_10" => "5a",
-        "OP_11" => "5b",
-        "OP_12" => "5c",
-        "OP_13" => "5d",
-        "OP_14" => "5e",
-        "OP_15" => "5f",
-        "OP_16" => "60",
-        "OP_NOP" => "61",
-        "OP_IF" => "63",
-        "OP_NOTIF" => "64",
-        "OP_ELSE" => "67",
-        "OP_ENDIF" => "68",
-        "OP_VERIFY" => "69",
-        "OP_RETURN" => "6a",
-        "OP_TOALTSTACK" => "6b",
-        "OP_FROMALTSTACK" => "6c",
-        "OP_IFDUP" => "73",
-        "OP_DEPTH" => "74",
-        "OP_DROP" => "75",
-        "OP_DUP" => "76",
-        "OP_NIP" => "77",
-        "OP_OVER" => "78",
-        "OP_PICK" => "79",
-        "OP_ROLL" => "7a",
-        "OP_ROT" => "7b",
-        "OP_SWAP" => "7c",
-        "OP_TUCK" => "7d",
-        "OP_2DROP" => "6d",
-        "OP_2DUP" => "6e",
-        "OP_3DUP" => "6f",
-        "OP_2OVER" => "70",
-        "OP_2ROT" => "71",
-        "OP_2SWAP" => "72",
-        "OP_SIZE" => "82",
-        "OP_EQUAL" => "87",
-        "OP_EQUALVERIFY" => "88",
-        "OP_1ADD" => "8b",
-        "OP_1SUB" => "8c",
-        "OP_NEGATE" => "8f",
-        "OP_ABS" => "90",
-        "OP_NOT" => "91",
-        "OP_0NOTEQUAL" => "92",
-        "OP_ADD" => "93",
-        "OP_SUB" => "94",
-        "OP_BOOLAND" => "9a",
-        "OP_BOOLOR" => "9b",
-        "OP_NUMEQUAL" => "9c",
-        "OP_NUMEQUALVERIFY" => "9d",
-        "OP_NUMNOTEQUAL" => "9e",
-        "OP_LESSTHAN" => "9f",
-        "OP_GREATERTHAN" => "a0",
-        "OP_LESSTHANOREQUAL" => "a1",
-        "OP_GREATERTHANOREQUAL" => "a2",
-        "OP_MIN" => "a3",
-        "OP_MAX" => "a4",
-        "OP_WITHIN" => "a5",
-        "OP_RIPEMD160" => "a6",
-        "OP_SHA1" => "a7",
-        "OP_SHA256" => "a8",
-        "OP_HASH160" => "a9",
-        "OP_HASH256" => "aa",
-        "OP_CODESEPARATOR" => "ab",
-        "OP_CHECKSIG" => "ac",
-        "OP_CHECKSIGVERIFY" => "ad",
-        "OP_CHECKMULTISIG" => "ae",
-        "OP_CHECKMULTISIGVERIFY" => "af",
-        "OP_CHECKLOCKTIMEVERIFY" => "b1",
-        "OP_CHECKSEQUENCEVERIFY" => "b2",
-        _ => return None,
-    })
-}
\ No newline at end of file
+    }
\ No newline at end of file
@@ -0,0 +1,57 @@
+//! The wallet: its key, address, inheritors and guardians.
+
+use std::path::Path;
+
+use secp256k1::{Secp256k1, SecretKey, PublicKey};
+
+use crate::create_transaction::wrap_p2pkh_testnet;
+use crate::file::{write_keys, write_wallet, read_watch_only};
+use crate::error::WalletError;
+
+pub use crate::wallet_info::{WalletInfo, Inheritor, Inheritors, Guardian, Guardians, WillRecord, RevocationAction, RevocationSettings, TransactionHistory,
+    WillTransaction, HistoryPage, mark_will_transactions};
+pub use crate::labels::{Label, Labels, LabelType};
+pub use crate::export::{PriceSource, CsvPriceFile, FiatValue, ExportRow, export_rows, to_csv, to_json};
+pub use crate::watch_only::{Xpub, WatchSource, WATCH_GAP};
+pub use crate::descriptor::{Descriptor, DescriptorKey, Miniscript, descriptor_checksum};
+pub use crate::policy::Policy;
+pub use crate::message::{sign_message, verify_message, verify_message_key, message_hash, bip322_message_hash};
+
+//redemption period of a new wallet's will, about a year of blocks
+pub const DEFAULT_LOCKTIME_BLOCKS: u32 = 52560;
+
+//writes the encrypted key and wallet files for a secret key, refusing to overwrite an existing wallet
+pub fn create_wallet(secret_key: SecretKey, password: &str) -> Result<WalletInfo, WalletError>{
+    if Path::new("wallet_info.json").exists(){
+        return Err(WalletError::Storage(String::from("wallet_info.json already exists")))
+    }
+    let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key).to_string();
+    let address = wrap_p2pkh_testnet(&public_key);
+    let wallet = WalletInfo::new(public_key, address, 0, vec![], vec![], 0, DEFAULT_LOCKTIME_BLOCKS);
+    write_keys(&secret_key.display_secret().to_string(), password)?;
+    write_wallet(wallet.clone(), password)?;
+    Ok(wallet)
+}
+
+//writes a wallet file that watches the source's addresses; there is no key file, so the wallet cannot sign
+pub fn create_watch_only_wallet(source: WatchSource, password: &str) -> Result<WalletInfo, WalletError>{
+    let addresses = source.addresses()?;
+    let pubkey = source.first_pubkey()?.unwrap_or_default();
+    let mut wallet = WalletInfo::new(pubkey, addresses[0].clone(), 0, vec![], vec![], 0, DEFAULT_LOCKTIME_BLOCKS);
+    wallet.watch = Some(source);
+    wallet.watch_addresses = addresses;
+    write_new_wallet(wallet, password)
+}
+
+//imports a watch-only copy of another wallet, with its will, so its will and vaults can be monitored
+pub fn import_watch_only_wallet(path: &str, password: &str) -> Result<WalletInfo, WalletError>{
+    write_new_wallet(read_watch_only(path)?, password)
+}
+
+fn write_new_wallet(wallet: WalletInfo, password: &str) -> Result<WalletInfo, WalletError>{
+    if Path::new("wallet_info.json").exists(){
+        return Err(WalletError::Storage(String::from("wallet_info.json already exists")))
+    }
+    write_wallet(wallet.clone(), password)?;
+    Ok(wallet)
+}
@@ -1,5 +1,17 @@
+use std::str::FromStr;
+
+use secp256k1::PublicKey;
 use serde::*;
 
+use crate::blockchain_utxo::UTXO;
+use crate::labels::Labels;
+use crate::message::{verify_message, verify_message_key};
+use crate::traits::HexString;
+use crate::transaction_parts::MAX_OP_RETURN_DATA;
+use crate::watch_only::WatchSource;
+use crate::will_components::{Tranche, Outpoint, Redemption, WillParts, TOTAL_SHARES, vault_locktime};
+use crate::error::WalletError;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WalletInfo{
     pub pubkey: String,
@@ -7,18 +19,47 @@ pub struct WalletInfo{
     pub value: u64,
     pub inheritors: Vec<Inheritor>,
     pub guardians: Vec<Guardian>,
+    #[serde(default)]
+    pub guardian_threshold: u8,
     pub locktime: u32,
+    #[serde(default)]
+    pub will: Option<WillRecord>,
+    #[serde(default)]
+    pub revocation_settings: RevocationSettings,
+    #[serde(default)]
+    pub labels: Labels,
+    //hex data the will initiation puts on chain in an OP_RETURN output, like the hash of a legal document; empty for none
+    #[serde(default)]
+    pub initiation_data: String,
+    //set for a wallet that watches addresses without their key; it builds unsigned PSBTs but never signs
+    #[serde(default)]
+    pub watch: Option<WatchSource>,
+    //every address a watch-only wallet watches, the first being its address
+    #[serde(default)]
+    pub watch_addresses: Vec<String>,
+    //the policy the will vaults are compiled from, written with parent, guardians, single_use and delay; empty for
+    //the standard vault
+    #[serde(default)]
+    pub vault_policy: String,
 }
 
 impl WalletInfo{
-    pub fn new(pubkey: String, address: String, value: u64, inheritors: Vec<Inheritor>, guardians: Vec<Guardian>, locktime: u32) -> Self{
+    pub fn new(pubkey: String, address: String, value: u64, inheritors: Vec<Inheritor>, guardians: Vec<Guardian>, guardian_threshold: u8, locktime: u32) -> Self{
         WalletInfo {
             pubkey,
             address,
             value,
             inheritors,
             guardians,
+            guardian_threshold,
             locktime,
+            will: None,
+            revocation_settings: RevocationSettings::default(),
+            labels: Labels::default(),
+            initiation_data: String::new(),
+            watch: None,
+            watch_addresses: vec![],
+            vault_policy: String::new(),
         }    
     }
 
@@ -29,9 +70,89 @@ impl WalletInfo{
             value: 0,
             inheritors: vec![],
             guardians: vec![],
+            guardian_threshold: 0,
             locktime: 0,
+            will: None,
+            revocation_settings: RevocationSettings::default(),
+            labels: Labels::default(),
+            initiation_data: String::new(),
+            watch: None,
+            watch_addresses: vec![],
+            vault_policy: String::new(),
         }    
     }
+
+    pub fn is_watch_only(&self) -> bool{
+        self.watch.is_some()
+    }
+
+    //the addresses whose coins belong to the wallet
+    pub fn addresses(&self) -> Vec<String>{
+        match self.watch_addresses.is_empty(){
+            true => vec![self.address.clone()],
+            false => self.watch_addresses.clone()
+        }
+    }
+
+    //a copy of the wallet to give to someone who should see its balance, history and will but not spend
+    //the wallet file never holds the key, but it holds the signed revocation, which would let anyone with the copy
+    //take the inheritance back, and the parent's private labels, so both are left out
+    pub fn watch_only(&self) -> WalletInfo{
+        let mut watch_only = self.clone();
+        if let Some(will) = watch_only.will.as_mut(){
+            will.revocation_hex = String::new();
+        }
+        watch_only.labels = Labels::default();
+        if watch_only.watch.is_none(){
+            watch_only.watch = Some(WatchSource::Addresses(self.addresses()));
+            watch_only.watch_addresses = self.addresses();
+        }
+        watch_only
+    }
+
+    //the will initiation's OP_RETURN data, if it has any
+    pub fn op_return_data(&self) -> Result<Option<Vec<u8>>, WalletError>{
+        if self.initiation_data.is_empty(){
+            return Ok(None)
+        }
+        let data = self.initiation_data.to_bytes().map_err(|_| WalletError::Parse(format!("Will initiation data is not hex: {}", self.initiation_data)))?;
+        if data.len() > MAX_OP_RETURN_DATA{
+            return Err(WalletError::Policy(format!("Will initiation data is {} bytes, more than the standard {}", data.len(), MAX_OP_RETURN_DATA)))
+        }
+        Ok(Some(data))
+    }
+
+    //generation of the latest will parts, 0 before any will has been made
+    pub fn will_generation(&self) -> u32{
+        match &self.will{
+            Some(will) => will.generation,
+            None => 0
+        }
+    }
+
+    //names of inheritors and guardians still holding will parts older than the latest generation
+    pub fn outdated_holders(&self) -> Vec<String>{
+        let generation = self.will_generation();
+        let mut names: Vec<String> = Vec::new();
+        for inheritor in self.inheritors.iter().filter(|inheritor| inheritor.generation < generation){
+            names.push(inheritor.name.clone());
+        }
+        for guardian in self.guardians.iter().filter(|guardian| guardian.generation < generation){
+            names.push(guardian.name.clone());
+        }
+        names
+    }
+
+    //records that every inheritor and guardian has been given the latest will parts
+    pub fn mark_will_distributed(&mut self){
+        let generation = self.will_generation();
+        for inheritor in self.inheritors.iter_mut(){
+            inheritor.generation = generation;
+        }
+        for guardian in self.guardians.iter_mut(){
+            guardian.generation = generation;
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -39,23 +160,50 @@ pub struct Inheritor{
     pub name: String,
     pub address: String,
     pub id: String,
-    pub value: u64,
+    #[serde(default)]
+    pub share: u16,
+    #[serde(default)]
+    pub locktime: u32,
+    #[serde(default)]
+    pub generation: u32,
 }
 
 impl Inheritor{
-    pub fn new(name: String, address: String, id: String, value: u64) -> Self {
+    pub fn new(name: String, address: String, id: String, share: u16, locktime: u32) -> Self {
         Inheritor{
             name,
             address,
             id,
-            value,
+            share,
+            locktime,
+            generation: 0,
+        }
+    }
+
+    //share of the vault in percent, formatted from basis points
+    pub fn share_percent(&self) -> String{
+        format!("{}.{:02}%", self.share / 100, self.share % 100)
+    }
+
+    //whether the inheritor signed the message with the key of their address, proving they can spend what they inherit
+    pub fn verify_ownership(&self, message: &[u8], signature: &str) -> Result<bool, WalletError>{
+        verify_message(&self.address, message, signature)
+    }
+
+    //number of blocks this inheritor waits after the will is initiated, a locktime of 0 uses the will's redemption period
+    pub fn locktime_blocks(&self, default_locktime: u32) -> u32{
+        match self.locktime{
+            0 => default_locktime,
+            n => n
         }
     }
 }
 
 pub trait Inheritors{
     fn addresses(&self) -> Vec<String>;
-    fn amounts(&self) -> Vec<u64>;
+    fn shares(&self) -> Vec<u64>;
+    fn validate_shares(&self) -> Result<(), WalletError>;
+    fn tranches(&self, default_locktime: u32) -> Result<Vec<Tranche>, WalletError>;
 }
 
 impl Inheritors for Vec<Inheritor>{
@@ -68,29 +216,194 @@ impl Inheritors for Vec<Inheritor>{
         result
     }
 
-    //returns a vector containing the amount of satoshis being inherited by each inheritor
-    fn amounts(&self) -> Vec<u64>{
+    //returns a vector containing the share of the will (in basis points) being inherited by each inheritor
+    fn shares(&self) -> Vec<u64>{
         let mut result = Vec::new();
         for inheritor in self{
-            result.push(inheritor.value);
+            result.push(inheritor.share as u64);
         }
         result
     }
+
+    //checks that the inheritors' shares add up to the whole will
+    fn validate_shares(&self) -> Result<(), WalletError>{
+        let total: u64 = self.shares().iter().sum();
+        if total != TOTAL_SHARES{
+            return Err(WalletError::Policy(format!("Inheritor shares add up to {}.{:02}%, they must add up to 100%", total / 100, total % 100)))
+        }
+        if self.iter().any(|inheritor| inheritor.share == 0){
+            return Err(WalletError::Policy(String::from("Every inheritor must have a share of the will")))
+        }
+        Ok(())
+    }
+
+    //groups inheritors sharing the same redemption delay, ordered from the shortest delay to the longest
+    //fails if a delay is 0 or longer than a vault's relative timelock can be
+    fn tranches(&self, default_locktime: u32) -> Result<Vec<Tranche>, WalletError>{
+        let mut result: Vec<Tranche> = Vec::new();
+        for inheritor in self{
+            let locktime = vault_locktime(inheritor.locktime_blocks(default_locktime))?;
+            match result.iter_mut().find(|tranche| tranche.locktime_blocks == locktime){
+                Some(tranche) => {
+                    tranche.addresses.push(inheritor.address.clone());
+                    tranche.shares.push(inheritor.share as u64);
+                }
+                None => result.push(Tranche::new(locktime, vec![inheritor.address.clone()], vec![inheritor.share as u64]))
+            }
+        }
+        result.sort_by_key(|tranche| tranche.locktime_blocks);
+        Ok(result)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Guardian{
     pub name: String,
     pub id: String,
+    #[serde(default)]
+    pub pubkey: String,
+    #[serde(default)]
+    pub generation: u32,
 }
 
 impl Guardian{
-    pub fn new(name: String, id: String) -> Self {
+    pub fn new(name: String, id: String, pubkey: String) -> Self {
         Guardian{
             name,
             id,
+            pubkey,
+            generation: 0,
         }
     }
+
+    //whether the guardian signed the message with the key they registered, from any of its addresses
+    pub fn verify_ownership(&self, message: &[u8], signature: &str) -> Result<bool, WalletError>{
+        let pubkey = PublicKey::from_str(&self.pubkey).map_err(|_| WalletError::Policy(format!("{} has not registered a public key", self.name)))?;
+        verify_message_key(&pubkey, message, signature)
+    }
+}
+
+pub trait Guardians{
+    fn pubkeys(&self) -> Vec<String>;
+}
+
+impl Guardians for Vec<Guardian>{
+    //returns a vector containing the public keys of each guardian that has registered one
+    fn pubkeys(&self) -> Vec<String>{
+        let mut result = Vec::new();
+        for guardian in self{
+            if !guardian.pubkey.is_empty(){
+                result.push(guardian.pubkey.clone());
+            }
+        }
+        result
+    }
+}
+
+//the current will: which generation of will parts it is, the wallet outputs its initiation spends and the txids to watch for
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WillRecord{
+    pub generation: u32,
+    pub initiation_outpoints: Vec<Outpoint>,
+    #[serde(default)]
+    pub initiation_txid: String,
+    #[serde(default)]
+    pub redemptions: Vec<Redemption>,
+    #[serde(default)]
+    pub revocation_txid: String,
+    #[serde(default)]
+    pub revocation_hex: String,
+    #[serde(default)]
+    pub vault_descriptors: Vec<String>,
+}
+
+impl WillRecord{
+    pub fn new(generation: u32, will_parts: &WillParts) -> Self{
+        WillRecord {
+            generation,
+            initiation_outpoints: will_parts.initiation_outpoints.clone(),
+            initiation_txid: will_parts.initiation_txid.clone(),
+            redemptions: will_parts.redemptions.clone(),
+            revocation_txid: will_parts.revocation_txid.clone(),
+            revocation_hex: will_parts.revocation_hex.clone(),
+            vault_descriptors: will_parts.vault_descriptors.clone(),
+        }
+    }
+
+    //blocks the earliest tranche waits after the will initiation confirms before it can be redeemed
+    pub fn earliest_locktime(&self) -> u64{
+        self.redemptions.iter().map(|redemption| redemption.locktime_blocks as u64).min().unwrap_or(0)
+    }
+
+    //the will initiation only works while it spends exactly the wallet's unspent outputs: a spent outpoint makes it
+    //invalid, and a new outpoint would be left out of the will
+    pub fn is_stale(&self, utxos: &[UTXO]) -> bool{
+        let current: Vec<Outpoint> = utxos.iter().map(|utxo| Outpoint::new(&utxo.txid, utxo.vout)).collect();
+        current.len() != self.initiation_outpoints.len()
+            || current.iter().any(|outpoint| !self.initiation_outpoints.contains(outpoint))
+    }
+
+    //which part of this will the transaction is, if any
+    pub fn transaction_role(&self, txid: &str) -> Option<WillTransaction>{
+        if !self.initiation_txid.is_empty() && self.initiation_txid.eq_ignore_ascii_case(txid){
+            Some(WillTransaction::Initiation)
+        }else if !self.revocation_txid.is_empty() && self.revocation_txid.eq_ignore_ascii_case(txid){
+            Some(WillTransaction::Revocation)
+        }else if self.redemptions.iter().any(|redemption| redemption.txid.eq_ignore_ascii_case(txid)){
+            Some(WillTransaction::Redemption)
+        }else{
+            None
+        }
+    }
+}
+
+//what the revocation watcher does when the will is initiated and the parent does not check in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationAction{
+    Notify,
+    Broadcast,
+}
+
+impl RevocationAction{
+    pub const ALL: [RevocationAction;2] = [
+        RevocationAction::Notify,
+        RevocationAction::Broadcast,
+    ];
+}
+
+impl std::fmt::Display for RevocationAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RevocationAction::Notify => "Notify Me",
+                RevocationAction::Broadcast => "Broadcast Revocation",
+            }
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevocationSettings{
+    pub action: RevocationAction,
+    //blocks after an initiation is detected that the parent has to check in before the action is taken
+    pub window_blocks: u32,
+}
+
+impl RevocationSettings{
+    pub fn new(action: RevocationAction, window_blocks: u32) -> Self{
+        RevocationSettings {
+            action,
+            window_blocks,
+        }
+    }
+}
+
+impl Default for RevocationSettings{
+    fn default() -> Self{
+        RevocationSettings::new(RevocationAction::Notify, 6)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -106,21 +419,168 @@ impl WalletKeys{
     }
 }
 
+//one of the wallet's transactions, from the wallet's side
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TransactionHistory{
-    pub address: String,
-    pub to: bool,
-    pub value: u64,
+    pub txid: String,
+    //where the coins went when the wallet sent, where they came from when it received
+    pub counterparties: Vec<String>,
+    //satoshis received minus satoshis spent, so a payment includes its fee
+    pub net: i64,
+    pub fee: u64,
+    //none while the transaction is in the mempool
+    pub block_height: Option<u64>,
+    //block time, or when the block explorer first saw a pending transaction
+    pub time: u64,
     pub confirmations: u64,
+    pub will: Option<WillTransaction>,
 }
 
 impl TransactionHistory{
-    pub fn new(address: String, to: bool, value: u64, confirmations: u64) -> Self {
+    pub fn new(txid: String, counterparties: Vec<String>, net: i64, fee: u64, block_height: Option<u64>, time: u64, confirmations: u64) -> Self {
         TransactionHistory{
-            address,
-            to,
-            value,
+            txid,
+            counterparties,
+            net,
+            fee,
+            block_height,
+            time,
             confirmations,
+            will: None,
+        }
+    }
+
+    pub fn is_sent(&self) -> bool{
+        self.net < 0
+    }
+
+    pub fn is_pending(&self) -> bool{
+        self.block_height.is_none()
+    }
+
+    //the time as YYYY-MM-DD HH:MM in UTC
+    pub fn date(&self) -> String{
+        let days = (self.time / 86400) as i64;
+        let minutes = self.time % 86400 / 60;
+        //civil date from days since 1970-01-01, after Howard Hinnant's days_from_civil inverse
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+    }
+}
+
+//sorts transactions the way the block explorer lists them: pending ones first, then by block height, newest first
+pub fn sort_newest_first(history: &mut [TransactionHistory]){
+    history.sort_by_key(|transaction| std::cmp::Reverse(transaction.block_height.unwrap_or(u64::MAX)));
+}
+
+//the part of a will a transaction is
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum WillTransaction{
+    Initiation,
+    Redemption,
+    Revocation,
+}
+
+impl WillTransaction{
+    pub fn description(&self) -> &'static str{
+        match self{
+            WillTransaction::Initiation => "Will initiation",
+            WillTransaction::Redemption => "Will redemption",
+            WillTransaction::Revocation => "Will revocation",
         }
     }
-}
\ No newline at end of file
+}
+
+//marks the transactions that belong to any of the wills
+pub fn mark_will_transactions(history: &mut [TransactionHistory], wills: &[WillRecord]){
+    for transaction in history.iter_mut(){
+        transaction.will = wills.iter().find_map(|will| will.transaction_role(&transaction.txid));
+    }
+}
+
+//one page of the wallet's transactions, newest first
+#[derive(Debug, Clone)]
+pub struct HistoryPage{
+    pub transactions: Vec<TransactionHistory>,
+    //pages start at 1
+    pub page: u64,
+    pub total_pages: u64,
+}
+
+impl HistoryPage{
+    pub fn new(transactions: Vec<TransactionHistory>, page: u64, total_pages: u64) -> Self{
+        HistoryPage{
+            transactions,
+            page,
+            //an address without transactions still has an empty first page
+            total_pages: total_pages.max(1),
+        }
+    }
+
+    //a page of a whole history, newest first
+    pub fn of(history: Vec<TransactionHistory>, page: u64, page_size: u64) -> Self{
+        let (page, page_size) = (page.max(1), page_size.max(1));
+        let total_pages = (history.len() as u64).div_ceil(page_size);
+        let transactions = history.into_iter().skip((page - 1).saturating_mul(page_size) as usize).take(page_size as usize).collect();
+        HistoryPage::new(transactions, page, total_pages)
+    }
+
+    pub fn has_newer(&self) -> bool{
+        self.page > 1
+    }
+
+    pub fn has_older(&self) -> bool{
+        self.page < self.total_pages
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::labels::LabelType;
+    use crate::will_components::MAX_LOCKTIME_BLOCKS;
+
+    fn inheritor(share: u16, locktime: u32) -> Inheritor{
+        Inheritor::new(String::from("alice"), String::from("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn"), String::new(), share, locktime)
+    }
+
+    #[test]
+    fn tranches_group_by_delay(){
+        let inheritors = vec![inheritor(5000, 0), inheritor(3000, 4320), inheritor(2000, 0)];
+        let tranches = inheritors.tranches(MAX_LOCKTIME_BLOCKS).unwrap();
+        assert_eq!(tranches.iter().map(|tranche| tranche.locktime_blocks).collect::<Vec<u16>>(), vec![4320, 0xffff]);
+        assert_eq!(tranches[1].shares, vec![5000, 2000]);
+    }
+
+    #[test]
+    fn tranches_refuse_delays_a_vault_cannot_enforce(){
+        //two years of blocks would wrap to about nine months as a 16 bit timelock
+        assert!(matches!(vec![inheritor(10000, 105120)].tranches(52560), Err(WalletError::Policy(_))));
+        assert!(matches!(vec![inheritor(10000, 0)].tranches(MAX_LOCKTIME_BLOCKS + 1), Err(WalletError::Policy(_))));
+        assert!(matches!(vec![inheritor(10000, 0)].tranches(0), Err(WalletError::Policy(_))));
+    }
+
+    #[test]
+    fn watch_only_copy_cannot_revoke_the_will(){
+        let revocation_hex = "0200000001aabbccdd";
+        let mut wallet = WalletInfo::new(String::new(), String::from("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn"), 0, vec![inheritor(10000, 0)], vec![], 0, 52560);
+        let will_parts = WillParts::new(String::new(), String::new(), vec![], String::new(), vec![], String::from("feedface"), String::from(revocation_hex));
+        wallet.will = Some(WillRecord::new(1, &will_parts));
+        wallet.labels.set(LabelType::Addr, "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", "savings");
+
+        let json = serde_json::to_string(&wallet.watch_only()).unwrap();
+        assert!(!json.contains(revocation_hex), "{}", json);
+        assert!(!json.contains("savings"), "{}", json);
+        //the txid is still there, so the copy can show that the will was revoked
+        assert!(json.contains("feedface"));
+        assert_eq!(wallet.will.as_ref().unwrap().revocation_hex, revocation_hex);
+    }
+}
@@ -0,0 +1,155 @@
+use std::str::FromStr;
+
+use ring::hmac;
+use secp256k1::{Secp256k1, PublicKey, Scalar};
+
+use crate::create_transaction::{base58_to_hex, decode_address, hash160, wrap_p2pkh_testnet};
+use crate::descriptor::Descriptor;
+use crate::traits::*;
+use crate::error::WalletError;
+
+//addresses derived from an extended public key that has no address list of its own
+pub const WATCH_GAP: u32 = 20;
+//index of the first hardened child; they cannot be derived from a public key
+const HARDENED: u32 = 0x80000000;
+const XPUB_VERSION: [u8;4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB_VERSION: [u8;4] = [0x04, 0x35, 0x87, 0xcf];
+
+//a BIP32 extended public key, which derives the public keys of a wallet without being able to spend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Xpub{
+    pub depth: u8,
+    pub parent_fingerprint: [u8;4],
+    pub child_number: u32,
+    pub chain_code: [u8;32],
+    pub public_key: PublicKey,
+}
+
+impl FromStr for Xpub{
+    type Err = WalletError;
+
+    //an xpub or tpub in base58check
+    fn from_str(xpub: &str) -> Result<Self, WalletError>{
+        let bytes = base58_to_hex(xpub.trim()).map_err(|e| WalletError::Parse(format!("Invalid extended public key [Error: {}]", e)))?.to_bytes().map_err(WalletError::Parse)?;
+        if bytes.len() != 82{
+            return Err(WalletError::Parse(format!("Extended public key is {} bytes, not 82", bytes.len())))
+        }
+        let (payload, checksum) = bytes.split_at(78);
+        if payload.to_vec().sha256d()[0..4] != *checksum{
+            return Err(WalletError::Parse(String::from("Extended public key checksum failed")))
+        }
+        if payload[0..4] != XPUB_VERSION && payload[0..4] != TPUB_VERSION{
+            return Err(WalletError::Parse(String::from("Not an xpub or tpub; private keys cannot be watched")))
+        }
+        Ok(Xpub{
+            depth: payload[4],
+            parent_fingerprint: payload[5..9].try_into().unwrap(),
+            child_number: u32::from_be_bytes(payload[9..13].try_into().unwrap()),
+            chain_code: payload[13..45].try_into().unwrap(),
+            public_key: PublicKey::from_slice(&payload[45..78])?,
+        })
+    }
+}
+
+impl Xpub{
+    //the non-hardened child at index (BIP32 CKDpub)
+    pub fn derive(&self, index: u32) -> Result<Xpub, WalletError>{
+        if index >= HARDENED{
+            return Err(WalletError::Policy(format!("Hardened child {}' cannot be derived from a public key", index - HARDENED)))
+        }
+        let key = hmac::Key::new(hmac::HMAC_SHA512, &self.chain_code);
+        let data = [self.public_key.serialize().to_vec(), index.to_be_bytes().to_vec()].concat();
+        let tag = hmac::sign(&key, &data);
+        let (tweak, chain_code) = tag.as_ref().split_at(32);
+        let tweak = Scalar::from_be_bytes(tweak.try_into().unwrap()).map_err(|_| WalletError::Crypto(format!("Child {} is invalid; use the next index", index)))?;
+        Ok(Xpub{
+            depth: self.depth.checked_add(1).ok_or(WalletError::Policy(String::from("Extended key is too deep")))?,
+            parent_fingerprint: hash160(&self.public_key.to_string())[0..4].try_into().unwrap(),
+            child_number: index,
+            chain_code: chain_code.try_into().unwrap(),
+            public_key: self.public_key.add_exp_tweak(&Secp256k1::verification_only(), &tweak)?,
+        })
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Xpub, WalletError>{
+        let mut xpub = self.clone();
+        for index in path.iter(){
+            xpub = xpub.derive(*index)?;
+        }
+        Ok(xpub)
+    }
+
+    pub fn address(&self) -> String{
+        wrap_p2pkh_testnet(&self.public_key.to_string())
+    }
+}
+
+//reads a derivation path like 0/1, without a leading m
+pub(crate) fn parse_path(path: &str) -> Result<Vec<u32>, WalletError>{
+    path.split('/').filter(|step| !step.is_empty()).map(|step| match step.trim_end_matches(['\'', 'h']).parse::<u32>(){
+        Ok(_) if step.ends_with('\'') || step.ends_with('h') => Err(WalletError::Policy(format!("Hardened step {} cannot be derived from a public key", step))),
+        Ok(index) => Ok(index),
+        Err(_) => Err(WalletError::Parse(format!("Invalid derivation step: {}", step)))
+    }).collect()
+}
+
+//where a watch-only wallet's addresses come from
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchSource{
+    //receive addresses 0/i and change addresses 1/i of an extended public key
+    Xpub(String),
+    //an output descriptor, watched over WATCH_GAP addresses when it ends in a wildcard
+    Descriptor(String),
+    Addresses(Vec<String>),
+}
+
+impl WatchSource{
+    //an xpub, a descriptor, or addresses separated by commas or spaces
+    pub fn parse(source: &str) -> Result<Self, WalletError>{
+        let source = source.trim();
+        let watch = if source.starts_with("xpub") || source.starts_with("tpub"){
+            WatchSource::Xpub(source.to_string())
+        }else if source.contains('('){
+            WatchSource::Descriptor(source.to_string())
+        }else{
+            WatchSource::Addresses(source.split(|c: char| c == ',' || c.is_whitespace()).filter(|address| !address.is_empty()).map(String::from).collect())
+        };
+        watch.addresses()?;
+        Ok(watch)
+    }
+
+    //the addresses to watch, the first getting the change of unsigned transactions
+    pub fn addresses(&self) -> Result<Vec<String>, WalletError>{
+        let addresses = match self{
+            WatchSource::Xpub(xpub) => {
+                let xpub = Xpub::from_str(xpub)?;
+                [derive_addresses(&xpub, &[0])?, derive_addresses(&xpub, &[1])?].concat()
+            }
+            WatchSource::Descriptor(descriptor) => Descriptor::from_str(descriptor)?.addresses(WATCH_GAP)?,
+            WatchSource::Addresses(addresses) => addresses.clone(),
+        };
+        if addresses.is_empty(){
+            return Err(WalletError::Policy(String::from("There are no addresses to watch")))
+        }
+        for address in addresses.iter(){
+            decode_address(address)?;
+        }
+        Ok(addresses)
+    }
+
+    //the public key of the first address, when the source has one
+    pub fn first_pubkey(&self) -> Result<Option<String>, WalletError>{
+        Ok(match self{
+            WatchSource::Xpub(xpub) => Some(Xpub::from_str(xpub)?.derive_path(&[0, 0])?.public_key.to_string()),
+            WatchSource::Descriptor(descriptor) => Descriptor::from_str(descriptor)?.first_pubkey()?,
+            WatchSource::Addresses(_) => None,
+        })
+    }
+}
+
+//the first WATCH_GAP addresses under the path
+fn derive_addresses(xpub: &Xpub, path: &[u32]) -> Result<Vec<String>, WalletError>{
+    let parent = xpub.derive_path(path)?;
+    (0..WATCH_GAP).map(|i| Ok(parent.derive(i)?.address())).collect()
+}
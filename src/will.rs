@@ -0,0 +1,59 @@
+//! Building wills, following them on chain, and the inheritor and guardian sides of a will.
+
+use secp256k1::SecretKey;
+
+use crate::create_transaction::{create_will_parts, predict_will_parts};
+use crate::file::write_will_parts;
+use crate::transaction_parts::SignedTransaction;
+use crate::wallet_info::*;
+use crate::error::WalletError;
+
+pub use crate::will_components::{TimelockComponents, WillSpend, Tranche, Outpoint, Redemption, WillParts, split_by_shares, inheritance_amounts, vault_locktime, TOTAL_SHARES, DUST_LIMIT, MAX_LOCKTIME_BLOCKS};
+pub use crate::create_transaction::{generate_timelock_components, generate_tranche_timelocks, create_will_initiation, predict_will_initiation,
+    create_will_redemption, create_will_revocation, create_will_revocation_psbt};
+pub use crate::will_status::{WillState, will_state, off_chain_will_state};
+pub use crate::descriptor::vault_descriptor;
+pub use crate::policy::{vault_policy, custom_vault_policy};
+pub use crate::revocation_watcher::{RevocationWatcher, WatchEvent, WatcherCommand, spawn_watcher};
+pub use crate::claim::{WillPackage, Claim, ClaimStatus, RedemptionStatus, csv_delay, spawn_claim};
+pub use crate::guardian::{GuardianPackage, GuardianStatus, Revocation, WatchedVault, guardian_status, broadcast_revocation, spawn_guardian_watch};
+
+//new vaults, each with its own single-use key, for the tranches of the wallet's inheritors
+pub fn will_vaults(will_info: &WalletInfo) -> Result<Vec<TimelockComponents>, WalletError>{
+    generate_tranche_timelocks(&will_info.pubkey, &will_info.inheritors.tranches(will_info.locktime)?, will_info.guardians.pubkeys(), will_info.guardian_threshold, &will_info.vault_policy)
+}
+
+//creates will parts from the wallet's current coins and records them as the next will generation
+pub fn create_will(will_info: &mut WalletInfo, secret_key: SecretKey) -> Result<(), WalletError>{
+    let vaults = will_vaults(will_info)?;
+    create_will_with_vaults(will_info, secret_key, vaults)
+}
+
+//creates will parts locking the coins in vaults that were generated beforehand, like ones the user has reviewed
+pub fn create_will_with_vaults(will_info: &mut WalletInfo, secret_key: SecretKey, vaults: Vec<TimelockComponents>) -> Result<(), WalletError>{
+    let will_parts = create_will_parts(will_info.inheritors.tranches(will_info.locktime)?, vaults, &will_spend(will_info, secret_key)?)?;
+    record_will_parts(will_info, will_parts)
+}
+
+//creates will parts spending the change of a transaction that has not confirmed yet
+pub fn predict_will(will_info: &mut WalletInfo, transaction: SignedTransaction, secret_key: SecretKey) -> Result<(), WalletError>{
+    let vaults = will_vaults(will_info)?;
+    let will_parts = predict_will_parts(will_info.inheritors.tranches(will_info.locktime)?, vaults, transaction, &will_spend(will_info, secret_key)?)?;
+    record_will_parts(will_info, will_parts)
+}
+
+//what the wallet's will initiation spends: its coins that are not frozen, committing to its OP_RETURN data
+fn will_spend(will_info: &WalletInfo, secret_key: SecretKey) -> Result<WillSpend, WalletError>{
+    let spend = WillSpend::new(&will_info.address, &will_info.pubkey, secret_key).with_coins(will_info.coin_control());
+    Ok(match will_info.op_return_data()?{
+        Some(data) => spend.with_data(data),
+        None => spend
+    })
+}
+
+//stores which coins the new will initiation spends and writes the will parts files
+pub fn record_will_parts(will_info: &mut WalletInfo, will_parts: WillParts) -> Result<(), WalletError>{
+    let generation = will_info.will_generation() + 1;
+    will_info.will = Some(WillRecord::new(generation, &will_parts));
+    write_will_parts(will_parts, generation)
+}
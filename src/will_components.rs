@@ -1,5 +1,11 @@
-use secp256k1::{SecretKey, PublicKey};
+use secp256k1::{Secp256k1, SecretKey, PublicKey};
+use crate::descriptor::Miniscript;
+use crate::coin_control::CoinControl;
+use crate::create_transaction::{hash160, wrap_p2wsh, wrap_segwit_testnet};
+use crate::script::Script;
+use crate::transaction_parts::StackItem;
 use crate::traits::*;
+use crate::error::WalletError;
 
 #[derive(Debug, Clone)]
 pub struct TimelockComponents {
@@ -9,24 +15,273 @@ pub struct TimelockComponents {
     pub sequence_flags: [u8;2],
     pub witness_script: Vec<u8>,
     pub locking_script: Vec<u8>,
+    pub guardian_pubkeys: Vec<String>,
+    pub guardian_threshold: u8,
+    //the compiled vault policy the witness script was encoded from
+    pub miniscript: Miniscript,
 }
 
     impl TimelockComponents{
-        pub fn new(single_use_private_key: SecretKey, single_use_public_key: PublicKey, locktime: u16, witness_script: Vec<u8>, locking_script: Vec<u8>) -> Result<Self, String>{
+        //the vault of the compiled policy, with the scripts encoded from it
+        pub fn new(single_use_private_key: SecretKey, locktime: u16, miniscript: Miniscript, guardian_pubkeys: Vec<String>, guardian_threshold: u8) -> Result<Self, WalletError>{
+            let witness_script = miniscript.encode(0)?;
             Ok(TimelockComponents{
                 single_use_private_key,
-                single_use_public_key,
+                single_use_public_key: PublicKey::from_secret_key(&Secp256k1::new(), &single_use_private_key),
                 sequence_locktime: locktime.to_le_bytes(),
                 sequence_flags: [0u8;2],
+                locking_script: wrap_p2wsh(witness_script.clone()),
                 witness_script,
-                locking_script,
+                guardian_pubkeys,
+                guardian_threshold,
+                miniscript,
             })
         }
 
+        pub fn locktime_blocks(&self) -> u16 {
+            u16::from_le_bytes(self.sequence_locktime)
+        }
+
+        //the witness spending the vault with these DER signatures once it is age blocks old, ending in the witness script
+        pub fn witness(&self, signatures: &[(PublicKey, Vec<u8>)], age: u32) -> Result<Vec<StackItem>, WalletError> {
+            //SIGHASH_ALL
+            let sign = |pubkey: &PublicKey| signatures.iter().find(|(key, _)| key == pubkey).map(|(_, signature)| [signature.clone(), vec![1u8]].concat());
+            let mut stack: Vec<StackItem> = self.miniscript.satisfy(0, &sign, age)?.into_iter()
+                .map(|item| if item.is_empty() { StackItem::OP([0u8]) } else { StackItem::Data(item) }).collect();
+            stack.push(StackItem::Data(self.witness_script.clone()));
+            Ok(stack)
+        }
+
+        //the witness script as text, like OP_IF <parent> OP_CHECKSIG OP_ELSE 52560 OP_CHECKSEQUENCEVERIFY ...
+        pub fn asm(&self) -> String {
+            Script::from_bytes(self.witness_script.clone()).asm()
+        }
+
+        //the testnet P2WSH address the will initiation pays the vault to
+        pub fn address(&self) -> String {
+            wrap_segwit_testnet(0, &self.witness_script.sha256())
+        }
+
+        //the first 4 bytes of the HASH160 of the single-use key, as a BIP32 key fingerprint
+        pub fn single_use_fingerprint(&self) -> String {
+            hash160(&self.single_use_public_key.to_string())[0..4].iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+
+        //true if the revocation branch of the vault is an m-of-n guardian multisig in addition to the parent key
+        pub fn has_guardians(&self) -> bool {
+            !self.guardian_pubkeys.is_empty()
+        }
+
         pub fn sequence(&self) -> Vec<u8> {
             let mut result: Vec<u8> = Vec::new();
             result.extend_from_slice(&self.sequence_locktime);
             result.extend_from_slice(&self.sequence_flags);
             result
         }
-    }
\ No newline at end of file
+    }
+
+//the parent's side of a will initiation: the wallet it spends from, the data it commits to and the coins it may spend
+#[derive(Debug, Clone)]
+pub struct WillSpend {
+    pub address: String,
+    pub pubkey: String,
+    pub secret_key: SecretKey,
+    pub data: Option<Vec<u8>>,
+    pub coins: CoinControl,
+}
+
+    impl WillSpend{
+        pub fn new(address: &str, pubkey: &str, secret_key: SecretKey) -> Self{
+            WillSpend{
+                address: address.to_string(),
+                pubkey: pubkey.to_string(),
+                secret_key,
+                data: None,
+                coins: CoinControl::default(),
+            }
+        }
+
+        pub fn with_data(mut self, data: Vec<u8>) -> Self{
+            self.data = Some(data);
+            self
+        }
+
+        pub fn with_coins(mut self, coins: CoinControl) -> Self{
+            self.coins = coins;
+            self
+        }
+    }
+
+//inheritor shares are in basis points of the will
+pub const TOTAL_SHARES: u64 = 10000;
+//outputs smaller than this are not relayed by the network
+pub const DUST_LIMIT: u64 = 546;
+//the longest relative timelock in blocks, as BIP68 keeps 16 bits of the input sequence for it (about 15 months)
+pub const MAX_LOCKTIME_BLOCKS: u32 = 0xffff;
+
+//a redemption delay as the relative timelock of a vault, refusing no delay and delays too long to be enforced
+pub fn vault_locktime(blocks: u32) -> Result<u16, WalletError>{
+    match u16::try_from(blocks){
+        Ok(blocks) if blocks > 0 => Ok(blocks),
+        _ => Err(WalletError::Policy(format!("A redemption delay must be between 1 and {} blocks, not {}", MAX_LOCKTIME_BLOCKS, blocks)))
+    }
+}
+
+//inheritors sharing one vault output of the will initiation and one redemption delay
+#[derive(Debug, Clone)]
+pub struct Tranche {
+    pub locktime_blocks: u16,
+    pub addresses: Vec<String>,
+    pub shares: Vec<u64>,
+}
+
+    impl Tranche{
+        pub fn new(locktime_blocks: u16, addresses: Vec<String>, shares: Vec<u64>) -> Self{
+            Tranche{
+                locktime_blocks,
+                addresses,
+                shares,
+            }
+        }
+
+        pub fn total_share(&self) -> u64 {
+            self.shares.iter().sum()
+        }
+
+        //splits the satoshis left in the tranche's vault after the redemption fee between its inheritors
+        pub fn amounts(&self, satoshis: u64) -> Result<Vec<u64>, WalletError> {
+            inheritance_amounts(satoshis, &self.shares)
+        }
+    }
+
+//splits an amount exactly in proportion to shares; satoshis left over from rounding down go one each to the
+//largest fractional parts, ties going to the earlier share
+pub fn split_by_shares(total: u64, shares: &[u64]) -> Result<Vec<u64>, WalletError>{
+    let share_total: u64 = shares.iter().sum();
+    if share_total == 0{
+        return Err(WalletError::Policy(String::from("Cannot split coins between zero shares")))
+    }
+    let mut amounts: Vec<u64> = Vec::new();
+    let mut remainders: Vec<(u64, usize)> = Vec::new();
+    for (i, share) in shares.iter().enumerate(){
+        let exact = total as u128 * *share as u128;
+        amounts.push((exact / share_total as u128) as u64);
+        remainders.push(((exact % share_total as u128) as u64, i));
+    }
+    let leftover = total - amounts.iter().sum::<u64>();
+    remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, i) in remainders.into_iter().take(leftover as usize){
+        amounts[i] += 1;
+    }
+    Ok(amounts)
+}
+
+//splits an amount between inheritors by share; shares that would be worth less than the dust limit are dropped (amount 0)
+//and their coins are split between the remaining inheritors
+pub fn inheritance_amounts(total: u64, shares: &[u64]) -> Result<Vec<u64>, WalletError>{
+    let mut shares = shares.to_vec();
+    loop{
+        let amounts = split_by_shares(total, &shares)?;
+        let dust: Vec<usize> = (0..amounts.len()).filter(|&i| shares[i] > 0 && amounts[i] < DUST_LIMIT).collect();
+        if dust.is_empty(){
+            return Ok(amounts)
+        }
+        if dust.len() == shares.iter().filter(|&&share| share > 0).count(){
+            return Err(WalletError::Policy(format!("{} satoshis is too little to pay any inheritor more than the dust limit", total)))
+        }
+        //drop the smallest dust share and try again, as the others may no longer be dust
+        let smallest = *dust.iter().min_by_key(|&&i| (shares[i], i)).unwrap();
+        shares[smallest] = 0;
+    }
+}
+
+
+//a transaction output being spent, with the txid in the usual reversed (display) byte order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Outpoint {
+    pub txid: String,
+    pub vout: u32,
+}
+
+    impl Outpoint{
+        pub fn new(txid: &str, vout: u32) -> Self{
+            Outpoint{
+                txid: txid.to_lowercase(),
+                vout,
+            }
+        }
+    }
+
+//a will redemption and the number of blocks its vault is locked for after the will initiation confirms
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Redemption {
+    pub locktime_blocks: u16,
+    pub txid: String,
+}
+
+    impl Redemption{
+        pub fn new(locktime_blocks: u16, txid: String) -> Self{
+            Redemption{
+                locktime_blocks,
+                txid,
+            }
+        }
+    }
+
+//the parts of a will to hand out, the wallet outputs its initiation spends, the txids to watch the chain for and the parent's signed revocation
+#[derive(Debug, Clone)]
+pub struct WillParts {
+    pub child_will_parts: String,
+    pub guardian_will_parts: String,
+    pub initiation_outpoints: Vec<Outpoint>,
+    pub initiation_txid: String,
+    pub redemptions: Vec<Redemption>,
+    pub revocation_txid: String,
+    pub revocation_hex: String,
+    //one output descriptor per vault, for watching or recovering the vaults with other wallets
+    pub vault_descriptors: Vec<String>,
+}
+
+    impl WillParts{
+        pub fn new(child_will_parts: String, guardian_will_parts: String, initiation_outpoints: Vec<Outpoint>, initiation_txid: String, redemptions: Vec<Redemption>, revocation_txid: String, revocation_hex: String) -> Self{
+            WillParts{
+                child_will_parts,
+                guardian_will_parts,
+                initiation_outpoints,
+                initiation_txid,
+                redemptions,
+                revocation_txid,
+                revocation_hex,
+                vault_descriptors: vec![],
+            }
+        }
+
+        pub fn with_vault_descriptors(mut self, vault_descriptors: Vec<String>) -> Self{
+            self.vault_descriptors = vault_descriptors;
+            self
+        }
+    }
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn leftover_satoshis_go_to_the_largest_remainders(){
+        assert_eq!(split_by_shares(100, &[3333, 3333, 3334]).unwrap(), vec![33, 33, 34]);
+        assert_eq!(split_by_shares(101, &[5000, 5000]).unwrap(), vec![51, 50]);
+        //equal remainders go to the earlier shares
+        assert_eq!(split_by_shares(10, &[1, 1, 1]).unwrap(), vec![4, 3, 3]);
+        assert_eq!(split_by_shares(11, &[1, 1, 1]).unwrap(), vec![4, 4, 3]);
+        assert_eq!(split_by_shares(u64::MAX, &[1, 2]).unwrap().iter().map(|amount| *amount as u128).sum::<u128>(), u64::MAX as u128);
+        assert!(split_by_shares(100, &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn dust_shares_are_dropped(){
+        assert_eq!(inheritance_amounts(10000, &[9900, 100]).unwrap(), vec![10000, 0]);
+        //once the smallest share is dropped the next one is no longer dust
+        assert_eq!(inheritance_amounts(3000, &[7000, 2500, 500]).unwrap(), vec![2211, 789, 0]);
+        assert!(matches!(inheritance_amounts(1000, &[5000, 5000]), Err(WalletError::Policy(_))));
+        assert_eq!(inheritance_amounts(100000, &[5000, 5000]).unwrap(), vec![50000, 50000]);
+    }
+}
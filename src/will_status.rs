@@ -0,0 +1,82 @@
+use crate::blockchain_info::ChainBackend;
+use crate::wallet_info::*;
+use crate::error::WalletError;
+
+//where the current will is in its lifecycle
+#[derive(Debug, Clone, PartialEq)]
+pub enum WillState{
+    NoWill,
+    //will parts have been made but not every inheritor and guardian has the latest ones
+    Drafted,
+    Distributed,
+    //the will initiation has been broadcast; the parent can still revoke until the redeemable height
+    Initiated{height: u64, redeemable_height: u64, blocks_left: u64},
+    //the earliest tranche can be redeemed in the next block
+    Redeemable{height: u64, redeemable_height: u64},
+    Redeemed,
+    Revoked,
+}
+
+impl WillState{
+    pub fn description(&self) -> String{
+        match self{
+            WillState::NoWill => String::from("No will has been made"),
+            WillState::Drafted => String::from("Drafted: the latest will parts have not been given out"),
+            WillState::Distributed => String::from("Distributed: waiting for the will to be initiated"),
+            WillState::Initiated{height, redeemable_height, ..} => format!("Initiated at block {}, redeemable at block {}", height, redeemable_height),
+            WillState::Redeemable{height, redeemable_height} => format!("Initiated at block {}, redeemable since block {}", height, redeemable_height),
+            WillState::Redeemed => String::from("Redeemed by the inheritors"),
+            WillState::Revoked => String::from("Revoked"),
+        }
+    }
+
+    //warning for the parent when someone has started the will
+    pub fn alert(&self) -> Option<String>{
+        match self{
+            WillState::Initiated{blocks_left, ..} => Some(format!("Your will has been initiated! You have {} blocks left to revoke it before your inheritors can redeem it.", blocks_left)),
+            WillState::Redeemable{..} => Some(String::from("Your will has been initiated and can now be redeemed. Revoking it is a race against the redemption.")),
+            _ => None
+        }
+    }
+}
+
+//state of a will that has not been seen on chain
+pub fn off_chain_will_state(will_info: &WalletInfo) -> WillState{
+    match will_info.will{
+        None => WillState::NoWill,
+        Some(_) if will_info.outdated_holders().is_empty() => WillState::Distributed,
+        Some(_) => WillState::Drafted,
+    }
+}
+
+//looks up the will's revocation, redemptions and initiation to find its current state
+pub fn will_state(will_info: &WalletInfo, backend: &impl ChainBackend) -> Result<WillState, WalletError>{
+    let will = match &will_info.will{
+        Some(will) => will,
+        None => return Ok(WillState::NoWill)
+    };
+
+    if backend.transaction(&will.revocation_txid)?.is_some(){
+        return Ok(WillState::Revoked)
+    }
+    for redemption in will.redemptions.iter(){
+        if backend.transaction(&redemption.txid)?.is_some(){
+            return Ok(WillState::Redeemed)
+        }
+    }
+
+    let initiation = match backend.transaction(&will.initiation_txid)?{
+        Some(initiation) => initiation,
+        None => return Ok(off_chain_will_state(will_info))
+    };
+    let tip = backend.tip_height()?;
+    //an initiation still in the mempool confirms in the next block at the earliest
+    let height = if initiation.confirmations > 0 { initiation.block_height as u64 } else { tip + 1 };
+    let redeemable_height = height + will.earliest_locktime();
+
+    //a relative timelock of n blocks lets the redemption into block height + n, so the revocation has to confirm before it
+    match redeemable_height.checked_sub(tip + 1){
+        Some(blocks_left) if blocks_left > 0 => Ok(WillState::Initiated{height, redeemable_height, blocks_left}),
+        _ => Ok(WillState::Redeemable{height, redeemable_height}),
+    }
+}